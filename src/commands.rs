@@ -1,32 +1,43 @@
-use sea_orm::DbErr;
+use sea_orm::{DbErr, TransactionTrait};
 use sea_orm_migration::prelude::*;
 use std::collections::HashMap;
 use std::future::Future;
+use std::path::Path;
 use std::pin::Pin;
 
+use crate::config::Config;
+use crate::db::entities::api_key::Role;
 use crate::db::{database, migrator};
-use crate::serve::serve_main;
+use crate::serve::item::schema::{SchemaError, SchemaFile, SchemaFormat};
+use crate::serve::{auth, serve_main};
 
 
 
-type AsyncFn = fn() -> Pin<Box<dyn Future<Output = ()> + Send>>;
+// Commands receive the arguments that follow them up to the next command
+type AsyncFn = fn(Config, Vec<String>) -> Pin<Box<dyn Future<Output = ()> + Send>>;
 
 pub fn get_commands() -> HashMap<&'static str, AsyncFn> {
-    let help_fn: AsyncFn = || Box::pin(async { help() });
-    let migrate_fn: AsyncFn = || Box::pin(async { do_migrate().await });
-    let serve_fn: AsyncFn = || Box::pin(serve_main::start_server());
+    let help_fn: AsyncFn = |_, _| Box::pin(async { help() });
+    let migrate_fn: AsyncFn = |config, _| Box::pin(do_migrate(config));
+    let serve_fn: AsyncFn = |config, _| Box::pin(serve_main::start_server(config));
+    let admin_key_fn: AsyncFn = |config, _| Box::pin(create_admin_key(config));
+    let import_fn: AsyncFn = |config, args| Box::pin(import_schema(config, args));
+    let export_fn: AsyncFn = |config, args| Box::pin(export_schema(config, args));
     
-    return HashMap::from([
+    HashMap::from([
         ("--help", help_fn),
         ("-h", help_fn),
         ("--migrate", migrate_fn),
         ("--serve", serve_fn),
-    ]);
+        ("--create-admin-key", admin_key_fn),
+        ("--import-schema", import_fn),
+        ("--export-schema", export_fn),
+    ])
 }
 
-async fn do_migrate() {
-    async fn run() -> Result<(), DbErr> {
-        let db = database::set_up_db().await.map_err(|e| {
+async fn do_migrate(config: Config) {
+    async fn run(config: &Config) -> Result<(), DbErr> {
+        let db = database::set_up_db(config).await.map_err(|e| {
             eprintln!("Error: Could not connect to the database. Reason: {:?}", e);
             e
         })?;
@@ -38,12 +49,68 @@ async fn do_migrate() {
 
         Ok(())
     }
-    match run().await {
+    match run(&config).await {
         Err(e) => eprintln!("Error: Could not migrate database!, \n Reason: {e}"),
         Ok(()) => println!("Success, database migrated!"),
     }
 }
 
+async fn create_admin_key(config: Config) {
+    async fn run(config: &Config) -> Result<String, DbErr> {
+        let db = database::set_up_db(config).await?;
+        let (_, key) = auth::issue_key(&db, None, Role::Admin).await?;
+        Ok(key)
+    }
+    match run(&config).await {
+        Err(e) => eprintln!("Error: Could not create admin key!, \n Reason: {e}"),
+        Ok(key) => println!("Admin key (shown only once): {key}"),
+    }
+}
+
+async fn import_schema(config: Config, args: Vec<String>) {
+    let dry_run = args.iter().any(|arg| arg == "--dry-run");
+    let Some(path) = args.iter().find(|arg| !arg.starts_with("--")) else {
+        eprintln!("Error: --import-schema needs a schema file, e.g. --import-schema items.toml");
+        return;
+    };
+
+    async fn run(config: &Config, path: &Path, dry_run: bool) -> Result<String, SchemaError> {
+        let format = SchemaFormat::from_path(path)?;
+        let schema = SchemaFile::parse(&std::fs::read_to_string(path)?, format)?;
+
+        let db = database::set_up_db(config).await?;
+        let txn = db.begin().await?;
+        let report = schema.import(&txn, dry_run).await?;
+        if !dry_run {
+            txn.commit().await?;
+        }
+        Ok(report.to_string())
+    }
+    match run(&config, Path::new(path), dry_run).await {
+        Err(e) => eprintln!("Error: Could not import item schema!, \n Reason: {e}"),
+        Ok(report) => println!("{report}"),
+    }
+}
+
+async fn export_schema(config: Config, args: Vec<String>) {
+    let Some(path) = args.first() else {
+        eprintln!("Error: --export-schema needs a schema file, e.g. --export-schema items.toml");
+        return;
+    };
+
+    async fn run(config: &Config, path: &Path) -> Result<usize, SchemaError> {
+        let format = SchemaFormat::from_path(path)?;
+        let db = database::set_up_db(config).await?;
+        let schema = SchemaFile::export(&db).await?;
+        std::fs::write(path, schema.render(format)?)?;
+        Ok(schema.items.len())
+    }
+    match run(&config, Path::new(path)).await {
+        Err(e) => eprintln!("Error: Could not export item schema!, \n Reason: {e}"),
+        Ok(count) => println!("Success, exported {count} items to {path}!"),
+    }
+}
+
 fn help() {
     fn print_command(command: &str, description: &str) {
         println!("   {command}    {description}");
@@ -51,5 +118,10 @@ fn help() {
     println!("--------Welcome to Ventil!--------");
     println!("Commands:");
     print_command("--help", "Display this menu");
+    print_command("--config <file>", "Read settings from this file instead of Ventil.toml");
     print_command("--migrate", "Apply migrations");
+    print_command("--serve", "Start the API server");
+    print_command("--create-admin-key", "Issue a new admin API key");
+    print_command("--import-schema <file> [--dry-run]", "Create or update items from a .json or .toml schema");
+    print_command("--export-schema <file>", "Write all items to a .json or .toml schema");
 }
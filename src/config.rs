@@ -0,0 +1,96 @@
+use rocket::figment::{
+    Figment,
+    providers::{Env, Format, Serialized, Toml},
+};
+use rocket::serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+
+// Read when neither `--config` nor `VENTIL_CONFIG` name another file
+pub const DEFAULT_CONFIG_FILE: &str = "Ventil.toml";
+
+// Settings of a Ventil instance.
+// Merged, last one wins: built-in defaults, Rocket's own figment (Rocket.toml and ROCKET_ vars),
+// the profile sections of Ventil.toml and finally VENTIL_ environment variables,
+// e.g. VENTIL_DATABASE_URL or VENTIL_PORT.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(crate = "rocket::serde")]
+pub struct Config {
+    pub database_url: String,
+    // SQLite only handles one writer at a time, so a single connection is the default
+    pub database_pool_size: u32,
+    pub address: IpAddr,
+    pub port: u16,
+    // Seconds until a new trade expires unless the request sets its own, 0 never expires
+    pub trade_expiry_secs: u64,
+    // Seconds between two sweeps for expired trades
+    pub trade_sweep_interval_secs: u64,
+    // Seconds both traders have to wait after getting ready before a trade can be confirmed
+    pub trade_confirm_cooldown_secs: u64,
+    // Seconds possessions of an executed trade are held in escrow, 0 delivers them right away
+    pub trade_hold_secs: u64,
+    // Trades are held when a trader's account is younger than this many seconds
+    pub trade_hold_account_age_secs: u64,
+    // Owner receiving the possessions of owners and items deleted with the graveyard policy
+    pub graveyard_owner_id: Option<i32>,
+    // Seconds the response of a request with an Idempotency-Key is kept for retries
+    pub idempotency_ttl_secs: u64,
+    // Seconds a key stays claimed by a request that never stored its response, e.g. after a crash
+    pub idempotency_claim_timeout_secs: u64,
+    // Currencies owners can hold in their wallets and offer in trades
+    pub currencies: Vec<String>,
+    // Most drops a loot box may roll, opening one grants up to this many possessions
+    pub max_lootbox_rolls: i32,
+    // File the settings were read from
+    #[serde(skip)]
+    pub source: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            database_url: "sqlite:./ventil.db?mode=rwc".to_string(),
+            database_pool_size: 1,
+            address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 8000,
+            trade_expiry_secs: 86400,
+            trade_sweep_interval_secs: 60,
+            trade_confirm_cooldown_secs: 5,
+            trade_hold_secs: 0,
+            trade_hold_account_age_secs: 604800,
+            graveyard_owner_id: None,
+            idempotency_ttl_secs: 86400,
+            idempotency_claim_timeout_secs: 60,
+            currencies: vec!["gold".to_string()],
+            max_lootbox_rolls: 100,
+            source: PathBuf::from(DEFAULT_CONFIG_FILE),
+        }
+    }
+}
+
+impl Config {
+    // The full figment, also handed to Rocket so its own settings can live in Ventil.toml
+    pub fn figment(path: &Path) -> Figment {
+        Figment::from(Serialized::defaults(Config::default()))
+            .merge(rocket::Config::figment())
+            .merge(Toml::file(path).nested())
+            .merge(Env::prefixed("VENTIL_").ignore(&["config"]).global())
+    }
+
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<rocket::figment::Error>> {
+        // Only the default file may be missing, a file that was asked for has to exist
+        let path = match path {
+            Some(path) if !path.exists() => {
+                return Err(Box::new(format!("{} does not exist", path.display()).into()));
+            }
+            Some(path) => path.to_path_buf(),
+            None => std::env::var_os("VENTIL_CONFIG")
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE)),
+        };
+
+        let mut config: Config = Config::figment(&path).extract()?;
+        config.source = path;
+        Ok(config)
+    }
+}
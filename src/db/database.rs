@@ -1,10 +1,64 @@
-use sea_orm::{Database, DatabaseConnection, DbErr};
+use crate::config::Config;
+use sea_orm::{ConnectOptions, Database, DatabaseConnection, DbErr};
 
-const DATABASE_URL: &str = "sqlite:./ventil.db?mode=rwc";
-//const DB_NAME: &str = "ventil_db";
+// Database backends, chosen by the scheme of the connection URL.
+// Each one is only available when Ventil is built with its Cargo feature.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Backend {
+    Sqlite,
+    Postgres,
+    MySql,
+}
+
+impl Backend {
+    pub fn from_url(url: &str) -> Result<Backend, DbErr> {
+        let scheme = url.split_once(':').map(|(scheme, _)| scheme).unwrap_or_default();
+
+        let backend = match scheme {
+            "sqlite" => Backend::Sqlite,
+            "postgres" | "postgresql" => Backend::Postgres,
+            "mysql" => Backend::MySql,
+            _ => {
+                return Err(DbErr::Custom(format!(
+                    "Unsupported database URL scheme {:?}, expected sqlite, postgres or mysql",
+                    scheme
+                )));
+            }
+        };
+
+        if !backend.is_enabled() {
+            return Err(DbErr::Custom(format!(
+                "Ventil was built without the {} feature",
+                backend.feature()
+            )));
+        }
+
+        Ok(backend)
+    }
+
+    pub fn feature(self) -> &'static str {
+        match self {
+            Backend::Sqlite => "sqlite",
+            Backend::Postgres => "postgres",
+            Backend::MySql => "mysql",
+        }
+    }
+
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Backend::Sqlite => cfg!(feature = "sqlite"),
+            Backend::Postgres => cfg!(feature = "postgres"),
+            Backend::MySql => cfg!(feature = "mysql"),
+        }
+    }
+}
+
+pub async fn set_up_db(config: &Config) -> Result<DatabaseConnection, DbErr> {
+    Backend::from_url(&config.database_url)?;
 
+    let mut options = ConnectOptions::new(config.database_url.clone());
+    options.max_connections(config.database_pool_size);
 
-pub async fn set_up_db() -> Result<DatabaseConnection, DbErr> {
-    let db = Database::connect(DATABASE_URL).await?;
+    let db = Database::connect(options).await?;
     Ok(db)
 }
@@ -0,0 +1,70 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Held possessions wait for their release time, the other states are final
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum EscrowState {
+    #[default]
+    #[sea_orm(string_value = "held")]
+    Held,
+    #[sea_orm(string_value = "delivered")]
+    Delivered,
+    #[sea_orm(string_value = "returned")]
+    Returned,
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "escrow")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub trade_history: i32,
+    pub possession: i32,
+    pub from_owner: i32,
+    pub to_owner: i32,
+    pub state: EscrowState,
+    pub held_at: DateTimeUtc,
+    pub release_at: DateTimeUtc,
+    pub settled_at: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::FromOwner",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    FromOwner,
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::ToOwner",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    ToOwner,
+    #[sea_orm(
+        belongs_to = "super::trade_history::Entity",
+        from = "Column::TradeHistory",
+        to = "super::trade_history::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    TradeHistory,
+}
+
+impl Related<super::trade_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeHistory.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
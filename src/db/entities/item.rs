@@ -1,6 +1,62 @@
-//! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.7
-
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum Quality {
+    #[default]
+    #[sea_orm(string_value = "normal")]
+    Normal,
+    #[sea_orm(string_value = "unique")]
+    Unique,
+    #[sea_orm(string_value = "vintage")]
+    Vintage,
+    #[sea_orm(string_value = "genuine")]
+    Genuine,
+    #[sea_orm(string_value = "strange")]
+    Strange,
+    #[sea_orm(string_value = "unusual")]
+    Unusual,
+    #[sea_orm(string_value = "haunted")]
+    Haunted,
+    #[sea_orm(string_value = "collectors")]
+    Collectors,
+    #[sea_orm(string_value = "community")]
+    Community,
+    #[sea_orm(string_value = "self_made")]
+    SelfMade,
+    #[sea_orm(string_value = "valve")]
+    Valve,
+}
+
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum Rarity {
+    #[default]
+    #[sea_orm(string_value = "common")]
+    Common,
+    #[sea_orm(string_value = "uncommon")]
+    Uncommon,
+    #[sea_orm(string_value = "rare")]
+    Rare,
+    #[sea_orm(string_value = "mythical")]
+    Mythical,
+    #[sea_orm(string_value = "legendary")]
+    Legendary,
+    #[sea_orm(string_value = "ancient")]
+    Ancient,
+    #[sea_orm(string_value = "immortal")]
+    Immortal,
+    #[sea_orm(string_value = "arcana")]
+    Arcana,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "item")]
@@ -8,14 +64,50 @@ pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
     pub item_type: String,
+    pub name: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub description: Option<String>,
+    pub quality: Quality,
+    pub rarity: Rarity,
+    pub slot: Option<String>,
+    pub tradable: bool,
+    pub marketable: bool,
+    // Possessions of stackable items hold a quantity, grants add to an existing stack
+    pub stackable: bool,
+    // Set when the item was deleted, loot boxes and possessions no longer use it
+    pub deleted_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::item_attribute::Entity")]
+    ItemAttribute,
+    #[sea_orm(has_many = "super::loot_entry::Entity")]
+    LootEntry,
+    #[sea_orm(has_many = "super::loot_table::Entity")]
+    LootTable,
     #[sea_orm(has_many = "super::possession::Entity")]
     Possession,
 }
 
+impl Related<super::item_attribute::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ItemAttribute.def()
+    }
+}
+
+impl Related<super::loot_entry::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LootEntry.def()
+    }
+}
+
+impl Related<super::loot_table::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LootTable.def()
+    }
+}
+
 impl Related<super::possession::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Possession.def()
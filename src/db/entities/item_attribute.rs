@@ -0,0 +1,60 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(Clone, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum AttributeKind {
+    #[sea_orm(string_value = "integer")]
+    Integer,
+    #[sea_orm(string_value = "float")]
+    Float,
+    #[sea_orm(string_value = "boolean")]
+    Boolean,
+    #[sea_orm(string_value = "string")]
+    String,
+}
+
+impl AttributeKind {
+    // Whether a JSON value can be stored as an attribute of this kind
+    pub fn accepts(&self, value: &Json) -> bool {
+        match self {
+            AttributeKind::Integer => value.is_i64() || value.is_u64(),
+            AttributeKind::Float => value.is_number(),
+            AttributeKind::Boolean => value.is_boolean(),
+            AttributeKind::String => value.is_string(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "item_attribute")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub item: i32,
+    pub name: String,
+    pub kind: AttributeKind,
+    pub value: Json,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::item::Entity",
+        from = "Column::Item",
+        to = "super::item::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Item,
+}
+
+impl Related<super::item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Item.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
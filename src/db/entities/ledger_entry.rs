@@ -0,0 +1,47 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "ledger_entry")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub ledger_transaction: i32,
+    // `None` is the issuer
+    pub owner: Option<i32>,
+    pub currency: String,
+    pub amount: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::ledger_transaction::Entity",
+        from = "Column::LedgerTransaction",
+        to = "super::ledger_transaction::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    LedgerTransaction,
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::Owner",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Owner,
+}
+
+impl Related<super::ledger_transaction::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LedgerTransaction.def()
+    }
+}
+
+impl Related<super::owner::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Owner.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,65 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum LedgerKind {
+    // Currency issued to an owner
+    #[sea_orm(string_value = "credit")]
+    Credit,
+    // Currency taken back from an owner
+    #[sea_orm(string_value = "debit")]
+    Debit,
+    #[sea_orm(string_value = "trade")]
+    Trade,
+    // Undoes a trade transfer when the trade is rolled back
+    #[sea_orm(string_value = "reversal")]
+    Reversal,
+    // Balance of a deleted owner handed to the graveyard owner
+    #[sea_orm(string_value = "graveyard")]
+    Graveyard,
+}
+
+// A balanced set of ledger entries, rows are never changed or deleted
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "ledger_transaction")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub kind: LedgerKind,
+    pub trade_history: Option<i32>,
+    pub memo: Option<String>,
+    pub created_at: DateTimeUtc,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(has_many = "super::ledger_entry::Entity")]
+    LedgerEntry,
+    #[sea_orm(
+        belongs_to = "super::trade_history::Entity",
+        from = "Column::TradeHistory",
+        to = "super::trade_history::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    TradeHistory,
+}
+
+impl Related<super::ledger_entry::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::LedgerEntry.def()
+    }
+}
+
+impl Related<super::trade_history::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeHistory.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
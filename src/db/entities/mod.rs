@@ -2,6 +2,22 @@
 
 pub mod prelude;
 
+pub mod api_key;
+pub mod escrow;
+pub mod idempotency;
 pub mod item;
+pub mod item_attribute;
+pub mod ledger_entry;
+pub mod ledger_transaction;
+pub mod loot_entry;
+pub mod loot_table;
 pub mod owner;
 pub mod possession;
+pub mod possession_attribute;
+pub mod trade;
+pub mod trade_history;
+pub mod trade_history_item;
+pub mod trade_offer_currency;
+pub mod trade_offer_item;
+pub mod trade_participant;
+pub mod wallet;
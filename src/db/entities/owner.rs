@@ -1,5 +1,3 @@
-//! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.7
-
 use sea_orm::entity::prelude::*;
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
@@ -7,12 +5,33 @@ use sea_orm::entity::prelude::*;
 pub struct Model {
     #[sea_orm(primary_key)]
     pub id: i32,
+    pub created_at: DateTimeUtc,
+    // Account of the owner in the game, unique when set
+    #[sea_orm(unique)]
+    pub external_id: Option<String>,
+    pub display_name: Option<String>,
+    // Banned owners can not use their API keys, trade banned ones can not trade
+    pub banned: bool,
+    pub trade_banned: bool,
+    pub metadata: Option<Json>,
+    // Set when the owner was deleted, the row stays for the trade history
+    pub deleted_at: Option<DateTimeUtc>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::api_key::Entity")]
+    ApiKey,
     #[sea_orm(has_many = "super::possession::Entity")]
     Possession,
+    #[sea_orm(has_many = "super::trade_offer_item::Entity")]
+    TradeOfferItem,
+}
+
+impl Related<super::api_key::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ApiKey.def()
+    }
 }
 
 impl Related<super::possession::Entity> for Entity {
@@ -21,4 +40,10 @@ impl Related<super::possession::Entity> for Entity {
     }
 }
 
+impl Related<super::trade_offer_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeOfferItem.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,6 +1,25 @@
-//! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.7
-
 use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+#[derive(
+    Clone, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum Origin {
+    #[default]
+    #[sea_orm(string_value = "granted")]
+    Granted,
+    #[sea_orm(string_value = "dropped")]
+    Dropped,
+    #[sea_orm(string_value = "unboxed")]
+    Unboxed,
+    #[sea_orm(string_value = "purchased")]
+    Purchased,
+    #[sea_orm(string_value = "crafted")]
+    Crafted,
+}
 
 #[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
 #[sea_orm(table_name = "possession")]
@@ -9,6 +28,9 @@ pub struct Model {
     pub id: i32,
     pub owner: i32,
     pub item: i32,
+    pub origin: Origin,
+    // Always 1 unless the item is stackable
+    pub quantity: i32,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
@@ -29,6 +51,10 @@ pub enum Relation {
         on_delete = "NoAction"
     )]
     Owner,
+    #[sea_orm(has_many = "super::possession_attribute::Entity")]
+    PossessionAttribute,
+    #[sea_orm(has_many = "super::trade_offer_item::Entity")]
+    TradeOfferItem,
 }
 
 impl Related<super::item::Entity> for Entity {
@@ -43,4 +69,16 @@ impl Related<super::owner::Entity> for Entity {
     }
 }
 
+impl Related<super::possession_attribute::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PossessionAttribute.def()
+    }
+}
+
+impl Related<super::trade_offer_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeOfferItem.def()
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,21 @@
 //! `SeaORM` Entity, @generated by sea-orm-codegen 1.1.7
 
+pub use super::api_key::Entity as ApiKey;
+pub use super::escrow::Entity as Escrow;
+pub use super::idempotency::Entity as Idempotency;
 pub use super::item::Entity as Item;
+pub use super::item_attribute::Entity as ItemAttribute;
+pub use super::ledger_entry::Entity as LedgerEntry;
+pub use super::ledger_transaction::Entity as LedgerTransaction;
+pub use super::loot_entry::Entity as LootEntry;
+pub use super::loot_table::Entity as LootTable;
 pub use super::owner::Entity as Owner;
 pub use super::possession::Entity as Possession;
+pub use super::possession_attribute::Entity as PossessionAttribute;
+pub use super::trade::Entity as Trade;
+pub use super::trade_history::Entity as TradeHistory;
+pub use super::trade_history_item::Entity as TradeHistoryItem;
+pub use super::trade_offer_currency::Entity as TradeOfferCurrency;
+pub use super::trade_offer_item::Entity as TradeOfferItem;
+pub use super::trade_participant::Entity as TradeParticipant;
+pub use super::wallet::Entity as Wallet;
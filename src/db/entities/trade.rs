@@ -0,0 +1,88 @@
+use sea_orm::entity::prelude::*;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+// Proposed, Negotiating and Accepted trades are open, the others are closed for good
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, EnumIter, DeriveActiveEnum, Serialize, Deserialize, ToSchema,
+)]
+#[sea_orm(rs_type = "String", db_type = "String(StringLen::N(16))")]
+#[serde(rename_all = "snake_case")]
+pub enum TradeState {
+    #[default]
+    #[sea_orm(string_value = "proposed")]
+    Proposed,
+    #[sea_orm(string_value = "negotiating")]
+    Negotiating,
+    #[sea_orm(string_value = "accepted")]
+    Accepted,
+    #[sea_orm(string_value = "executed")]
+    Executed,
+    #[sea_orm(string_value = "cancelled")]
+    Cancelled,
+    #[sea_orm(string_value = "expired")]
+    Expired,
+}
+
+// `trader_1` opened the trade and `trader_2` was invited first,
+// every participant including these two is in `trade_participant`
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "trade")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub trader_1: i32,
+    pub trader_2: i32,
+    pub state: TradeState,
+    pub created_at: DateTimeUtc,
+    pub updated_at: DateTimeUtc,
+    pub expires_at: Option<DateTimeUtc>,
+    pub closed_at: Option<DateTimeUtc>,
+    pub ready_at: Option<DateTimeUtc>,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::Trader1",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Trader1,
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::Trader2",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Trader2,
+    #[sea_orm(has_many = "super::trade_offer_currency::Entity")]
+    TradeOfferCurrency,
+    #[sea_orm(has_many = "super::trade_offer_item::Entity")]
+    TradeOfferItem,
+    #[sea_orm(has_many = "super::trade_participant::Entity")]
+    TradeParticipant,
+}
+
+impl Related<super::trade_offer_currency::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeOfferCurrency.def()
+    }
+}
+
+impl Related<super::trade_offer_item::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeOfferItem.def()
+    }
+}
+
+impl Related<super::trade_participant::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::TradeParticipant.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,42 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "trade_offer_currency")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub trade: i32,
+    pub owner: i32,
+    // Owner the amount goes to once the trade is executed
+    pub recipient: i32,
+    pub currency: String,
+    pub amount: i64,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::Owner",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Owner,
+    #[sea_orm(
+        belongs_to = "super::trade::Entity",
+        from = "Column::Trade",
+        to = "super::trade::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Trade,
+}
+
+impl Related<super::trade::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Trade.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,64 @@
+use sea_orm::entity::prelude::*;
+
+#[derive(Clone, Debug, PartialEq, DeriveEntityModel, Eq)]
+#[sea_orm(table_name = "trade_offer_item")]
+pub struct Model {
+    #[sea_orm(primary_key)]
+    pub id: i32,
+    pub trade: i32,
+    pub owner: i32,
+    #[sea_orm(unique)]
+    pub possession: i32,
+    // Owner the possession goes to once the trade is executed
+    pub recipient: i32,
+    // Part of the possession's stack on offer, split off when the trade is executed
+    pub quantity: i32,
+}
+
+#[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::owner::Entity",
+        from = "Column::Owner",
+        to = "super::owner::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Owner,
+    #[sea_orm(
+        belongs_to = "super::possession::Entity",
+        from = "Column::Possession",
+        to = "super::possession::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Possession,
+    #[sea_orm(
+        belongs_to = "super::trade::Entity",
+        from = "Column::Trade",
+        to = "super::trade::Column::Id",
+        on_update = "NoAction",
+        on_delete = "Cascade"
+    )]
+    Trade,
+}
+
+impl Related<super::owner::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Owner.def()
+    }
+}
+
+impl Related<super::possession::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Possession.def()
+    }
+}
+
+impl Related<super::trade::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Trade.def()
+    }
+}
+
+impl ActiveModelBehavior for ActiveModel {}
@@ -38,6 +38,7 @@ impl MigrationTrait for Migration {
 }
 
     #[derive(Iden)]
+    #[allow(clippy::enum_variant_names)]
     pub enum Item{
         Table,
         Id,
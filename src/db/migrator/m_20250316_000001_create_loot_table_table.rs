@@ -0,0 +1,60 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000002_create_item_table::Item;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250316_000001_create_loot_table_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LootTable::Table)
+                    .col(
+                        ColumnDef::new(LootTable::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LootTable::Name).string_len(255).not_null())
+                    .col(ColumnDef::new(LootTable::CrateItem).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("LootTable-crate_item")
+                            .from(LootTable::Table, LootTable::CrateItem)
+                            .to(Item::Table, Item::Id),
+                    )
+                    .col(
+                        ColumnDef::new(LootTable::Rolls)
+                            .integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LootTable::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum LootTable {
+    Table,
+    Id,
+    Name,
+    CrateItem,
+    Rolls,
+}
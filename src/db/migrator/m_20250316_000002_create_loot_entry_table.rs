@@ -0,0 +1,65 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000002_create_item_table::Item,
+    m_20250316_000001_create_loot_table_table::LootTable,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250316_000002_create_loot_entry_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LootEntry::Table)
+                    .col(
+                        ColumnDef::new(LootEntry::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LootEntry::LootTable).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("LootEntry-loot_table")
+                            .from(LootEntry::Table, LootEntry::LootTable)
+                            .to(LootTable::Table, LootTable::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(LootEntry::Item).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("LootEntry-item")
+                            .from(LootEntry::Table, LootEntry::Item)
+                            .to(Item::Table, Item::Id),
+                    )
+                    .col(ColumnDef::new(LootEntry::Weight).integer().not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LootEntry::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum LootEntry {
+    Table,
+    Id,
+    LootTable,
+    Item,
+    Weight,
+}
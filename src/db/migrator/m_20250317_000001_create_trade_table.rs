@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000001_create_owner_table::Owner;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250317_000001_create_trade_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Trade::Table)
+                    .col(
+                        ColumnDef::new(Trade::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Trade::Trader1).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("Trade-trader_1")
+                            .from(Trade::Table, Trade::Trader1)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(Trade::Trader1Accept)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(ColumnDef::new(Trade::Trader2).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("Trade-trader_2")
+                            .from(Trade::Table, Trade::Trader2)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(Trade::Trader2Accept)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Trade::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Trade {
+    Table,
+    Id,
+    #[iden = "trader_1"]
+    Trader1,
+    #[iden = "trader_1_accept"]
+    Trader1Accept,
+    #[iden = "trader_2"]
+    Trader2,
+    #[iden = "trader_2_accept"]
+    Trader2Accept,
+}
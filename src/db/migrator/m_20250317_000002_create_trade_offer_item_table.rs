@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000001_create_owner_table::Owner,
+    m_20250315_000001_create_possesion_table::Possession,
+    m_20250317_000001_create_trade_table::Trade,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250317_000002_create_trade_offer_item_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TradeOfferItem::Table)
+                    .col(
+                        ColumnDef::new(TradeOfferItem::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(TradeOfferItem::Trade).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeOfferItem-trade")
+                            .from(TradeOfferItem::Table, TradeOfferItem::Trade)
+                            .to(Trade::Table, Trade::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(TradeOfferItem::Owner).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeOfferItem-owner")
+                            .from(TradeOfferItem::Table, TradeOfferItem::Owner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(TradeOfferItem::Possession)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeOfferItem-possession")
+                            .from(TradeOfferItem::Table, TradeOfferItem::Possession)
+                            .to(Possession::Table, Possession::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TradeOfferItem::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeOfferItem {
+    Table,
+    Id,
+    Trade,
+    Owner,
+    Possession,
+}
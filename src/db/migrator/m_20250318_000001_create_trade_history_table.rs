@@ -0,0 +1,90 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000001_create_owner_table::Owner;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250318_000001_create_trade_history_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TradeHistory::Table)
+                    .col(
+                        ColumnDef::new(TradeHistory::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // The trade row itself is removed once executed, so no foreign key
+                    .col(ColumnDef::new(TradeHistory::Trade).integer().not_null())
+                    .col(ColumnDef::new(TradeHistory::Trader1).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeHistory-trader_1")
+                            .from(TradeHistory::Table, TradeHistory::Trader1)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(ColumnDef::new(TradeHistory::Trader2).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeHistory-trader_2")
+                            .from(TradeHistory::Table, TradeHistory::Trader2)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(TradeHistory::ExecutedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-trade_history-trader_1")
+                    .table(TradeHistory::Table)
+                    .col(TradeHistory::Trader1)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-trade_history-trader_2")
+                    .table(TradeHistory::Table)
+                    .col(TradeHistory::Trader2)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TradeHistory::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeHistory {
+    Table,
+    Id,
+    Trade,
+    #[iden = "trader_1"]
+    Trader1,
+    #[iden = "trader_2"]
+    Trader2,
+    ExecutedAt,
+}
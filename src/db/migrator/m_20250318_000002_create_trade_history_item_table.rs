@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000001_create_owner_table::Owner,
+    m_20250318_000001_create_trade_history_table::TradeHistory,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250318_000002_create_trade_history_item_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TradeHistoryItem::Table)
+                    .col(
+                        ColumnDef::new(TradeHistoryItem::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TradeHistoryItem::TradeHistory)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeHistoryItem-trade_history")
+                            .from(TradeHistoryItem::Table, TradeHistoryItem::TradeHistory)
+                            .to(TradeHistory::Table, TradeHistory::Id),
+                    )
+                    // Possessions may be deleted later on, the ledger keeps their IDs regardless
+                    .col(
+                        ColumnDef::new(TradeHistoryItem::Possession)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TradeHistoryItem::FromOwner)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeHistoryItem-from_owner")
+                            .from(TradeHistoryItem::Table, TradeHistoryItem::FromOwner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(TradeHistoryItem::ToOwner)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeHistoryItem-to_owner")
+                            .from(TradeHistoryItem::Table, TradeHistoryItem::ToOwner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TradeHistoryItem::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeHistoryItem {
+    Table,
+    Id,
+    TradeHistory,
+    Possession,
+    FromOwner,
+    ToOwner,
+}
@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250317_000002_create_trade_offer_item_table::TradeOfferItem;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250319_000001_add_trade_offer_item_possession_index"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Keep only the oldest offer of every possession before locking them down
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "DELETE FROM trade_offer_item WHERE id NOT IN \
+                 (SELECT id FROM (SELECT MIN(id) AS id FROM trade_offer_item GROUP BY possession) AS kept)",
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-trade_offer_item-possession")
+                    .table(TradeOfferItem::Table)
+                    .col(TradeOfferItem::Possession)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-trade_offer_item-possession")
+                    .table(TradeOfferItem::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
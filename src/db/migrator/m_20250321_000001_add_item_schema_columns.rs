@@ -0,0 +1,82 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000002_create_item_table::Item;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250321_000001_add_item_schema_columns"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only alters one column per statement
+        let columns = [
+            ColumnDef::new(ItemSchema::Name).string_len(255).not_null().default("").to_owned(),
+            ColumnDef::new(ItemSchema::Description).text().null().to_owned(),
+            ColumnDef::new(ItemSchema::Quality).string_len(16).not_null().default("normal").to_owned(),
+            ColumnDef::new(ItemSchema::Rarity).string_len(16).not_null().default("common").to_owned(),
+            ColumnDef::new(ItemSchema::Slot).string_len(64).null().to_owned(),
+            ColumnDef::new(ItemSchema::Tradable).boolean().not_null().default(true).to_owned(),
+            ColumnDef::new(ItemSchema::Marketable).boolean().not_null().default(true).to_owned(),
+        ];
+
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Item::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Existing items are named after their type until they are given a real name
+        manager
+            .get_connection()
+            .execute_unprepared("UPDATE item SET name = item_type")
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ItemSchema::Name,
+            ItemSchema::Description,
+            ItemSchema::Quality,
+            ItemSchema::Rarity,
+            ItemSchema::Slot,
+            ItemSchema::Tradable,
+            ItemSchema::Marketable,
+        ];
+
+        for column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Item::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum ItemSchema {
+    Name,
+    Description,
+    Quality,
+    Rarity,
+    Slot,
+    Tradable,
+    Marketable,
+}
@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000002_create_item_table::Item;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250321_000002_create_item_attribute_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ItemAttribute::Table)
+                    .col(
+                        ColumnDef::new(ItemAttribute::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(ItemAttribute::Item).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("ItemAttribute-item")
+                            .from(ItemAttribute::Table, ItemAttribute::Item)
+                            .to(Item::Table, Item::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(ItemAttribute::Name).string_len(255).not_null())
+                    .col(ColumnDef::new(ItemAttribute::Kind).string_len(16).not_null())
+                    .col(ColumnDef::new(ItemAttribute::Value).json().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // An item carries every attribute at most once
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-item_attribute-item-name")
+                    .table(ItemAttribute::Table)
+                    .col(ItemAttribute::Item)
+                    .col(ItemAttribute::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ItemAttribute::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum ItemAttribute {
+    Table,
+    Id,
+    Item,
+    Name,
+    Kind,
+    Value,
+}
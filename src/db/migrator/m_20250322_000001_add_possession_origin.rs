@@ -0,0 +1,47 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250315_000001_create_possesion_table::Possession;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250322_000001_add_possession_origin"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Possessions created before origins were tracked count as granted
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Possession::Table)
+                    .add_column(
+                        ColumnDef::new(PossessionOrigin::Origin)
+                            .string_len(16)
+                            .not_null()
+                            .default("granted"),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Possession::Table)
+                    .drop_column(PossessionOrigin::Origin)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PossessionOrigin {
+    Origin,
+}
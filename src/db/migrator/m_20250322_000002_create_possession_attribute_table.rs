@@ -0,0 +1,71 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250315_000001_create_possesion_table::Possession;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250322_000002_create_possession_attribute_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(PossessionAttribute::Table)
+                    .col(
+                        ColumnDef::new(PossessionAttribute::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(PossessionAttribute::Possession).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("PossessionAttribute-possession")
+                            .from(PossessionAttribute::Table, PossessionAttribute::Possession)
+                            .to(Possession::Table, Possession::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(ColumnDef::new(PossessionAttribute::Name).string_len(255).not_null())
+                    .col(ColumnDef::new(PossessionAttribute::Kind).string_len(16).not_null())
+                    .col(ColumnDef::new(PossessionAttribute::Value).json().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        // A possession carries every attribute at most once
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-possession_attribute-possession-name")
+                    .table(PossessionAttribute::Table)
+                    .col(PossessionAttribute::Possession)
+                    .col(PossessionAttribute::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(PossessionAttribute::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum PossessionAttribute {
+    Table,
+    Id,
+    Possession,
+    Name,
+    Kind,
+    Value,
+}
@@ -0,0 +1,101 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250317_000001_create_trade_table::Trade;
+use super::m_20250317_000002_create_trade_offer_item_table::TradeOfferItem;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250323_000001_add_trade_state"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can not add columns with a non-constant default, so the
+        // timestamps of existing trades are filled in below
+        let columns = [
+            ColumnDef::new(TradeState::State).string_len(16).not_null().default("proposed").to_owned(),
+            ColumnDef::new(TradeState::CreatedAt).timestamp_with_time_zone().null().to_owned(),
+            ColumnDef::new(TradeState::UpdatedAt).timestamp_with_time_zone().null().to_owned(),
+            ColumnDef::new(TradeState::ExpiresAt).timestamp_with_time_zone().null().to_owned(),
+            ColumnDef::new(TradeState::ClosedAt).timestamp_with_time_zone().null().to_owned(),
+        ];
+
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Trade::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        let now = chrono::Utc::now();
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Trade::Table)
+                    .value(TradeState::CreatedAt, now)
+                    .value(TradeState::UpdatedAt, now)
+                    .to_owned(),
+            )
+            .await?;
+
+        // Open trades that already hold offers are being negotiated
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Trade::Table)
+                    .value(TradeState::State, "negotiating")
+                    .and_where(
+                        Expr::col(Trade::Id).in_subquery(
+                            Query::select()
+                                .column(TradeOfferItem::Trade)
+                                .from(TradeOfferItem::Table)
+                                .to_owned(),
+                        ),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            TradeState::State,
+            TradeState::CreatedAt,
+            TradeState::UpdatedAt,
+            TradeState::ExpiresAt,
+            TradeState::ClosedAt,
+        ];
+
+        for column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Trade::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeState {
+    State,
+    CreatedAt,
+    UpdatedAt,
+    ExpiresAt,
+    ClosedAt,
+}
@@ -0,0 +1,77 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250317_000001_create_trade_table::Trade;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250324_000001_add_trade_confirmation"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(TradeConfirmation::Trader1Confirm).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(TradeConfirmation::Trader2Confirm).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(TradeConfirmation::ReadyAt).timestamp_with_time_zone().null().to_owned(),
+        ];
+
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Trade::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Trades both sides already accepted can be confirmed right away
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Trade::Table)
+                    .value(TradeConfirmation::ReadyAt, chrono::Utc::now())
+                    .and_where(Expr::col(Trade::Trader1Accept).eq(true))
+                    .and_where(Expr::col(Trade::Trader2Accept).eq(true))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            TradeConfirmation::Trader1Confirm,
+            TradeConfirmation::Trader2Confirm,
+            TradeConfirmation::ReadyAt,
+        ];
+
+        for column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Trade::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeConfirmation {
+    #[iden = "trader_1_confirm"]
+    Trader1Confirm,
+    #[iden = "trader_2_confirm"]
+    Trader2Confirm,
+    ReadyAt,
+}
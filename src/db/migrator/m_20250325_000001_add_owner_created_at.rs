@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000001_create_owner_table::Owner;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250325_000001_add_owner_created_at"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite can not add columns with a non-constant default, new owners
+        // get their timestamp from the entity instead
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Owner::Table)
+                    .add_column(
+                        ColumnDef::new(OwnerCreatedAt::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // The age of existing owners is unknown, they count as established
+        manager
+            .exec_stmt(
+                Query::update()
+                    .table(Owner::Table)
+                    .value(OwnerCreatedAt::CreatedAt, chrono::DateTime::<chrono::Utc>::UNIX_EPOCH)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Owner::Table)
+                    .drop_column(OwnerCreatedAt::CreatedAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum OwnerCreatedAt {
+    CreatedAt,
+}
@@ -0,0 +1,124 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000001_create_owner_table::Owner,
+    m_20250318_000001_create_trade_history_table::TradeHistory,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250325_000002_create_escrow_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Escrow::Table)
+                    .col(
+                        ColumnDef::new(Escrow::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::TradeHistory)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("Escrow-trade_history")
+                            .from(Escrow::Table, Escrow::TradeHistory)
+                            .to(TradeHistory::Table, TradeHistory::Id),
+                    )
+                    // Held possessions are locked, so they can not be deleted before settling
+                    .col(
+                        ColumnDef::new(Escrow::Possession)
+                            .integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::FromOwner)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("Escrow-from_owner")
+                            .from(Escrow::Table, Escrow::FromOwner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::ToOwner)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("Escrow-to_owner")
+                            .from(Escrow::Table, Escrow::ToOwner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::State)
+                            .string_len(16)
+                            .not_null()
+                            .default("held"),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::HeldAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::ReleaseAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Escrow::SettledAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-escrow-possession")
+                    .table(Escrow::Table)
+                    .col(Escrow::Possession)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Escrow::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Escrow {
+    Table,
+    Id,
+    TradeHistory,
+    Possession,
+    FromOwner,
+    ToOwner,
+    State,
+    HeldAt,
+    ReleaseAt,
+    SettledAt,
+}
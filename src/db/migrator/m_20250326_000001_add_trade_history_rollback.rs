@@ -0,0 +1,45 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250318_000001_create_trade_history_table::TradeHistory;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250326_000001_add_trade_history_rollback"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TradeHistory::Table)
+                    .add_column(
+                        ColumnDef::new(TradeHistoryRollback::RolledBackAt)
+                            .timestamp_with_time_zone()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TradeHistory::Table)
+                    .drop_column(TradeHistoryRollback::RolledBackAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeHistoryRollback {
+    RolledBackAt,
+}
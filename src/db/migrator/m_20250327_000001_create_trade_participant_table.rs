@@ -0,0 +1,190 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000001_create_owner_table::Owner,
+    m_20250317_000001_create_trade_table::Trade,
+    m_20250317_000002_create_trade_offer_item_table::TradeOfferItem,
+    m_20250324_000001_add_trade_confirmation::TradeConfirmation,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250327_000001_create_trade_participant_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TradeParticipant::Table)
+                    .col(
+                        ColumnDef::new(TradeParticipant::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TradeParticipant::Trade)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeParticipant-trade")
+                            .from(TradeParticipant::Table, TradeParticipant::Trade)
+                            .to(Trade::Table, Trade::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(TradeParticipant::Owner)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeParticipant-owner")
+                            .from(TradeParticipant::Table, TradeParticipant::Owner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(TradeParticipant::Ready)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .col(
+                        ColumnDef::new(TradeParticipant::Confirmed)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-trade_participant-trade-owner")
+                    .table(TradeParticipant::Table)
+                    .col(TradeParticipant::Trade)
+                    .col(TradeParticipant::Owner)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // Both sides of existing trades become participants, keeping their flags
+        let connection = manager.get_connection();
+        for (trader, accept, confirm) in [
+            ("trader_1", "trader_1_accept", "trader_1_confirm"),
+            ("trader_2", "trader_2_accept", "trader_2_confirm"),
+        ] {
+            connection
+                .execute_unprepared(&format!(
+                    "INSERT INTO trade_participant (trade, owner, ready, confirmed) \
+                     SELECT id, {}, {}, {} FROM trade",
+                    trader, accept, confirm
+                ))
+                .await?;
+        }
+
+        // Offers name who receives the possession, in existing trades that is the other side
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TradeOfferItem::Table)
+                    .add_column(
+                        ColumnDef::new(TradeOfferRecipient::Recipient)
+                            .integer()
+                            .null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        connection
+            .execute_unprepared(
+                "UPDATE trade_offer_item SET recipient = \
+                 (SELECT CASE WHEN trade_offer_item.owner = trade.trader_1 \
+                 THEN trade.trader_2 ELSE trade.trader_1 END \
+                 FROM trade WHERE trade.id = trade_offer_item.trade)",
+            )
+            .await?;
+
+        // Readiness and confirmation now live with the participants
+        let columns = [
+            Trade::Trader1Accept.into_iden(),
+            Trade::Trader2Accept.into_iden(),
+            TradeConfirmation::Trader1Confirm.into_iden(),
+            TradeConfirmation::Trader2Confirm.into_iden(),
+        ];
+
+        for column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Trade::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            ColumnDef::new(Trade::Trader1Accept).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(Trade::Trader2Accept).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(TradeConfirmation::Trader1Confirm).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(TradeConfirmation::Trader2Confirm).boolean().not_null().default(false).to_owned(),
+        ];
+
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Trade::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(TradeOfferItem::Table)
+                    .drop_column(TradeOfferRecipient::Recipient)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(TradeParticipant::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeParticipant {
+    Table,
+    Id,
+    Trade,
+    Owner,
+    Ready,
+    Confirmed,
+}
+
+#[derive(Iden)]
+pub enum TradeOfferRecipient {
+    Recipient,
+}
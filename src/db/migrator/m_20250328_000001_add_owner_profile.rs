@@ -0,0 +1,89 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000001_create_owner_table::Owner;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250328_000001_add_owner_profile"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // SQLite only alters one column per statement
+        let columns = [
+            ColumnDef::new(OwnerProfile::ExternalId).string_len(255).null().to_owned(),
+            ColumnDef::new(OwnerProfile::DisplayName).string_len(255).null().to_owned(),
+            ColumnDef::new(OwnerProfile::Banned).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(OwnerProfile::TradeBanned).boolean().not_null().default(false).to_owned(),
+            ColumnDef::new(OwnerProfile::Metadata).json().null().to_owned(),
+        ];
+
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Owner::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        // Owners without an account of the game keep a NULL id, which never collides
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-owner-external_id")
+                    .table(Owner::Table)
+                    .col(OwnerProfile::ExternalId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx-owner-external_id")
+                    .table(Owner::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        let columns = [
+            OwnerProfile::ExternalId,
+            OwnerProfile::DisplayName,
+            OwnerProfile::Banned,
+            OwnerProfile::TradeBanned,
+            OwnerProfile::Metadata,
+        ];
+
+        for column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Owner::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum OwnerProfile {
+    ExternalId,
+    DisplayName,
+    Banned,
+    TradeBanned,
+    Metadata,
+}
@@ -0,0 +1,100 @@
+use sea_orm_migration::prelude::*;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250331_000001_create_idempotency_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Idempotency::Table)
+                    .col(
+                        ColumnDef::new(Idempotency::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    // Keys are picked by callers, so every API key has its own
+                    .col(ColumnDef::new(Idempotency::ApiKey).integer().not_null())
+                    .col(
+                        ColumnDef::new(Idempotency::IdempotencyKey)
+                            .string_len(255)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Idempotency::Method).string_len(8).not_null())
+                    .col(ColumnDef::new(Idempotency::Path).string_len(1024).not_null())
+                    // The response is unset while the first request is still running
+                    .col(ColumnDef::new(Idempotency::Status).integer().null())
+                    .col(ColumnDef::new(Idempotency::Location).string_len(1024).null())
+                    .col(ColumnDef::new(Idempotency::Body).text().null())
+                    // SHA-256 of the request body, retries have to send the same one
+                    .col(ColumnDef::new(Idempotency::BodyHash).string_len(64).null())
+                    .col(
+                        ColumnDef::new(Idempotency::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(Idempotency::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-idempotency-api_key-idempotency_key")
+                    .table(Idempotency::Table)
+                    .col(Idempotency::ApiKey)
+                    .col(Idempotency::IdempotencyKey)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        // The scheduler sweeps expired keys
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-idempotency-expires_at")
+                    .table(Idempotency::Table)
+                    .col(Idempotency::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Idempotency::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+#[allow(clippy::enum_variant_names)]
+pub enum Idempotency {
+    Table,
+    Id,
+    ApiKey,
+    IdempotencyKey,
+    Method,
+    Path,
+    Status,
+    Location,
+    Body,
+    BodyHash,
+    CreatedAt,
+    ExpiresAt,
+}
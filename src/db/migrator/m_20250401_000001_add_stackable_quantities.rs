@@ -0,0 +1,92 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000002_create_item_table::Item,
+    m_20250315_000001_create_possesion_table::Possession,
+    m_20250317_000002_create_trade_offer_item_table::TradeOfferItem,
+    m_20250318_000002_create_trade_history_item_table::TradeHistoryItem,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250401_000001_add_stackable_quantities"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Item::Table)
+                    .add_column(
+                        ColumnDef::new(Stacks::Stackable)
+                            .boolean()
+                            .not_null()
+                            .default(false),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Every existing possession, offer and transfer is a single item
+        for table in [
+            Possession::Table.into_iden(),
+            TradeOfferItem::Table.into_iden(),
+            TradeHistoryItem::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .add_column(
+                            ColumnDef::new(Stacks::Quantity)
+                                .integer()
+                                .not_null()
+                                .default(1),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for table in [
+            Possession::Table.into_iden(),
+            TradeOfferItem::Table.into_iden(),
+            TradeHistoryItem::Table.into_iden(),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table)
+                        .drop_column(Stacks::Quantity)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Item::Table)
+                    .drop_column(Stacks::Stackable)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+pub enum Stacks {
+    Stackable,
+    Quantity,
+}
@@ -0,0 +1,73 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250314_000001_create_owner_table::Owner;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250402_000001_create_wallet_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Wallet::Table)
+                    .col(
+                        ColumnDef::new(Wallet::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Wallet::Owner).integer().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("Wallet-owner")
+                            .from(Wallet::Table, Wallet::Owner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(ColumnDef::new(Wallet::Currency).string_len(32).not_null())
+                    // Kept in step with the ledger, which it can always be rebuilt from
+                    .col(
+                        ColumnDef::new(Wallet::Balance)
+                            .big_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-wallet-owner-currency")
+                    .table(Wallet::Table)
+                    .col(Wallet::Owner)
+                    .col(Wallet::Currency)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Wallet::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum Wallet {
+    Table,
+    Id,
+    Owner,
+    Currency,
+    Balance,
+}
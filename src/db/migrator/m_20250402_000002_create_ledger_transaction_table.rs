@@ -0,0 +1,84 @@
+use sea_orm_migration::prelude::*;
+
+use super::m_20250318_000001_create_trade_history_table::TradeHistory;
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250402_000002_create_ledger_transaction_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LedgerTransaction::Table)
+                    .col(
+                        ColumnDef::new(LedgerTransaction::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LedgerTransaction::Kind)
+                            .string_len(16)
+                            .not_null(),
+                    )
+                    // Set for currency that changed hands in a trade and for its reversal
+                    .col(
+                        ColumnDef::new(LedgerTransaction::TradeHistory)
+                            .integer()
+                            .null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("LedgerTransaction-trade_history")
+                            .from(LedgerTransaction::Table, LedgerTransaction::TradeHistory)
+                            .to(TradeHistory::Table, TradeHistory::Id),
+                    )
+                    .col(
+                        ColumnDef::new(LedgerTransaction::Memo)
+                            .string_len(255)
+                            .null(),
+                    )
+                    .col(
+                        ColumnDef::new(LedgerTransaction::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-ledger_transaction-trade_history")
+                    .table(LedgerTransaction::Table)
+                    .col(LedgerTransaction::TradeHistory)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LedgerTransaction::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum LedgerTransaction {
+    Table,
+    Id,
+    Kind,
+    TradeHistory,
+    Memo,
+    CreatedAt,
+}
@@ -0,0 +1,91 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000001_create_owner_table::Owner,
+    m_20250402_000002_create_ledger_transaction_table::LedgerTransaction,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250402_000003_create_ledger_entry_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(LedgerEntry::Table)
+                    .col(
+                        ColumnDef::new(LedgerEntry::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(LedgerEntry::LedgerTransaction)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("LedgerEntry-ledger_transaction")
+                            .from(LedgerEntry::Table, LedgerEntry::LedgerTransaction)
+                            .to(LedgerTransaction::Table, LedgerTransaction::Id),
+                    )
+                    // Empty for the issuer, the account currency is credited from and debited to
+                    .col(ColumnDef::new(LedgerEntry::Owner).integer().null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("LedgerEntry-owner")
+                            .from(LedgerEntry::Table, LedgerEntry::Owner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(LedgerEntry::Currency)
+                            .string_len(32)
+                            .not_null(),
+                    )
+                    // Positive for the account receiving, the entries of a transaction add up to 0
+                    .col(
+                        ColumnDef::new(LedgerEntry::Amount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-ledger_entry-owner-currency")
+                    .table(LedgerEntry::Table)
+                    .col(LedgerEntry::Owner)
+                    .col(LedgerEntry::Currency)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(LedgerEntry::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum LedgerEntry {
+    Table,
+    Id,
+    LedgerTransaction,
+    Owner,
+    Currency,
+    Amount,
+}
@@ -0,0 +1,110 @@
+use sea_orm_migration::prelude::*;
+
+use super::{
+    m_20250314_000001_create_owner_table::Owner,
+    m_20250317_000001_create_trade_table::Trade,
+};
+
+pub struct Migration;
+
+impl MigrationName for Migration {
+    fn name(&self) -> &str {
+        "m_20250402_000004_create_trade_offer_currency_table"
+    }
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(TradeOfferCurrency::Table)
+                    .col(
+                        ColumnDef::new(TradeOfferCurrency::Id)
+                            .integer()
+                            .not_null()
+                            .auto_increment()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(TradeOfferCurrency::Trade)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeOfferCurrency-trade")
+                            .from(TradeOfferCurrency::Table, TradeOfferCurrency::Trade)
+                            .to(Trade::Table, Trade::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .col(
+                        ColumnDef::new(TradeOfferCurrency::Owner)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeOfferCurrency-owner")
+                            .from(TradeOfferCurrency::Table, TradeOfferCurrency::Owner)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(TradeOfferCurrency::Recipient)
+                            .integer()
+                            .not_null(),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("TradeOfferCurrency-recipient")
+                            .from(TradeOfferCurrency::Table, TradeOfferCurrency::Recipient)
+                            .to(Owner::Table, Owner::Id),
+                    )
+                    .col(
+                        ColumnDef::new(TradeOfferCurrency::Currency)
+                            .string_len(32)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(TradeOfferCurrency::Amount)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // A trader offers each recipient one amount per currency
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-trade_offer_currency-trade-owner-recipient-currency")
+                    .table(TradeOfferCurrency::Table)
+                    .col(TradeOfferCurrency::Trade)
+                    .col(TradeOfferCurrency::Owner)
+                    .col(TradeOfferCurrency::Recipient)
+                    .col(TradeOfferCurrency::Currency)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(TradeOfferCurrency::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+pub enum TradeOfferCurrency {
+    Table,
+    Id,
+    Trade,
+    Owner,
+    Recipient,
+    Currency,
+    Amount,
+}
@@ -3,6 +3,32 @@ use sea_orm_migration::prelude::*;
 mod m_20250314_000001_create_owner_table;
 mod m_20250314_000002_create_item_table;
 mod m_20250315_000001_create_possesion_table;
+mod m_20250316_000001_create_loot_table_table;
+mod m_20250316_000002_create_loot_entry_table;
+mod m_20250317_000001_create_trade_table;
+mod m_20250317_000002_create_trade_offer_item_table;
+mod m_20250318_000001_create_trade_history_table;
+mod m_20250318_000002_create_trade_history_item_table;
+mod m_20250319_000001_add_trade_offer_item_possession_index;
+mod m_20250320_000001_create_api_key_table;
+mod m_20250321_000001_add_item_schema_columns;
+mod m_20250321_000002_create_item_attribute_table;
+mod m_20250322_000001_add_possession_origin;
+mod m_20250322_000002_create_possession_attribute_table;
+mod m_20250323_000001_add_trade_state;
+mod m_20250324_000001_add_trade_confirmation;
+mod m_20250325_000001_add_owner_created_at;
+mod m_20250325_000002_create_escrow_table;
+mod m_20250326_000001_add_trade_history_rollback;
+mod m_20250327_000001_create_trade_participant_table;
+mod m_20250328_000001_add_owner_profile;
+mod m_20250329_000001_add_soft_delete;
+mod m_20250331_000001_create_idempotency_table;
+mod m_20250401_000001_add_stackable_quantities;
+mod m_20250402_000001_create_wallet_table;
+mod m_20250402_000002_create_ledger_transaction_table;
+mod m_20250402_000003_create_ledger_entry_table;
+mod m_20250402_000004_create_trade_offer_currency_table;
 
 pub struct Migrator;
 
@@ -13,6 +39,32 @@ impl MigratorTrait for Migrator {
             Box::new(m_20250314_000001_create_owner_table::Migration),
             Box::new(m_20250314_000002_create_item_table::Migration),
             Box::new(m_20250315_000001_create_possesion_table::Migration),
+            Box::new(m_20250316_000001_create_loot_table_table::Migration),
+            Box::new(m_20250316_000002_create_loot_entry_table::Migration),
+            Box::new(m_20250317_000001_create_trade_table::Migration),
+            Box::new(m_20250317_000002_create_trade_offer_item_table::Migration),
+            Box::new(m_20250318_000001_create_trade_history_table::Migration),
+            Box::new(m_20250318_000002_create_trade_history_item_table::Migration),
+            Box::new(m_20250319_000001_add_trade_offer_item_possession_index::Migration),
+            Box::new(m_20250320_000001_create_api_key_table::Migration),
+            Box::new(m_20250321_000001_add_item_schema_columns::Migration),
+            Box::new(m_20250321_000002_create_item_attribute_table::Migration),
+            Box::new(m_20250322_000001_add_possession_origin::Migration),
+            Box::new(m_20250322_000002_create_possession_attribute_table::Migration),
+            Box::new(m_20250323_000001_add_trade_state::Migration),
+            Box::new(m_20250324_000001_add_trade_confirmation::Migration),
+            Box::new(m_20250325_000001_add_owner_created_at::Migration),
+            Box::new(m_20250325_000002_create_escrow_table::Migration),
+            Box::new(m_20250326_000001_add_trade_history_rollback::Migration),
+            Box::new(m_20250327_000001_create_trade_participant_table::Migration),
+            Box::new(m_20250328_000001_add_owner_profile::Migration),
+            Box::new(m_20250329_000001_add_soft_delete::Migration),
+            Box::new(m_20250331_000001_create_idempotency_table::Migration),
+            Box::new(m_20250401_000001_add_stackable_quantities::Migration),
+            Box::new(m_20250402_000001_create_wallet_table::Migration),
+            Box::new(m_20250402_000002_create_ledger_transaction_table::Migration),
+            Box::new(m_20250402_000003_create_ledger_entry_table::Migration),
+            Box::new(m_20250402_000004_create_trade_offer_currency_table::Migration),
         ]
     }
 }
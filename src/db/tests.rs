@@ -1,49 +1,228 @@
 // Definitions of db-tests should be put here
 
 #[cfg(test)]
+#[allow(clippy::module_inception)]
 mod tests {
+    use crate::config::Config;
     use crate::db::database::set_up_db;
     use crate::db::entities::{prelude::*, *};
     use crate::db::migrator;
     use sea_orm_migration::MigratorTrait;
+    use rocket::http::{Header, Status};
+    use rocket::local::asynchronous::Client;
+    use rocket::serde::json::{Value, json};
     use sea_orm::*;
-    use tokio::sync::OnceCell;
+    use std::sync::LazyLock;
+    use tokio::sync::Mutex;
 
-    static MIGRATION_DONE: OnceCell<Result<(), DbErr>> = OnceCell::const_new();
+    // Database URLs that were already migrated during this run
+    static MIGRATED: Mutex<Vec<String>> = Mutex::const_new(Vec::new());
+
+    // Every test runs against each enabled backend.
+    // SQLite gets a new file per run, apart from the one in ./ventil.db.
+    // Postgres and MySQL use the databases in VENTIL_TEST_POSTGRES_URL and
+    // VENTIL_TEST_MYSQL_URL, which are emptied at the start of the run.
+    static TEST_CONFIGS: LazyLock<Vec<Config>> = LazyLock::new(|| {
+        let mut urls = Vec::new();
+
+        if cfg!(feature = "sqlite") {
+            let path = std::env::temp_dir().join(format!("ventil-test-{}.db", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            urls.push(format!("sqlite:{}?mode=rwc", path.display()));
+        }
+
+        let servers = [
+            (cfg!(feature = "postgres"), "VENTIL_TEST_POSTGRES_URL"),
+            (cfg!(feature = "mysql"), "VENTIL_TEST_MYSQL_URL"),
+        ];
+        for (enabled, variable) in servers {
+            if enabled {
+                match std::env::var(variable) {
+                    Ok(url) => urls.push(url),
+                    Err(_) => panic!("{} has to be set to test this backend", variable),
+                }
+            }
+        }
+
+        urls.into_iter()
+            .map(|database_url| Config {
+                database_url,
+                ..Default::default()
+            })
+            .collect()
+    });
 
     #[tokio::test]
     async fn create_db_test(){
-       create_db().await;
+        for config in TEST_CONFIGS.iter() {
+            create_db(config).await;
+        }
     }
     
-    async fn create_db() {
-        let db = set_up_db().await;
+    async fn create_db(config: &Config) {
+        let db = set_up_db(config).await;
         assert!(db.is_ok());
         let db = db.unwrap();
         
-    
-        let result = MIGRATION_DONE.get_or_init(|| async {
-            migrator::Migrator::up(&db, None).await
-        }).await;
-        
-        assert!(result.is_ok());
+        // Every backend starts the run from an empty, freshly migrated database
+        let mut migrated = MIGRATED.lock().await;
+        if !migrated.contains(&config.database_url) {
+            assert!(migrator::Migrator::fresh(&db).await.is_ok());
+            migrated.push(config.database_url.clone());
+        }
+    }
+
+    // The whole app on the database of `config`, with an admin key to call its routes.
+    // Trades can be confirmed as soon as everyone is ready.
+    async fn client(config: &Config) -> (Client, Header<'static>) {
+        use crate::db::entities::api_key::Role;
+        use crate::serve::auth::issue_key;
+        use crate::serve::serve_main;
+
+        create_db(config).await;
+
+        let config = Config {
+            trade_confirm_cooldown_secs: 0,
+            ..config.clone()
+        };
+        let db = set_up_db(&config).await.unwrap();
+        let (_, key) = issue_key(&db, None, Role::Admin).await.unwrap();
+
+        let client = Client::tracked(serve_main::rocket(config).await).await.unwrap();
+        (client, Header::new("X-Api-Key", key))
+    }
+
+    // Opens a trade between `traders` through the routes, adds the `offers` sent to add-item
+    // and gets every trader ready. Returns the trade id and the hash to confirm with.
+    async fn ready_trade(
+        client: &Client,
+        key: &Header<'static>,
+        traders: &[i32],
+        offers: &[Value],
+    ) -> (i32, String) {
+        let response = client
+            .post("/trades")
+            .header(key.clone())
+            .json(&json!({
+                "trader_1_id": traders[0],
+                "trader_2_id": traders[1],
+                "other_trader_ids": &traders[2..],
+            }))
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Created);
+        let id = response.into_json::<Value>().await.unwrap()["id"].as_i64().unwrap() as i32;
+
+        for offer in offers {
+            let response = client
+                .post(format!("/trades/{}/add-item", id))
+                .header(key.clone())
+                .json(offer)
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        for trader in traders {
+            let response = client
+                .put(format!("/trades/{}/accept?owner_id={}", id, trader))
+                .header(key.clone())
+                .dispatch()
+                .await;
+            assert_eq!(response.status(), Status::Ok);
+        }
+
+        let response = client.get(format!("/trades/{}", id)).header(key.clone()).dispatch().await;
+        let trade = response.into_json::<Value>().await.unwrap();
+        assert!(!trade["ready_at"].is_null());
+        (id, trade["items_hash"].as_str().unwrap().to_string())
+    }
+
+    async fn confirm(client: &Client, key: &Header<'static>, trade: i32, owner: i32, hash: &str) -> Status {
+        client
+            .put(format!("/trades/{}/confirm?owner_id={}", trade, owner))
+            .header(key.clone())
+            .json(&json!({ "items_hash": hash }))
+            .dispatch()
+            .await
+            .status()
+    }
+
+    // Rolls back the recorded trade `history` and returns what the rollback reports
+    async fn roll_back(client: &Client, key: &Header<'static>, history: &Value) -> Value {
+        let response = client
+            .post(format!("/trades/history/{}/rollback", history["id"]))
+            .header(key.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Ok);
+        response.into_json::<Value>().await.unwrap()
+    }
+
+    async fn get_json(client: &Client, key: &Header<'static>, uri: String) -> Value {
+        let response = client.get(uri).header(key.clone()).dispatch().await;
+        assert_eq!(response.status(), Status::Ok);
+        response.into_json::<Value>().await.unwrap()
+    }
+
+    // Owners of the route tests are inserted fresh, the other tests share the first ones
+    async fn insert_trader<C: ConnectionTrait>(db: &C) -> owner::Model {
+        owner::ActiveModel {
+            created_at: ActiveValue::set(chrono::Utc::now()),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap()
+    }
+
+    async fn insert_tradable<C: ConnectionTrait>(db: &C, name: &str, stackable: bool) -> item::Model {
+        item::ActiveModel {
+            item_type: ActiveValue::set(name.to_string()),
+            name: ActiveValue::set(name.to_string()),
+            stackable: ActiveValue::set(stackable),
+            ..Default::default()
+        }
+        .insert(db)
+        .await
+        .unwrap()
+    }
+
+    async fn owner_of<C: ConnectionTrait>(db: &C, possession: i32) -> i32 {
+        Possession::find_by_id(possession).one(db).await.unwrap().unwrap().owner
+    }
+
+    // Units of `item` across every possession of `owner`
+    async fn held_quantity<C: ConnectionTrait>(db: &C, owner: i32, item: i32) -> i32 {
+        Possession::find()
+            .filter(possession::Column::Owner.eq(owner))
+            .filter(possession::Column::Item.eq(item))
+            .all(db)
+            .await
+            .unwrap()
+            .iter()
+            .map(|p| p.quantity)
+            .sum()
     }
 
     #[tokio::test]
     async fn insert_owner_test(){
-        insert_owner().await
+        for config in TEST_CONFIGS.iter() {
+            insert_owner(config).await;
+        }
     }
 
-    async fn insert_owner() {
-        create_db().await;
+    async fn insert_owner(config: &Config) {
+        create_db(config).await;
 
-        let db = set_up_db().await;
+        let db = set_up_db(config).await;
 
         assert!(db.is_ok());
 
         let db = db.unwrap();
 
         let user_test = owner::ActiveModel {
+            created_at: ActiveValue::set(chrono::Utc::now()),
             ..Default::default()
         };
 
@@ -52,15 +231,169 @@ mod tests {
         assert!(res.is_ok());
     }
 
+    #[tokio::test]
+    async fn owner_profile_test(){
+        for config in TEST_CONFIGS.iter() {
+            owner_profile(config).await;
+        }
+    }
+
+    async fn owner_profile(config: &Config) {
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let external_id = format!("steam-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        let profile = owner::ActiveModel {
+            created_at: ActiveValue::set(chrono::Utc::now()),
+            external_id: ActiveValue::set(Some(external_id.clone())),
+            display_name: ActiveValue::set(Some("Disco Stu".to_owned())),
+            metadata: ActiveValue::set(Some(rocket::serde::json::json!({ "level": 12 }))),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await;
+        assert!(profile.is_ok());
+
+        let profile = profile.unwrap();
+        assert!(!profile.banned && !profile.trade_banned);
+
+        let found = Owner::find()
+            .filter(owner::Column::ExternalId.eq(external_id.as_str()))
+            .one(&db)
+            .await
+            .unwrap();
+        assert_eq!(found, Some(profile.clone()));
+
+        // An external id belongs to one owner only
+        let taken = owner::ActiveModel {
+            created_at: ActiveValue::set(chrono::Utc::now()),
+            external_id: ActiveValue::set(Some(external_id)),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await;
+        assert!(matches!(
+            taken.err().and_then(|err| err.sql_err()),
+            Some(SqlErr::UniqueConstraintViolation(_))
+        ));
+
+        // Owners without one never collide
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let mut banned: owner::ActiveModel = profile.into();
+        banned.trade_banned = ActiveValue::set(true);
+        let banned = banned.update(&db).await.unwrap();
+        assert!(banned.trade_banned && !banned.banned);
+    }
+
+    #[tokio::test]
+    async fn soft_delete_test(){
+        for config in TEST_CONFIGS.iter() {
+            soft_delete(config).await;
+        }
+    }
+
+    async fn soft_delete(config: &Config) {
+        use crate::serve::deletion::{DeletePolicy, settle_possessions};
+        use crate::serve::error::VentilError;
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let new_owner = || owner::ActiveModel {
+            created_at: ActiveValue::set(chrono::Utc::now()),
+            ..Default::default()
+        };
+        let graveyard = new_owner().insert(&db).await.unwrap();
+        let player = new_owner().insert(&db).await.unwrap();
+
+        let item = item::ActiveModel {
+            item_type: ActiveValue::set("Disco".to_owned()),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let mut held = Vec::new();
+        for _ in 0..2 {
+            let possession = possession::ActiveModel {
+                item: ActiveValue::set(item.id),
+                owner: ActiveValue::set(player.id),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            held.push(possession);
+        }
+
+        // Nothing is touched unless a policy says so
+        let refused = settle_possessions(&db, held.clone(), DeletePolicy::Refuse, Some(graveyard.id)).await;
+        assert!(matches!(refused, Err(VentilError::Conflict(_))));
+
+        let unconfigured = settle_possessions(&db, held.clone(), DeletePolicy::Graveyard, None).await;
+        assert!(matches!(unconfigured, Err(VentilError::BadRequest(_))));
+
+        let moved = settle_possessions(&db, held.clone(), DeletePolicy::Graveyard, Some(graveyard.id)).await;
+        assert!(moved.is_ok());
+
+        let buried = Possession::find()
+            .filter(possession::Column::Owner.eq(graveyard.id))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(buried, 2);
+
+        // A deleted owner keeps its row but drops out of the active owners
+        let mut deleted: owner::ActiveModel = player.clone().into();
+        deleted.deleted_at = ActiveValue::set(Some(chrono::Utc::now()));
+        deleted.update(&db).await.unwrap();
+
+        let active = Owner::find_by_id(player.id)
+            .filter(owner::Column::DeletedAt.is_null())
+            .one(&db)
+            .await
+            .unwrap();
+        assert!(active.is_none());
+        assert!(Owner::find_by_id(player.id).one(&db).await.unwrap().is_some());
+
+        let buried = Possession::find()
+            .filter(possession::Column::Owner.eq(graveyard.id))
+            .all(&db)
+            .await
+            .unwrap();
+        let cascaded = settle_possessions(&db, buried, DeletePolicy::Cascade, None).await;
+        assert!(cascaded.is_ok());
+
+        let left = Possession::find()
+            .filter(possession::Column::Item.eq(item.id))
+            .count(&db)
+            .await
+            .unwrap();
+        assert_eq!(left, 0);
+    }
+
     #[tokio::test]
     async fn insert_item_test(){
-        insert_item().await
+        for config in TEST_CONFIGS.iter() {
+            insert_item(config).await;
+        }
     }
     
-    async fn insert_item() {
-        create_db().await;
+    async fn insert_item(config: &Config) {
+        create_db(config).await;
 
-        let db = set_up_db().await;
+        let db = set_up_db(config).await;
 
         assert!(db.is_ok());
 
@@ -78,15 +411,17 @@ mod tests {
 
     #[tokio::test]
     async fn insert_possession_test(){
-        insert_possession().await;
+        for config in TEST_CONFIGS.iter() {
+            insert_possession(config).await;
+        }
     }
 
-    async fn insert_possession() {
-        create_db().await;
-        insert_owner().await;
-        insert_item().await;
+    async fn insert_possession(config: &Config) {
+        create_db(config).await;
+        insert_owner(config).await;
+        insert_item(config).await;
 
-        let db = set_up_db().await;
+        let db = set_up_db(config).await;
         assert!(db.is_ok());
 
         let db = db.unwrap();
@@ -109,4 +444,1518 @@ mod tests {
 
         assert!(res.is_ok());
     }
+
+    #[tokio::test]
+    async fn possession_stacks_test(){
+        for config in TEST_CONFIGS.iter() {
+            possession_stacks(config).await;
+        }
+    }
+
+    async fn possession_stacks(config: &Config) {
+        use crate::db::entities::possession::Origin;
+        use crate::serve::possession::stacks;
+        use crate::serve::trade::logic::{Trade as StoredTrade, TradeLogic};
+
+        create_db(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let item = |name: &str, stackable: bool| item::ActiveModel {
+            item_type: ActiveValue::set(name.to_string()),
+            name: ActiveValue::set(name.to_string()),
+            stackable: ActiveValue::set(stackable),
+            ..Default::default()
+        };
+        let coins = item("coin", true).insert(&db).await.unwrap();
+        let hat = item("hat", false).insert(&db).await.unwrap();
+
+        // Grants of a stackable item end up in one stack, the others get a possession each
+        let granted = stacks::grant(&db, owners[0].id, &coins, 5, Origin::Granted).await.unwrap();
+        assert_eq!(granted.len(), 1);
+        let stack = stacks::grant(&db, owners[0].id, &coins, 3, Origin::Dropped).await.unwrap().remove(0);
+        assert_eq!((stack.id, stack.quantity), (granted[0].id, 8));
+        let hats = stacks::grant(&db, owners[0].id, &hat, 2, Origin::Granted).await.unwrap();
+        assert!(hats.iter().all(|p| p.quantity == 1));
+        assert_eq!(hats.len(), 2);
+        assert!(stacks::is_stacked(&db, coins.id).await.unwrap());
+        assert!(!stacks::is_stacked(&db, hat.id).await.unwrap());
+
+        let split = stacks::split_off(&db, stack.clone(), 3).await.unwrap();
+        assert_eq!((split.owner, split.quantity), (owners[0].id, 3));
+        let stack = Possession::find_by_id(stack.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(stack.quantity, 5);
+
+        // Stacks offered in a trade are locked, grants start or grow another one
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        assert!(matches!(trade.add_to_trade(&db, &owners[0], &stack, owners[1].id, 2).await, Ok(true)));
+        assert_eq!(trade.offers[0].quantity, 2);
+        assert!(stacks::is_locked(&db, stack.id).await.unwrap());
+        let grown = stacks::grant(&db, owners[0].id, &coins, 1, Origin::Granted).await.unwrap().remove(0);
+        assert_eq!((grown.id, grown.quantity), (split.id, 4));
+        assert!(trade.delete(&db).await.is_ok());
+
+        let merged = stacks::merge(&db, stack, grown).await.unwrap();
+        assert_eq!(merged.quantity, 9);
+        assert!(Possession::find_by_id(split.id).one(&db).await.unwrap().is_none());
+
+        // Taking everything deletes the stack
+        let left = stacks::take(&db, merged.clone(), 4).await.unwrap();
+        assert_eq!(left.map(|p| p.quantity), Some(5));
+        let left = stacks::take(&db, Possession::find_by_id(merged.id).one(&db).await.unwrap().unwrap(), 5).await;
+        assert!(matches!(left, Ok(None)));
+        assert!(!stacks::is_stacked(&db, coins.id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn wallet_ledger_test(){
+        for config in TEST_CONFIGS.iter() {
+            wallet_ledger(config).await;
+        }
+    }
+
+    async fn wallet_ledger(config: &Config) {
+        use crate::db::entities::ledger_transaction::LedgerKind;
+        use crate::serve::trade::history::TradeRecord;
+        use crate::serve::trade::logic::{Trade as StoredTrade, TradeLogic};
+        use crate::serve::wallet::ledger::{self, Account, LedgerError, Transfer};
+
+        create_db(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let (a, b) = (owners[0].id, owners[1].id);
+        let transfer = |from, to, amount| Transfer {
+            from,
+            to,
+            currency: "ledger-gold".to_string(),
+            amount,
+        };
+
+        // Credits come from the issuer, the wallet is opened on the first one
+        let before = ledger::balance(&db, a, "ledger-gold").await.unwrap();
+        let credit = transfer(Account::Issuer, Account::Owner(a), 100);
+        assert!(ledger::post(&db, LedgerKind::Credit, &credit, None, Some("test".to_string())).await.is_ok());
+        assert_eq!(ledger::balance(&db, a, "ledger-gold").await.unwrap(), before + 100);
+
+        let overdraw = transfer(Account::Owner(b), Account::Issuer, before + 1_000_000);
+        assert!(matches!(
+            ledger::post(&db, LedgerKind::Debit, &overdraw, None, None).await,
+            Err(LedgerError::InsufficientFunds { .. })
+        ));
+        let zero = transfer(Account::Issuer, Account::Owner(a), 0);
+        assert!(matches!(
+            ledger::post(&db, LedgerKind::Credit, &zero, None, None).await,
+            Err(LedgerError::InvalidAmount(0))
+        ));
+
+        // Every transaction is balanced
+        let sum: i64 = LedgerEntry::find()
+            .filter(ledger_entry::Column::Currency.eq("ledger-gold"))
+            .all(&db)
+            .await
+            .unwrap()
+            .iter()
+            .map(|e| e.amount)
+            .sum();
+        assert_eq!(sum, 0);
+
+        // Currency offers take part in the items hash and settle with the recorded trade
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        let empty_hash = trade.items_hash();
+        assert!(matches!(trade.offer_currency(&db, &owners[0], b, "ledger-gold", 40).await, Ok(true)));
+        assert_ne!(trade.items_hash(), empty_hash);
+        assert_eq!(trade.currency_offered_by(a, "ledger-gold"), 40);
+        let stored = StoredTrade::find_by_id(&db, trade.id).await.unwrap().unwrap();
+        assert_eq!(stored.currency_offers, trade.currency_offers);
+        assert!(stored.receives_anything(b));
+
+        let b_before = ledger::balance(&db, b, "ledger-gold").await.unwrap();
+        let mut record = TradeRecord::record(&db, &trade, &[]).await.unwrap();
+        assert!(record.settle(&db, &trade.currency_offers).await.is_ok());
+        assert_eq!(ledger::balance(&db, b, "ledger-gold").await.unwrap(), b_before + 40);
+        let found = TradeRecord::find_by_id(&db, record.history.id).await.unwrap().unwrap();
+        assert_eq!(found.currency, vec![transfer(Account::Owner(a), Account::Owner(b), 40)]);
+
+        // Withdrawing an offer sets it to 0
+        assert!(matches!(trade.offer_currency(&db, &owners[0], b, "ledger-gold", 0).await, Ok(true)));
+        assert!(trade.currency_offers.is_empty());
+        assert!(trade.delete(&db).await.is_ok());
+
+        // Rolling back pays the currency back while the recipient still holds it
+        let rollback = record.roll_back(&db, chrono::Utc::now()).await.unwrap();
+        assert_eq!(rollback.refunded.len(), 1);
+        assert!(rollback.unrefunded.is_empty());
+        assert_eq!(ledger::balance(&db, a, "ledger-gold").await.unwrap(), before + 100);
+        assert_eq!(ledger::balance(&db, b, "ledger-gold").await.unwrap(), b_before);
+
+        let kinds: Vec<_> = ledger::trade_transfers(&db, [record.history.id])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|(transaction, _)| transaction.kind)
+            .collect();
+        assert_eq!(kinds, vec![LedgerKind::Trade, LedgerKind::Reversal]);
+    }
+
+    #[tokio::test]
+    async fn settle_wallets_test(){
+        for config in TEST_CONFIGS.iter() {
+            settle_wallets(config).await;
+        }
+    }
+
+    async fn settle_wallets(config: &Config) {
+        use crate::db::entities::ledger_transaction::LedgerKind;
+        use crate::serve::deletion::{self, DeletePolicy};
+        use crate::serve::error::VentilError;
+        use crate::serve::wallet::ledger::{self, Account, Transfer};
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let new_owner = || owner::ActiveModel {
+            created_at: ActiveValue::set(chrono::Utc::now()),
+            ..Default::default()
+        };
+        let graveyard = new_owner().insert(&db).await.unwrap();
+        let buried = new_owner().insert(&db).await.unwrap();
+        let cascaded = new_owner().insert(&db).await.unwrap();
+
+        for (owner, amount) in [(buried.id, 50), (cascaded.id, 30)] {
+            let credit = Transfer {
+                from: Account::Issuer,
+                to: Account::Owner(owner),
+                currency: "settled-gold".to_string(),
+                amount,
+            };
+            ledger::post(&db, LedgerKind::Credit, &credit, None, None).await.unwrap();
+        }
+
+        // Balances are kept unless a policy says what happens to them
+        let refused = deletion::settle_wallets(&db, buried.id, DeletePolicy::Refuse, Some(graveyard.id)).await;
+        assert!(matches!(refused, Err(VentilError::Conflict(_))));
+        assert_eq!(ledger::balance(&db, buried.id, "settled-gold").await.unwrap(), 50);
+
+        let unconfigured = deletion::settle_wallets(&db, buried.id, DeletePolicy::Graveyard, None).await;
+        assert!(matches!(unconfigured, Err(VentilError::BadRequest(_))));
+
+        let moved = deletion::settle_wallets(&db, buried.id, DeletePolicy::Graveyard, Some(graveyard.id)).await;
+        assert!(moved.is_ok());
+        assert_eq!(ledger::balance(&db, buried.id, "settled-gold").await.unwrap(), 0);
+        assert_eq!(ledger::balance(&db, graveyard.id, "settled-gold").await.unwrap(), 50);
+
+        let debited = deletion::settle_wallets(&db, cascaded.id, DeletePolicy::Cascade, None).await;
+        assert!(debited.is_ok());
+        assert_eq!(ledger::balance(&db, cascaded.id, "settled-gold").await.unwrap(), 0);
+
+        // Emptied wallets are nothing to refuse over
+        let empty = deletion::settle_wallets(&db, cascaded.id, DeletePolicy::Refuse, None).await;
+        assert!(empty.is_ok());
+
+        // Both went through the ledger, which still balances
+        let entries = LedgerEntry::find()
+            .filter(ledger_entry::Column::Currency.eq("settled-gold"))
+            .all(&db)
+            .await
+            .unwrap();
+        assert_eq!(entries.len(), 8);
+        assert_eq!(entries.iter().map(|e| e.amount).sum::<i64>(), 0);
+    }
+
+    #[tokio::test]
+    async fn idempotency_test(){
+        for config in TEST_CONFIGS.iter() {
+            idempotency_keys(config).await;
+        }
+    }
+
+    async fn idempotency_keys(config: &Config) {
+        use crate::serve::idempotency::{Claim, KeyLifetime, claim, purge_expired};
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let key = format!("retry-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+        let ttl = chrono::TimeDelta::minutes(5);
+        let lifetime = KeyLifetime {
+            ttl,
+            claim_timeout: chrono::TimeDelta::minutes(1),
+        };
+        let now = chrono::Utc::now();
+
+        let first = claim(&db, 1, &key, "POST", "/possessions", now, lifetime).await.unwrap();
+        let Claim::New(id) = first else {
+            panic!("a new key has to be claimed");
+        };
+
+        // The first request has not stored its response yet
+        let running = claim(&db, 1, &key, "POST", "/possessions", now, lifetime).await.unwrap();
+        assert!(matches!(running, Claim::Done(ref entry) if entry.id == id && entry.status.is_none()));
+
+        // Every API key has keys of its own
+        let other_caller = claim(&db, 2, &key, "POST", "/possessions", now, lifetime).await.unwrap();
+        assert!(matches!(other_caller, Claim::New(_)));
+
+        // A claim that never got its response is given up after the claim timeout
+        let stalled = now + chrono::TimeDelta::minutes(2);
+        let reclaimed = claim(&db, 2, &key, "POST", "/possessions", stalled, lifetime).await.unwrap();
+        assert!(matches!(reclaimed, Claim::New(_)));
+
+        idempotency::ActiveModel {
+            id: ActiveValue::unchanged(id),
+            status: ActiveValue::set(Some(201)),
+            body: ActiveValue::set(Some("{}".to_owned())),
+            ..Default::default()
+        }
+        .update(&db)
+        .await
+        .unwrap();
+
+        let retried = claim(&db, 1, &key, "POST", "/possessions", now, lifetime).await.unwrap();
+        assert!(matches!(retried, Claim::Done(ref entry) if entry.status == Some(201)));
+
+        // Past the TTL the key starts over
+        let later = now + ttl + chrono::TimeDelta::seconds(1);
+        let expired = claim(&db, 1, &key, "POST", "/possessions", later, lifetime).await.unwrap();
+        assert!(matches!(expired, Claim::New(new_id) if new_id != id));
+
+        let remaining = |api_key: i32| {
+            Idempotency::find()
+                .filter(idempotency::Column::ApiKey.eq(api_key))
+                .filter(idempotency::Column::IdempotencyKey.eq(key.as_str()))
+                .count(&db)
+        };
+        assert!(purge_expired(&db, later + ttl).await.unwrap() >= 2);
+        assert_eq!(remaining(1).await.unwrap(), 0);
+        assert_eq!(remaining(2).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn insert_loot_table_test(){
+        for config in TEST_CONFIGS.iter() {
+            insert_loot_table(config).await;
+        }
+    }
+
+    async fn insert_loot_table(config: &Config) {
+        create_db(config).await;
+        insert_item(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let item = Item::find_by_id(1).one(&db).await
+            .unwrap()
+            .unwrap();
+
+        let loot_table_test = loot_table::ActiveModel {
+            name: ActiveValue::set("Disco crate".to_owned()),
+            crate_item: ActiveValue::set(item.id),
+            rolls: ActiveValue::set(2),
+            ..Default::default()
+        };
+
+        let table = LootTable::insert(loot_table_test).exec(&db).await;
+        assert!(table.is_ok());
+
+        let loot_entry_test = loot_entry::ActiveModel {
+            loot_table: ActiveValue::set(table.unwrap().last_insert_id),
+            item: ActiveValue::set(item.id),
+            weight: ActiveValue::set(10),
+            ..Default::default()
+        };
+
+        let entry = loot_entry_test.insert(&db).await;
+        assert!(entry.is_ok());
+    }
+
+    #[tokio::test]
+    async fn insert_trade_test(){
+        for config in TEST_CONFIGS.iter() {
+            insert_trade(config).await;
+        }
+    }
+
+    async fn insert_trade(config: &Config) {
+        use crate::serve::trade::logic::{Offer, Trade as StoredTrade, TradeLogic};
+
+        create_db(config).await;
+        insert_possession(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        assert!(owners.len() >= 2);
+
+        let possession = Possession::find()
+            .filter(possession::Column::Owner.eq(owners[0].id))
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await;
+        assert!(trade.is_ok());
+
+        let mut trade = trade.unwrap();
+        let added = trade.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await;
+        assert!(matches!(added, Ok(true)));
+
+        // The offered item must survive a reload from the database
+        let stored = StoredTrade::find_by_id(&db, trade.id).await.unwrap().unwrap();
+        assert_eq!(
+            stored.offers,
+            vec![Offer { owner: owners[0].id, possession: possession.id, recipient: owners[1].id, quantity: 1 }]
+        );
+
+        assert!(stored.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn insert_trade_history_test(){
+        for config in TEST_CONFIGS.iter() {
+            insert_trade_history(config).await;
+        }
+    }
+
+    async fn insert_trade_history(config: &Config) {
+        use crate::serve::pagination::{PageRequest, SortOrder};
+        use crate::serve::trade::history::TradeRecord;
+        use crate::serve::trade::logic::{Offer, Trade as StoredTrade};
+
+        create_db(config).await;
+        insert_possession(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        assert!(owners.len() >= 2);
+
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        trade.offers.push(Offer { owner: owners[0].id, possession: 1, recipient: owners[1].id, quantity: 1 });
+
+        let record = TradeRecord::record(&db, &trade, &trade.offers).await;
+        assert!(record.is_ok());
+
+        let record = record.unwrap();
+        assert_eq!(record.items_given_by(owners[0].id), vec![1]);
+        assert!(record.items_given_by(owners[1].id).is_empty());
+
+        let owner_history = TradeRecord::find_with(&db, TradeRecord::owner_query(owners[1].id)).await.unwrap();
+        assert!(owner_history.iter().any(|r| r.history.id == record.history.id));
+
+        // A page of the history still carries the items of each record
+        let page = PageRequest::new(Some(1), Some(record.history.id + 1), Some(SortOrder::Desc));
+        let records = TradeRecord::find_with(&db, page.apply(TradeHistory::find(), trade_history::Column::Id))
+            .await
+            .unwrap();
+        let (records, _) = page.finish(records, |r| r.history.id);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].history.id, record.history.id);
+        assert_eq!(records[0].items.len(), 1);
+
+        assert!(trade.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_lock_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_lock(config).await;
+        }
+    }
+
+    async fn trade_lock(config: &Config) {
+        use crate::serve::trade::logic::{Trade as StoredTrade, TradeLogic};
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let possession = possession::ActiveModel {
+            item: ActiveValue::set(item.id),
+            owner: ActiveValue::set(owners[0].id),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let mut first = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        let mut second = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+
+        assert!(matches!(first.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await, Ok(true)));
+        assert_eq!(StoredTrade::find_locking(&db, possession.id).await.unwrap(), Some(first.id));
+
+        // The same possession can not be offered a second time, in any trade
+        assert!(first.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await.is_err());
+        assert!(second.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await.is_err());
+
+        // Cancelling the trade releases the lock
+        assert!(first.delete(&db).await.is_ok());
+        assert_eq!(StoredTrade::find_locking(&db, possession.id).await.unwrap(), None);
+
+        assert!(second.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_state_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_state(config).await;
+        }
+    }
+
+    async fn trade_state(config: &Config) {
+        use crate::db::entities::trade::TradeState;
+        use crate::serve::trade::events::TradeEventKind;
+        use crate::serve::trade::logic::{Trade as StoredTrade, TradeError, TradeLogic};
+        use chrono::{TimeDelta, Utc};
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let possession = possession::ActiveModel {
+            item: ActiveValue::set(item.id),
+            owner: ActiveValue::set(owners[0].id),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        assert_eq!(trade.state, TradeState::Proposed);
+
+        assert!(matches!(trade.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await, Ok(true)));
+        assert_eq!(trade.state, TradeState::Negotiating);
+
+        assert!(trade.change_trade_status(&db, &owners[1]).await.is_ok());
+        assert_eq!(trade.state, TradeState::Accepted);
+
+        // Closing a trade releases its offers, it can not change afterwards
+        assert!(trade.close(&db, TradeState::Cancelled, Utc::now()).await.is_ok());
+        assert!(trade.closed_at.is_some());
+
+        // Every change is recorded for the trade's followers, in order
+        let kinds: Vec<_> = trade.take_events().into_iter().map(|event| event.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                TradeEventKind::ItemAdded {
+                    owner_id: owners[0].id,
+                    possession_id: possession.id,
+                    to_owner_id: owners[1].id,
+                    quantity: 1,
+                },
+                TradeEventKind::AcceptToggled { owner_id: owners[1].id, ready: true },
+                TradeEventKind::Cancelled,
+            ]
+        );
+        assert!(trade.take_events().is_empty());
+        assert_eq!(StoredTrade::find_locking(&db, possession.id).await.unwrap(), None);
+
+        let stored = StoredTrade::find_by_id(&db, trade.id).await.unwrap().unwrap();
+        assert_eq!(stored.state, TradeState::Cancelled);
+        assert!(matches!(
+            trade.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await,
+            Err(TradeError::Closed { .. })
+        ));
+
+        // Trades past their expiry time are swept by expire_stale
+        let now = Utc::now();
+        let mut stale = StoredTrade::create(&db, &owners[0], &owners[1..2], Some(now - TimeDelta::seconds(1)))
+            .await
+            .unwrap();
+        let fresh = StoredTrade::create(&db, &owners[0], &owners[1..2], Some(now + TimeDelta::hours(1)))
+            .await
+            .unwrap();
+
+        assert!(matches!(
+            stale.add_to_trade(&db, &owners[0], &possession, owners[1].id, 1).await,
+            Err(TradeError::Expired(_))
+        ));
+
+        let expired = StoredTrade::expire_stale(&db, now).await.unwrap();
+        assert!(expired.contains(&stale.id));
+        assert!(!expired.contains(&fresh.id));
+
+        let stale = StoredTrade::find_by_id(&db, stale.id).await.unwrap().unwrap();
+        assert_eq!(stale.state, TradeState::Expired);
+
+        assert!(trade.delete(&db).await.is_ok());
+        assert!(stale.delete(&db).await.is_ok());
+        assert!(fresh.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_confirm_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_confirm(config).await;
+        }
+    }
+
+    async fn trade_confirm(config: &Config) {
+        use crate::serve::trade::logic::{Trade as StoredTrade, TradeError, TradeLogic};
+        use chrono::TimeDelta;
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let mut possessions = Vec::new();
+        for _ in 0..2 {
+            let possession = possession::ActiveModel {
+                item: ActiveValue::set(item.id),
+                owner: ActiveValue::set(owners[0].id),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            possessions.push(possession);
+        }
+
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        assert!(matches!(trade.add_to_trade(&db, &owners[0], &possessions[0], owners[1].id, 1).await, Ok(true)));
+        let hash = trade.items_hash();
+
+        // Confirming needs every trader to be ready first
+        assert!(matches!(
+            trade.confirm_trade(&db, &owners[0], &hash, TimeDelta::zero()).await,
+            Err(TradeError::NotReady(_))
+        ));
+
+        assert!(trade.change_trade_status(&db, &owners[0]).await.is_ok());
+        assert!(trade.change_trade_status(&db, &owners[1]).await.is_ok());
+        assert!(trade.ready_at.is_some());
+
+        assert!(matches!(
+            trade.confirm_trade(&db, &owners[0], &hash, TimeDelta::hours(1)).await,
+            Err(TradeError::CoolingDown { .. })
+        ));
+        assert!(matches!(
+            trade.confirm_trade(&db, &owners[0], "stale", TimeDelta::zero()).await,
+            Err(TradeError::ItemsChanged(_))
+        ));
+        assert!(trade.confirm_trade(&db, &owners[0], &hash, TimeDelta::zero()).await.is_ok());
+        assert!(trade.participants[0].confirmed && !trade.all_confirmed());
+
+        // Changing the items withdraws readiness and confirmations, and changes the hash
+        assert!(matches!(trade.add_to_trade(&db, &owners[0], &possessions[1], owners[1].id, 1).await, Ok(true)));
+        assert!(!trade.participants[0].confirmed && trade.ready_at.is_none());
+        assert_ne!(trade.items_hash(), hash);
+
+        let stored = StoredTrade::find_by_id(&db, trade.id).await.unwrap().unwrap();
+        assert!(!stored.participants[0].confirmed && stored.ready_at.is_none());
+        assert_eq!(stored.items_hash(), trade.items_hash());
+
+        assert!(trade.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_participants_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_participants(config).await;
+        }
+    }
+
+    async fn trade_participants(config: &Config) {
+        use crate::serve::trade::history::TradeRecord;
+        use crate::serve::trade::logic::{Trade as StoredTrade, TradeLogic};
+        use chrono::TimeDelta;
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        assert!(owners.len() >= 3);
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let mut possessions = Vec::new();
+        for owner in &owners[..3] {
+            let possession = possession::ActiveModel {
+                item: ActiveValue::set(item.id),
+                owner: ActiveValue::set(owner.id),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            possessions.push(possession);
+        }
+
+        // A gift only needs the recipient to confirm
+        let mut gift = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        assert!(matches!(gift.add_to_trade(&db, &owners[0], &possessions[0], owners[1].id, 1).await, Ok(true)));
+        assert!(gift.change_trade_status(&db, &owners[0]).await.is_ok());
+        assert!(gift.change_trade_status(&db, &owners[1]).await.is_ok());
+
+        let hash = gift.items_hash();
+        assert!(gift.confirm_trade(&db, &owners[1], &hash, TimeDelta::zero()).await.is_ok());
+        assert!(gift.all_confirmed());
+        assert!(gift.delete(&db).await.is_ok());
+
+        // In a ring of three every trader gives to the next one
+        let mut ring = StoredTrade::create(&db, &owners[0], &owners[1..3], None).await.unwrap();
+        assert_eq!(ring.participants.len(), 3);
+        for i in 0..3 {
+            let recipient = owners[(i + 1) % 3].id;
+            assert!(matches!(
+                ring.add_to_trade(&db, &owners[i], &possessions[i], recipient, 1).await,
+                Ok(true)
+            ));
+        }
+
+        // Items only go to traders of the trade, and never to the giver
+        assert!(matches!(
+            ring.add_to_trade(&db, &owners[0], &possessions[0], owners[0].id, 1).await,
+            Ok(false)
+        ));
+
+        let stored = StoredTrade::find_by_id(&db, ring.id).await.unwrap().unwrap();
+        assert_eq!(stored.participants.len(), 3);
+        assert_eq!(stored.offers, ring.offers);
+        assert_eq!(stored.items_hash(), ring.items_hash());
+
+        for owner in &owners[..3] {
+            assert!(ring.change_trade_status(&db, owner).await.is_ok());
+        }
+        assert!(ring.ready_at.is_some());
+
+        let hash = ring.items_hash();
+        for owner in &owners[..2] {
+            assert!(ring.confirm_trade(&db, owner, &hash, TimeDelta::zero()).await.is_ok());
+        }
+        assert!(!ring.all_confirmed());
+        assert!(ring.confirm_trade(&db, &owners[2], &hash, TimeDelta::zero()).await.is_ok());
+        assert!(ring.all_confirmed());
+
+        // The history lists every move, also for the third trader
+        let record = TradeRecord::record(&db, &ring, &ring.offers).await.unwrap();
+        assert_eq!(record.items.len(), 3);
+        assert_eq!(record.items_given_by(owners[2].id), vec![possessions[2].id]);
+        let owner_history = TradeRecord::find_with(&db, TradeRecord::owner_query(owners[2].id)).await.unwrap();
+        assert!(owner_history.iter().any(|r| r.history.id == record.history.id));
+
+        assert!(ring.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_banned_before_confirm_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_banned_before_confirm(config).await;
+        }
+    }
+
+    async fn trade_banned_before_confirm(config: &Config) {
+        let (client, key) = client(config).await;
+        let db = set_up_db(config).await.unwrap();
+
+        let giver = insert_trader(&db).await;
+        let taker = insert_trader(&db).await;
+        let item = insert_tradable(&db, "banned trade", false).await;
+        let possession = possession::ActiveModel {
+            item: ActiveValue::set(item.id),
+            owner: ActiveValue::set(giver.id),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let offer = json!({ "owner_id": giver.id, "item_id": possession.id, "to_owner_id": taker.id });
+        let (trade, hash) = ready_trade(&client, &key, &[giver.id, taker.id], &[offer]).await;
+
+        // Only the recipient confirms, the giver is banned after getting ready
+        let mut banned = owner::ActiveModel::from(giver.clone());
+        banned.trade_banned = ActiveValue::set(true);
+        banned.update(&db).await.unwrap();
+
+        assert_eq!(confirm(&client, &key, trade, taker.id, &hash).await, Status::Forbidden);
+
+        assert_eq!(owner_of(&db, possession.id).await, giver.id);
+        assert_eq!(get_json(&client, &key, format!("/trades/{}", trade)).await["state"], "accepted");
+
+        // A deleted trader blocks the trade the same way
+        let mut restored = owner::ActiveModel::from(giver);
+        restored.trade_banned = ActiveValue::set(false);
+        restored.deleted_at = ActiveValue::set(Some(chrono::Utc::now()));
+        restored.update(&db).await.unwrap();
+
+        assert_eq!(confirm(&client, &key, trade, taker.id, &hash).await, Status::Forbidden);
+    }
+
+    #[tokio::test]
+    async fn trade_escrow_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_escrow(config).await;
+        }
+    }
+
+    async fn trade_escrow(config: &Config) {
+        use crate::db::entities::escrow::EscrowState;
+        use crate::serve::trade::escrow::Escrow;
+        use crate::serve::trade::history::TradeRecord;
+        use crate::serve::trade::logic::{Offer, Trade as StoredTrade};
+        use chrono::{TimeDelta, Utc};
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let possession = possession::ActiveModel {
+            item: ActiveValue::set(item.id),
+            owner: ActiveValue::set(owners[0].id),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        trade.offers.push(Offer { owner: owners[0].id, possession: possession.id, recipient: owners[1].id, quantity: 1 });
+        let record = TradeRecord::record(&db, &trade, &trade.offers).await.unwrap();
+
+        let now = Utc::now();
+        let held = Escrow::hold(&db, &record, now + TimeDelta::hours(1)).await.unwrap();
+        assert_eq!(held.len(), 1);
+        assert_eq!(held[0].state, EscrowState::Held);
+
+        // Held possessions stay with the sender and show up for both traders
+        let holding = Escrow::find_holding(&db, possession.id).await.unwrap();
+        assert_eq!(holding.map(|entry| entry.id), Some(held[0].id));
+        for owner in &owners[..2] {
+            let entries = Escrow::find_by_owner(&db, owner.id).await.unwrap();
+            assert!(entries.iter().any(|entry| entry.id == held[0].id));
+        }
+
+        // Nothing is delivered before the hold is over
+        let released = Escrow::release_due(&db, now).await.unwrap();
+        assert!(!released.contains(&held[0].id));
+        let stored = Possession::find_by_id(possession.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(stored.owner, owners[0].id);
+
+        let released = Escrow::release_due(&db, now + TimeDelta::hours(2)).await.unwrap();
+        assert!(released.contains(&held[0].id));
+        let stored = Possession::find_by_id(possession.id).one(&db).await.unwrap().unwrap();
+        assert_eq!(stored.owner, owners[1].id);
+        assert!(Escrow::find_holding(&db, possession.id).await.unwrap().is_none());
+
+        let entry = escrow::Entity::find_by_id(held[0].id).one(&db).await.unwrap().unwrap();
+        assert_eq!(entry.state, EscrowState::Delivered);
+        assert!(entry.settled_at.is_some());
+
+        assert!(trade.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_rollback_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_rollback(config).await;
+        }
+    }
+
+    async fn trade_rollback(config: &Config) {
+        use crate::db::entities::escrow::EscrowState;
+        use crate::serve::trade::escrow::Escrow;
+        use crate::serve::trade::events::TradeEventKind;
+        use crate::serve::trade::history::{TradeRecord, Unrecoverable};
+        use crate::serve::trade::logic::{Offer, Trade as StoredTrade, TradeLogic};
+        use chrono::{TimeDelta, Utc};
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owners = Owner::find().all(&db).await.unwrap();
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let mut possessions = Vec::new();
+        for _ in 0..3 {
+            let possession = possession::ActiveModel {
+                item: ActiveValue::set(item.id),
+                owner: ActiveValue::set(owners[1].id),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            possessions.push(possession);
+        }
+
+        // The first two changed hands, the last one is still held in escrow
+        let mut trade = StoredTrade::create(&db, &owners[0], &owners[1..2], None).await.unwrap();
+        trade.offers = possessions
+            .iter()
+            .map(|p| Offer { owner: owners[0].id, possession: p.id, recipient: owners[1].id, quantity: 1 })
+            .collect();
+        let mut record = TradeRecord::record(&db, &trade, &trade.offers).await.unwrap();
+
+        let mut held = possession::ActiveModel::from(possessions[2].clone());
+        held.owner = ActiveValue::set(owners[0].id);
+        held.update(&db).await.unwrap();
+
+        let mut escrow_record = TradeRecord::find_by_id(&db, record.history.id).await.unwrap().unwrap();
+        escrow_record.items.retain(|i| i.possession == possessions[2].id);
+        let entries = Escrow::hold(&db, &escrow_record, Utc::now() + TimeDelta::hours(1)).await.unwrap();
+
+        // The second one is gone before the rollback, the first is offered in another trade
+        assert!(possessions[1].clone().delete(&db).await.is_ok());
+        let mut offering = StoredTrade::create(&db, &owners[1], &owners[0..1], None).await.unwrap();
+        let added = offering.add_to_trade(&db, &owners[1], &possessions[0], owners[0].id, 1).await;
+        assert!(matches!(added, Ok(true)));
+        offering.take_events();
+
+        assert!(!record.is_rolled_back());
+        let rollback = record.roll_back(&db, Utc::now()).await.unwrap();
+        assert!(record.is_rolled_back());
+
+        // Withdrawing the offer is reported for the open trade
+        assert_eq!(rollback.events.len(), 1);
+        assert_eq!(rollback.events[0].trade_id, offering.id);
+        assert!(matches!(
+            rollback.events[0].kind,
+            TradeEventKind::ItemRemoved { possession_id, .. } if possession_id == possessions[0].id
+        ));
+
+        let mut restored = rollback.restored.clone();
+        restored.sort_unstable();
+        assert_eq!(restored, vec![possessions[0].id, possessions[2].id]);
+        assert_eq!(rollback.unrecoverable.len(), 1);
+        assert_eq!(rollback.unrecoverable[0].0, possessions[1].id);
+        assert!(matches!(rollback.unrecoverable[0].1, Unrecoverable::Missing));
+
+        for possession in [&possessions[0], &possessions[2]] {
+            let stored = Possession::find_by_id(possession.id).one(&db).await.unwrap().unwrap();
+            assert_eq!(stored.owner, owners[0].id);
+        }
+
+        let entry = escrow::Entity::find_by_id(entries[0].id).one(&db).await.unwrap().unwrap();
+        assert_eq!(entry.state, EscrowState::Returned);
+
+        let stored = TradeRecord::find_by_id(&db, record.history.id).await.unwrap().unwrap();
+        assert!(stored.is_rolled_back());
+
+        assert!(offering.delete(&db).await.is_ok());
+        assert!(trade.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_rollback_stacks_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_rollback_stacks(config).await;
+        }
+    }
+
+    async fn trade_rollback_stacks(config: &Config) {
+        use crate::db::entities::possession::Origin;
+        use crate::serve::possession::stacks;
+        use crate::serve::trade::history::{TradeRecord, Unrecoverable};
+        use crate::serve::trade::logic::{Offer, Trade as StoredTrade, TradeLogic};
+        use chrono::Utc;
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let sender = insert_trader(&db).await;
+        let recipient = insert_trader(&db).await;
+        let coins = insert_tradable(&db, "rollback coin", true).await;
+        let gems = insert_tradable(&db, "rollback gem", true).await;
+
+        // 3 coins and 5 gems changed hands
+        let coin_stack = stacks::grant(&db, recipient.id, &coins, 3, Origin::Granted).await.unwrap().remove(0);
+        let gem_stack = stacks::grant(&db, recipient.id, &gems, 5, Origin::Granted).await.unwrap().remove(0);
+        let mut trade = StoredTrade::create(&db, &sender, std::slice::from_ref(&recipient), None).await.unwrap();
+        trade.offers = vec![
+            Offer { owner: sender.id, possession: coin_stack.id, recipient: recipient.id, quantity: 3 },
+            Offer { owner: sender.id, possession: gem_stack.id, recipient: recipient.id, quantity: 5 },
+        ];
+        let mut record = TradeRecord::record(&db, &trade, &trade.offers).await.unwrap();
+
+        // Since then the coins grew to 7 and 2 of them are offered elsewhere, 3 of the gems were used up
+        let coin_stack = stacks::grant(&db, recipient.id, &coins, 4, Origin::Dropped).await.unwrap().remove(0);
+        assert_eq!((coin_stack.id, coin_stack.quantity), (trade.offers[0].possession, 7));
+        let mut offering = StoredTrade::create(&db, &recipient, std::slice::from_ref(&sender), None).await.unwrap();
+        let added = offering.add_to_trade(&db, &recipient, &coin_stack, sender.id, 2).await;
+        assert!(matches!(added, Ok(true)));
+        offering.take_events();
+        stacks::take(&db, gem_stack, 3).await.unwrap();
+
+        let rollback = record.roll_back(&db, Utc::now()).await.unwrap();
+
+        // The rest of the coins still covers the other offer, so it stays
+        assert!(rollback.events.is_empty());
+        assert!(stacks::is_locked(&db, coin_stack.id).await.unwrap());
+        assert_eq!(rollback.restored, vec![coin_stack.id]);
+        assert_eq!(rollback.unrecoverable.len(), 1);
+        assert_eq!(rollback.unrecoverable[0].0, trade.offers[1].possession);
+        assert!(matches!(rollback.unrecoverable[0].1, Unrecoverable::Shortfall { held: 2, traded: 5 }));
+
+        // Only the traded coins and what was left of the gems went back
+        assert_eq!(held_quantity(&db, recipient.id, coins.id).await, 4);
+        assert_eq!(held_quantity(&db, sender.id, coins.id).await, 3);
+        assert_eq!(held_quantity(&db, recipient.id, gems.id).await, 0);
+        assert_eq!(held_quantity(&db, sender.id, gems.id).await, 2);
+
+        assert!(offering.delete(&db).await.is_ok());
+        assert!(trade.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn trade_routes_multi_party_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_routes_multi_party(config).await;
+        }
+    }
+
+    async fn trade_routes_multi_party(config: &Config) {
+        let (client, key) = client(config).await;
+        let db = set_up_db(config).await.unwrap();
+
+        let item = insert_tradable(&db, "ring", false).await;
+        let mut traders = Vec::new();
+        let mut possessions = Vec::new();
+        for _ in 0..3 {
+            let trader = insert_trader(&db).await;
+            let possession = possession::ActiveModel {
+                item: ActiveValue::set(item.id),
+                owner: ActiveValue::set(trader.id),
+                ..Default::default()
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+            traders.push(trader.id);
+            possessions.push(possession.id);
+        }
+
+        // In a ring of three every trader gives to the next one
+        let offers: Vec<Value> = (0..3)
+            .map(|i| json!({ "owner_id": traders[i], "item_id": possessions[i], "to_owner_id": traders[(i + 1) % 3] }))
+            .collect();
+        let (trade, hash) = ready_trade(&client, &key, &traders, &offers).await;
+
+        // Nothing changes hands before the last recipient confirms
+        for trader in &traders[..2] {
+            assert_eq!(confirm(&client, &key, trade, *trader, &hash).await, Status::Ok);
+        }
+        assert_eq!(get_json(&client, &key, format!("/trades/{}", trade)).await["state"], "accepted");
+        assert_eq!(owner_of(&db, possessions[0]).await, traders[0]);
+
+        assert_eq!(confirm(&client, &key, trade, traders[2], &hash).await, Status::Ok);
+        assert_eq!(get_json(&client, &key, format!("/trades/{}", trade)).await["state"], "executed");
+        for i in 0..3 {
+            assert_eq!(owner_of(&db, possessions[i]).await, traders[(i + 1) % 3]);
+        }
+
+        // The third trader finds the trade in their history, rolling it back undoes every move
+        let history = get_json(&client, &key, format!("/owners/{}/trades", traders[2])).await;
+        let record = &history["items"][0];
+        assert_eq!(record["trade_id"], trade);
+        assert_eq!(record["items"].as_array().unwrap().len(), 3);
+
+        let rollback = roll_back(&client, &key, record).await;
+        assert_eq!(rollback["restored"].as_array().unwrap().len(), 3);
+        for i in 0..3 {
+            assert_eq!(owner_of(&db, possessions[i]).await, traders[i]);
+        }
+
+        let response = client
+            .post(format!("/trades/history/{}/rollback", record["id"]))
+            .header(key.clone())
+            .dispatch()
+            .await;
+        assert_eq!(response.status(), Status::Conflict);
+    }
+
+    #[tokio::test]
+    async fn trade_routes_escrow_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_routes_escrow(config).await;
+        }
+    }
+
+    async fn trade_routes_escrow(config: &Config) {
+        // Trades of the new test accounts are held for an hour
+        let (client, key) = client(&Config {
+            trade_hold_secs: 3600,
+            ..config.clone()
+        })
+        .await;
+        let db = set_up_db(config).await.unwrap();
+
+        let giver = insert_trader(&db).await;
+        let taker = insert_trader(&db).await;
+        let item = insert_tradable(&db, "escrowed", false).await;
+        let possession = possession::ActiveModel {
+            item: ActiveValue::set(item.id),
+            owner: ActiveValue::set(giver.id),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        let offer = json!({ "owner_id": giver.id, "item_id": possession.id, "to_owner_id": taker.id });
+        let (trade, hash) = ready_trade(&client, &key, &[giver.id, taker.id], &[offer]).await;
+        assert_eq!(confirm(&client, &key, trade, taker.id, &hash).await, Status::Ok);
+        assert_eq!(get_json(&client, &key, format!("/trades/{}", trade)).await["state"], "executed");
+
+        // The possession stays with the giver while it is held
+        assert_eq!(owner_of(&db, possession.id).await, giver.id);
+        let escrow = get_json(&client, &key, format!("/owners/{}/escrow", taker.id)).await;
+        assert_eq!(escrow[0]["possession_id"], possession.id);
+        assert_eq!(escrow[0]["state"], "held");
+
+        // Rolling back returns it from escrow instead of delivering it
+        let history = get_json(&client, &key, format!("/owners/{}/trades", giver.id)).await;
+        let rollback = roll_back(&client, &key, &history["items"][0]).await;
+        assert_eq!(rollback["restored"], json!([possession.id]));
+
+        assert_eq!(owner_of(&db, possession.id).await, giver.id);
+        for owner in [giver.id, taker.id] {
+            let escrow = get_json(&client, &key, format!("/owners/{}/escrow", owner)).await;
+            assert_eq!(escrow, json!([]));
+        }
+    }
+
+    #[tokio::test]
+    async fn trade_routes_rollback_test(){
+        for config in TEST_CONFIGS.iter() {
+            trade_routes_rollback(config).await;
+        }
+    }
+
+    async fn trade_routes_rollback(config: &Config) {
+        use crate::db::entities::possession::Origin;
+        use crate::serve::possession::stacks;
+
+        let (client, key) = client(config).await;
+        let db = set_up_db(config).await.unwrap();
+
+        let sender = insert_trader(&db).await;
+        let recipient = insert_trader(&db).await;
+        let third = insert_trader(&db).await;
+        let coins = insert_tradable(&db, "route coin", true).await;
+        let stack = stacks::grant(&db, sender.id, &coins, 5, Origin::Granted).await.unwrap().remove(0);
+
+        // 3 of the 5 coins change hands, split off into a stack of their own
+        let offer = json!({ "owner_id": sender.id, "item_id": stack.id, "to_owner_id": recipient.id, "quantity": 3 });
+        let (trade, hash) = ready_trade(&client, &key, &[sender.id, recipient.id], &[offer]).await;
+        assert_eq!(confirm(&client, &key, trade, recipient.id, &hash).await, Status::Ok);
+
+        let history = get_json(&client, &key, format!("/owners/{}/trades", recipient.id)).await;
+        let record = &history["items"][0];
+        let traded = record["items"][0]["possession_id"].as_i64().unwrap() as i32;
+        assert_ne!(traded, stack.id);
+        assert_eq!(owner_of(&db, traded).await, recipient.id);
+
+        // The stack grows to 7 and 6 of them are offered to a third trader
+        let grown = stacks::grant(&db, recipient.id, &coins, 4, Origin::Dropped).await.unwrap().remove(0);
+        assert_eq!((grown.id, grown.quantity), (traded, 7));
+        let offer = json!({ "owner_id": recipient.id, "item_id": traded, "to_owner_id": third.id, "quantity": 6 });
+        let (open, _) = ready_trade(&client, &key, &[recipient.id, third.id], &[offer]).await;
+
+        // The 4 coins the recipient keeps no longer cover that offer, so it is withdrawn
+        let rollback = roll_back(&client, &key, record).await;
+        assert_eq!(rollback["restored"], json!([traded]));
+        assert!(rollback["unrecoverable"].as_array().unwrap().is_empty());
+
+        let open = get_json(&client, &key, format!("/trades/{}", open)).await;
+        assert_eq!(open["state"], "negotiating");
+        for participant in open["participants"].as_array().unwrap() {
+            assert!(participant["gives"].as_array().unwrap().is_empty());
+        }
+
+        assert_eq!(held_quantity(&db, recipient.id, coins.id).await, 4);
+        assert_eq!(held_quantity(&db, sender.id, coins.id).await, 5);
+    }
+
+    #[tokio::test]
+    async fn issue_api_key_test(){
+        for config in TEST_CONFIGS.iter() {
+            issue_api_key(config).await;
+        }
+    }
+
+    async fn issue_api_key(config: &Config) {
+        use crate::db::entities::api_key::Role;
+        use crate::serve::auth::{hash_key, issue_key};
+
+        create_db(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owner = Owner::find()
+            .order_by_desc(owner::Column::Id)
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let (issued, key) = issue_key(&db, Some(owner.id), Role::Owner).await.unwrap();
+
+        // Only the hash is stored, the key itself resolves back to its owner
+        assert_ne!(issued.key_hash, key);
+        let found = ApiKey::find()
+            .filter(api_key::Column::KeyHash.eq(hash_key(&key)))
+            .one(&db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, issued.id);
+        assert_eq!(found.owner, Some(owner.id));
+        assert_eq!(found.role, Role::Owner);
+
+        assert!(found.delete(&db).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn insert_item_attribute_test(){
+        for config in TEST_CONFIGS.iter() {
+            insert_item_attribute(config).await;
+        }
+    }
+
+    async fn insert_item_attribute(config: &Config) {
+        use crate::db::entities::item::{Quality, Rarity};
+        use crate::db::entities::item_attribute::AttributeKind;
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let hat = item::ActiveModel {
+            item_type: ActiveValue::set("cosmetic".to_owned()),
+            name: ActiveValue::set("Team Captain".to_owned()),
+            quality: ActiveValue::set(Quality::Unusual),
+            rarity: ActiveValue::set(Rarity::Legendary),
+            slot: ActiveValue::set(Some("head".to_owned())),
+            tradable: ActiveValue::set(false),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        assert!(!hat.tradable);
+        assert!(hat.marketable);
+
+        let effect = item_attribute::ActiveModel {
+            item: ActiveValue::set(hat.id),
+            name: ActiveValue::set("particle_effect".to_owned()),
+            kind: ActiveValue::set(AttributeKind::Integer),
+            value: ActiveValue::set(rocket::serde::json::json!(13)),
+            ..Default::default()
+        };
+        assert!(effect.clone().insert(&db).await.is_ok());
+
+        // Every attribute name is stored once per item
+        assert!(effect.insert(&db).await.is_err());
+
+        assert!(AttributeKind::Integer.accepts(&rocket::serde::json::json!(13)));
+        assert!(!AttributeKind::Integer.accepts(&rocket::serde::json::json!("13")));
+
+        let attributes = hat.find_related(ItemAttribute).all(&db).await.unwrap();
+        assert_eq!(attributes.len(), 1);
+        assert_eq!(attributes[0].kind, AttributeKind::Integer);
+    }
+
+    #[tokio::test]
+    async fn possession_attribute_test(){
+        for config in TEST_CONFIGS.iter() {
+            possession_attribute(config).await;
+        }
+    }
+
+    async fn possession_attribute(config: &Config) {
+        use crate::db::entities::item_attribute::AttributeKind;
+        use crate::db::entities::possession::Origin;
+        use crate::serve::possession::attributes::{self, AttributeError};
+        use rocket::serde::json::json;
+
+        create_db(config).await;
+        insert_item(config).await;
+        insert_owner(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let owner = Owner::find().one(&db).await.unwrap().unwrap();
+        let item = Item::find().one(&db).await.unwrap().unwrap();
+
+        let possession = possession::ActiveModel {
+            item: ActiveValue::set(item.id),
+            owner: ActiveValue::set(owner.id),
+            origin: ActiveValue::set(Origin::Dropped),
+            ..Default::default()
+        }
+        .insert(&db)
+        .await
+        .unwrap();
+
+        assert_eq!(possession.origin, Origin::Dropped);
+
+        let wear = attributes::set(&db, possession.id, "wear", AttributeKind::Float, json!(0.07)).await;
+        assert!(wear.is_ok());
+
+        // Values have to match their kind
+        let bad = attributes::set(&db, possession.id, "custom_name", AttributeKind::String, json!(5)).await;
+        assert!(matches!(bad, Err(AttributeError::InvalidValue { .. })));
+
+        // Counters start at zero and only integers can be incremented
+        assert!(attributes::increment(&db, possession.id, "kills", 3).await.is_ok());
+        let kills = attributes::increment(&db, possession.id, "kills", 2).await.unwrap();
+        assert_eq!(kills.value, json!(5));
+
+        let wear_increment = attributes::increment(&db, possession.id, "wear", 1).await;
+        assert!(matches!(wear_increment, Err(AttributeError::NotCounter(_))));
+
+        assert!(attributes::remove(&db, possession.id, "wear").await.is_ok());
+        let left = possession.find_related(PossessionAttribute).all(&db).await.unwrap();
+        assert_eq!(left.len(), 1);
+        assert_eq!(left[0].name, "kills");
+
+        // Attributes go along with their possession
+        assert!(possession.delete(&db).await.is_ok());
+        assert!(PossessionAttribute::find_by_id(left[0].id).one(&db).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn import_schema_test(){
+        for config in TEST_CONFIGS.iter() {
+            import_schema(config).await;
+        }
+    }
+
+    async fn import_schema(config: &Config) {
+        use crate::serve::item::schema::{SchemaError, SchemaFile, SchemaFormat};
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let name = "Schema test hat";
+        let schema = SchemaFile::parse(
+            &format!(
+                r#"
+                    version = 1
+
+                    [[items]]
+                    name = "{name}"
+                    item_type = "cosmetic"
+                    rarity = "mythical"
+
+                    [[items.attributes]]
+                    name = "paintable"
+                    kind = "boolean"
+                    value = true
+                "#
+            ),
+            SchemaFormat::Toml,
+        )
+        .unwrap();
+
+        // A dry run reports the change without writing it
+        let report = schema.import(&db, true).await.unwrap();
+        assert_eq!(report.created, vec![name.to_string()]);
+        let stored = Item::find()
+            .filter(item::Column::Name.eq(name))
+            .all(&db)
+            .await
+            .unwrap();
+        assert!(stored.is_empty());
+
+        let report = schema.import(&db, false).await.unwrap();
+        assert_eq!(report.created.len(), 1);
+
+        // Importing the same schema again changes nothing
+        let report = schema.import(&db, false).await.unwrap();
+        assert!(report.created.is_empty() && report.updated.is_empty());
+        assert_eq!(report.unchanged.len(), 1);
+
+        let exported = SchemaFile::export(&db).await.unwrap();
+        let hat = exported.items.iter().find(|i| i.name == name).unwrap();
+        assert_eq!(hat.attributes.len(), 1);
+
+        let unsupported = SchemaFile::parse(r#"{"version": 2, "items": []}"#, SchemaFormat::Json);
+        assert!(matches!(unsupported, Err(SchemaError::UnsupportedVersion(2))));
+    }
+
+    #[tokio::test]
+    async fn paginate_items_test(){
+        for config in TEST_CONFIGS.iter() {
+            paginate_items(config).await;
+        }
+    }
+
+    async fn paginate_items(config: &Config) {
+        use crate::serve::pagination::{PageRequest, SortOrder};
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let paged_item = item::ActiveModel {
+                item_type: ActiveValue::set("Paginated".to_owned()),
+                ..Default::default()
+            };
+            ids.push(paged_item.insert(&db).await.unwrap().id);
+        }
+
+        let query = || Item::find().filter(item::Column::ItemType.eq("Paginated"));
+
+        // Two full pages, the second one reports no further page
+        let page = PageRequest::new(Some(2), None, None);
+        let rows = page.apply(query(), item::Column::Id).all(&db).await.unwrap();
+        let (rows, next) = page.finish(rows, |i| i.id);
+        assert_eq!(rows.iter().map(|i| i.id).collect::<Vec<_>>(), ids[..2]);
+        assert_eq!(next, Some(ids[1]));
+
+        let page = PageRequest::new(Some(2), next, None);
+        let rows = page.apply(query(), item::Column::Id).all(&db).await.unwrap();
+        let (rows, next) = page.finish(rows, |i| i.id);
+        assert_eq!(rows.iter().map(|i| i.id).collect::<Vec<_>>(), ids[2..]);
+        assert_eq!(next, None);
+
+        let page = PageRequest::new(Some(1), Some(ids[2]), Some(SortOrder::Desc));
+        let rows = page.apply(query(), item::Column::Id).all(&db).await.unwrap();
+        let (rows, next) = page.finish(rows, |i| i.id);
+        assert_eq!(rows.iter().map(|i| i.id).collect::<Vec<_>>(), vec![ids[1]]);
+        assert_eq!(next, Some(ids[1]));
+    }
+
+    #[tokio::test]
+    async fn map_db_errors_test(){
+        for config in TEST_CONFIGS.iter() {
+            map_db_errors(config).await;
+        }
+    }
+
+    async fn map_db_errors(config: &Config) {
+        use crate::serve::error::{ErrorCode, VentilError};
+
+        create_db(config).await;
+
+        let db = set_up_db(config).await;
+        assert!(db.is_ok());
+
+        let db = db.unwrap();
+
+        // A possession of a missing owner violates its foreign key
+        let orphan = possession::ActiveModel {
+            owner: ActiveValue::set(i32::MAX),
+            item: ActiveValue::set(i32::MAX),
+            ..Default::default()
+        };
+        let err = VentilError::from(orphan.insert(&db).await.unwrap_err());
+        assert_eq!(err.code(), ErrorCode::Conflict);
+
+        // Other database errors are reported without their details
+        let err = VentilError::from(DbErr::Custom("connection lost".to_string()));
+        assert_eq!(err.code(), ErrorCode::DatabaseError);
+        assert_eq!(err.to_string(), "Database error");
+    }
 }
@@ -1,18 +1,51 @@
 use std::env;
+use std::path::PathBuf;
 
 mod commands;
+mod config;
 mod db;
 mod serve;
 
+#[cfg(not(any(feature = "sqlite", feature = "postgres", feature = "mysql")))]
+compile_error!("Enable at least one database backend feature: sqlite, postgres or mysql");
+
 #[rocket::main]
 async fn main() {
     let commands = commands::get_commands();
 
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // `--config <file>` applies to every command, wherever it is given
+    let mut config_path = None;
+    if let Some(i) = args.iter().position(|arg| arg == "--config") {
+        args.remove(i);
+        if i < args.len() {
+            config_path = Some(PathBuf::from(args.remove(i)));
+        } else {
+            eprintln!("Error: --config needs a file, e.g. --config Ventil.toml");
+            return;
+        }
+    }
+
+    let config = match config::Config::load(config_path.as_deref()) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("Error: Could not read the configuration!, \n Reason: {e}");
+            return;
+        }
+    };
+
+    let mut args = args.into_iter().peekable();
+
+    while let Some(command) = args.next() {
+        // Everything up to the next command are arguments of this one
+        let mut command_args = Vec::new();
+        while let Some(arg) = args.next_if(|arg| !commands.contains_key(arg.as_str())) {
+            command_args.push(arg);
+        }
 
-    for command in &args[1..] {
         match commands.get(&command.as_str()) {
-            Some(func) => func().await,
+            Some(func) => func(config.clone(), command_args).await,
             None => eprintln!(
                 "Error: {} is not a command, --help for list of commands",
                 &command
@@ -0,0 +1,155 @@
+use crate::db::entities::api_key::{self, Role};
+use crate::db::entities::prelude::{ApiKey, Owner};
+use rand::RngCore;
+use rocket::{
+    Request, State,
+    http::Status,
+    request::{FromRequest, Outcome},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, QueryFilter,
+};
+use sha2::{Digest, Sha256};
+use utoipa::{
+    Modify,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+// Name of the security scheme referenced by protected routes in the API docs
+pub const SECURITY_SCHEME: &str = "api_key";
+
+#[derive(Clone, Copy, Debug)]
+pub enum AuthError {
+    Missing,
+    Invalid,
+    Forbidden,
+    // The key belongs to a banned owner
+    Banned,
+    Database,
+}
+
+// The authenticated caller of a request, resolved from its API key.
+// Keys are sent as `Authorization: Bearer <key>` or `X-Api-Key: <key>`.
+#[derive(Clone)]
+pub struct Caller {
+    // Id of the API key, not the key itself
+    pub key_id: i32,
+    pub owner_id: Option<i32>,
+    pub role: Role,
+}
+
+impl Caller {
+    pub fn is_admin(&self) -> bool {
+        self.role == Role::Admin
+    }
+
+    // Owners may only act for themselves, admins may act for anyone
+    pub fn can_act_for(&self, owner_id: i32) -> bool {
+        self.is_admin() || self.owner_id == Some(owner_id)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Caller {
+    type Error = AuthError;
+
+    // The key is looked up once per request, further guards get the cached caller
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.local_cache_async(authenticate(request)).await {
+            Ok(caller) => Outcome::Success(caller.clone()),
+            Err(error) => Outcome::Error(*error),
+        }
+    }
+}
+
+// Resolves the caller from the API key of the request
+async fn authenticate(request: &Request<'_>) -> Result<Caller, (Status, AuthError)> {
+    let key = request
+        .headers()
+        .get_one("Authorization")
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .or_else(|| request.headers().get_one("X-Api-Key"));
+
+    let Some(key) = key else {
+        return Err((Status::Unauthorized, AuthError::Missing));
+    };
+
+    let db = match request.guard::<&State<DatabaseConnection>>().await {
+        Outcome::Success(db) => db.inner(),
+        _ => return Err((Status::InternalServerError, AuthError::Database)),
+    };
+
+    match ApiKey::find()
+        .filter(api_key::Column::KeyHash.eq(hash_key(key)))
+        .find_also_related(Owner)
+        .one(db)
+        .await
+    {
+        Ok(Some((_, Some(owner)))) if owner.banned => Err((Status::Forbidden, AuthError::Banned)),
+        Ok(Some((api_key, _))) => Ok(Caller {
+            key_id: api_key.id,
+            owner_id: api_key.owner,
+            role: api_key.role,
+        }),
+        Ok(None) => Err((Status::Unauthorized, AuthError::Invalid)),
+        Err(_) => Err((Status::InternalServerError, AuthError::Database)),
+    }
+}
+
+// A caller holding an admin key, required for item definitions and possession CRUD
+pub struct Admin;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Admin {
+    type Error = AuthError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        match request.guard::<Caller>().await {
+            Outcome::Success(caller) if caller.is_admin() => Outcome::Success(Admin),
+            Outcome::Success(_) => Outcome::Error((Status::Forbidden, AuthError::Forbidden)),
+            Outcome::Error(error) => Outcome::Error(error),
+            Outcome::Forward(status) => Outcome::Forward(status),
+        }
+    }
+}
+
+// Only the SHA-256 of a key is stored, the key itself is shown once when issued
+pub fn hash_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// Generates and stores a new key, returning the row and the plain key
+pub async fn issue_key<C: ConnectionTrait>(
+    db: &C,
+    owner_id: Option<i32>,
+    role: Role,
+) -> Result<(api_key::Model, String), DbErr> {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let key = hex::encode(bytes);
+
+    let new_key = api_key::ActiveModel {
+        owner: ActiveValue::set(owner_id),
+        key_hash: ActiveValue::set(hash_key(&key)),
+        role: ActiveValue::set(role),
+        created_at: ActiveValue::set(chrono::Utc::now()),
+        ..Default::default()
+    };
+
+    let model = new_key.insert(db).await?;
+    Ok((model, key))
+}
+
+// Registers the bearer key scheme in the OpenAPI documentation
+pub struct SecurityAddon;
+
+impl Modify for SecurityAddon {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            SECURITY_SCHEME,
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
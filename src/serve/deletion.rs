@@ -0,0 +1,144 @@
+use crate::db::entities::ledger_transaction::LedgerKind;
+use crate::db::entities::possession;
+use crate::db::entities::prelude::{Owner, Possession};
+use crate::serve::error::VentilError;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::logic::Trade;
+use crate::serve::wallet::ledger::{self, Account, Transfer};
+use rocket::FromFormField;
+use sea_orm::{ColumnTrait, ConnectionTrait, EntityTrait, QueryFilter, prelude::Expr};
+use utoipa::ToSchema;
+
+// What happens to the possessions and balances of a deleted owner or item
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromFormField, ToSchema)]
+#[schema(rename_all = "lowercase")]
+pub enum DeletePolicy {
+    // Only delete when nothing refers to the row anymore
+    #[default]
+    Refuse,
+    // Delete the possessions along with it
+    Cascade,
+    // Hand the possessions to the configured graveyard owner
+    Graveyard,
+}
+
+// Applies `policy` to the possessions still referring to a row that is being deleted.
+// Possessions offered in an open trade or held in escrow are never touched.
+pub async fn settle_possessions<C: ConnectionTrait>(
+    db: &C,
+    possessions: Vec<possession::Model>,
+    policy: DeletePolicy,
+    graveyard_owner_id: Option<i32>,
+) -> Result<(), VentilError> {
+    if possessions.is_empty() {
+        return Ok(());
+    }
+
+    // Possessions go to the graveyard owner, or are deleted when there is none
+    let graveyard = match policy {
+        DeletePolicy::Refuse => {
+            return Err(VentilError::Conflict(format!(
+                "{} possessions still refer to it, delete them first or pick another policy",
+                possessions.len()
+            )));
+        }
+        DeletePolicy::Cascade => None,
+        DeletePolicy::Graveyard => Some(find_graveyard(db, graveyard_owner_id).await?),
+    };
+
+    for possession in &possessions {
+        if let Some(trade_id) = Trade::find_locking(db, possession.id).await? {
+            return Err(VentilError::Conflict(format!(
+                "Possession {} is locked by trade {}",
+                possession.id, trade_id
+            )));
+        }
+        if let Some(entry) = Escrow::find_holding(db, possession.id).await? {
+            return Err(VentilError::Conflict(format!(
+                "Possession {} is held in escrow until {}",
+                possession.id,
+                entry.release_at.to_rfc3339()
+            )));
+        }
+    }
+
+    let ids: Vec<i32> = possessions.iter().map(|p| p.id).collect();
+    match graveyard {
+        None => {
+            Possession::delete_many()
+                .filter(possession::Column::Id.is_in(ids))
+                .exec(db)
+                .await?;
+        }
+        Some(graveyard) => {
+            Possession::update_many()
+                .col_expr(possession::Column::Owner, Expr::value(graveyard))
+                .filter(possession::Column::Id.is_in(ids))
+                .exec(db)
+                .await?;
+        }
+    }
+    Ok(())
+}
+
+// Applies `policy` to the balances of an owner that is being deleted, always through the ledger:
+// cascading debits them back to the issuer, the graveyard owner gets them transferred.
+pub async fn settle_wallets<C: ConnectionTrait>(
+    db: &C,
+    owner_id: i32,
+    policy: DeletePolicy,
+    graveyard_owner_id: Option<i32>,
+) -> Result<(), VentilError> {
+    let mut wallets = ledger::wallets(db, owner_id).await?;
+    wallets.retain(|wallet| wallet.balance != 0);
+    if wallets.is_empty() {
+        return Ok(());
+    }
+
+    let (kind, to) = match policy {
+        DeletePolicy::Refuse => {
+            let currencies: Vec<&str> = wallets.iter().map(|w| w.currency.as_str()).collect();
+            return Err(VentilError::Conflict(format!(
+                "Owner {} still holds {}, debit it first or pick another policy",
+                owner_id,
+                currencies.join(", ")
+            )));
+        }
+        DeletePolicy::Cascade => (LedgerKind::Debit, Account::Issuer),
+        DeletePolicy::Graveyard => (
+            LedgerKind::Graveyard,
+            Account::Owner(find_graveyard(db, graveyard_owner_id).await?),
+        ),
+    };
+
+    for wallet in wallets {
+        let transfer = Transfer {
+            from: Account::Owner(owner_id),
+            to,
+            currency: wallet.currency,
+            amount: wallet.balance,
+        };
+        let memo = format!("Owner {} deleted", owner_id);
+        ledger::post(db, kind, &transfer, None, Some(memo)).await?;
+    }
+    Ok(())
+}
+
+async fn find_graveyard<C: ConnectionTrait>(
+    db: &C,
+    graveyard_owner_id: Option<i32>,
+) -> Result<i32, VentilError> {
+    let Some(id) = graveyard_owner_id else {
+        return Err(VentilError::BadRequest(
+            "No graveyard owner is configured, set graveyard_owner_id".to_string(),
+        ));
+    };
+
+    match Owner::find_by_id(id).one(db).await? {
+        Some(owner) if owner.deleted_at.is_none() => Ok(owner.id),
+        _ => Err(VentilError::BadRequest(format!(
+            "The graveyard owner {} does not exist",
+            id
+        ))),
+    }
+}
@@ -0,0 +1,186 @@
+use crate::serve::possession::attributes::AttributeError;
+use crate::serve::item::schema::SchemaError;
+use crate::serve::trade::logic::TradeError;
+use crate::serve::wallet::ledger::LedgerError;
+use rocket::{
+    Request, catch, catchers, Catcher,
+    http::Status,
+    response::{self, Responder, status::Custom},
+    serde::{Serialize, json::Json},
+};
+use sea_orm::{DbErr, SqlErr};
+use std::fmt;
+use utoipa::ToSchema;
+
+// Machine-readable reason of a failed request, sent as `code` in every error body
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(crate = "rocket::serde", rename_all = "snake_case")]
+pub enum ErrorCode {
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    Conflict,
+    InvalidState,
+    UnprocessableEntity,
+    DatabaseError,
+    InternalError,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> Status {
+        match self {
+            ErrorCode::BadRequest => Status::BadRequest,
+            ErrorCode::Unauthorized => Status::Unauthorized,
+            ErrorCode::Forbidden => Status::Forbidden,
+            ErrorCode::NotFound => Status::NotFound,
+            ErrorCode::Conflict | ErrorCode::InvalidState => Status::Conflict,
+            ErrorCode::UnprocessableEntity => Status::UnprocessableEntity,
+            ErrorCode::DatabaseError | ErrorCode::InternalError => Status::InternalServerError,
+        }
+    }
+
+    fn from_status(status: Status) -> Self {
+        match status.code {
+            400 => ErrorCode::BadRequest,
+            401 => ErrorCode::Unauthorized,
+            403 => ErrorCode::Forbidden,
+            404 => ErrorCode::NotFound,
+            409 => ErrorCode::Conflict,
+            422 => ErrorCode::UnprocessableEntity,
+            _ => ErrorCode::InternalError,
+        }
+    }
+}
+
+// Body of every error response
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+// Error returned by the routes in `serve`, rendered as an `ErrorResponse`
+#[derive(Debug)]
+pub enum VentilError {
+    BadRequest(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    // The request is not allowed in the current state of a trade
+    InvalidState(String),
+    Database(DbErr),
+}
+
+impl VentilError {
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            VentilError::BadRequest(_) => ErrorCode::BadRequest,
+            VentilError::Forbidden(_) => ErrorCode::Forbidden,
+            VentilError::NotFound(_) => ErrorCode::NotFound,
+            VentilError::Conflict(_) => ErrorCode::Conflict,
+            VentilError::InvalidState(_) => ErrorCode::InvalidState,
+            VentilError::Database(_) => ErrorCode::DatabaseError,
+        }
+    }
+}
+
+impl fmt::Display for VentilError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VentilError::BadRequest(message)
+            | VentilError::Forbidden(message)
+            | VentilError::NotFound(message)
+            | VentilError::Conflict(message)
+            | VentilError::InvalidState(message) => write!(f, "{}", message),
+            // Details of database errors are logged, not sent to clients
+            VentilError::Database(_) => write!(f, "Database error"),
+        }
+    }
+}
+
+// Constraint violations are caused by the request, everything else is a server error
+impl From<DbErr> for VentilError {
+    fn from(err: DbErr) -> Self {
+        match err.sql_err() {
+            Some(SqlErr::UniqueConstraintViolation(_)) => {
+                VentilError::Conflict("The record conflicts with an existing one".to_string())
+            }
+            Some(SqlErr::ForeignKeyConstraintViolation(_)) => {
+                VentilError::Conflict("The record is referenced by or references missing records".to_string())
+            }
+            _ => match err {
+                DbErr::RecordNotFound(message) => VentilError::NotFound(message),
+                err => VentilError::Database(err),
+            },
+        }
+    }
+}
+
+impl From<SchemaError> for VentilError {
+    fn from(err: SchemaError) -> Self {
+        match err {
+            SchemaError::Database(err) => err.into(),
+            err @ SchemaError::Stacked(_) => VentilError::Conflict(err.to_string()),
+            err => VentilError::BadRequest(err.to_string()),
+        }
+    }
+}
+
+impl From<AttributeError> for VentilError {
+    fn from(err: AttributeError) -> Self {
+        match err {
+            AttributeError::Database(err) => err.into(),
+            err => VentilError::BadRequest(err.to_string()),
+        }
+    }
+}
+
+impl From<LedgerError> for VentilError {
+    fn from(err: LedgerError) -> Self {
+        match err {
+            LedgerError::Database(err) => err.into(),
+            err @ LedgerError::InvalidAmount(_) => VentilError::BadRequest(err.to_string()),
+            err => VentilError::Conflict(err.to_string()),
+        }
+    }
+}
+
+impl From<TradeError> for VentilError {
+    fn from(err: TradeError) -> Self {
+        match err {
+            TradeError::Database(err) => err.into(),
+            err => VentilError::InvalidState(err.to_string()),
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for VentilError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        if let VentilError::Database(err) = &self {
+            rocket::error!("Database error on {}: {}", request.uri(), err);
+        }
+
+        let code = self.code();
+        let body = ErrorResponse {
+            code,
+            message: self.to_string(),
+        };
+        Custom(code.status(), Json(body)).respond_to(request)
+    }
+}
+
+// Errors raised outside of the routes, e.g. by request guards or malformed bodies
+#[catch(default)]
+fn default_catcher(status: Status, _request: &Request) -> Custom<Json<ErrorResponse>> {
+    let body = ErrorResponse {
+        code: ErrorCode::from_status(status),
+        message: status.reason_lossy().to_string(),
+    };
+    Custom(status, Json(body))
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    catchers![default_catcher]
+}
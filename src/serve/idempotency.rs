@@ -0,0 +1,359 @@
+use crate::config::Config;
+use crate::db::entities::idempotency;
+use crate::db::entities::prelude::Idempotency as IdempotencyEntry;
+use crate::serve::auth::Caller;
+use chrono::{DateTime, TimeDelta, Utc};
+use rocket::{
+    Request, State,
+    data::{self, Data, FromData, Limits},
+    fairing::AdHoc,
+    http::{ContentType, Header, Status},
+    request::{FromRequest, Outcome},
+    response::{self, Responder, Response},
+    serde::{DeserializeOwned, json::serde_json},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, QueryFilter,
+};
+use sha2::{Digest, Sha256};
+use std::io::Cursor;
+use std::ops::Deref;
+
+// Header carrying the key picked by the caller
+pub const HEADER: &str = "Idempotency-Key";
+
+// Set on responses that were stored by an earlier request with the same key
+pub const REPLAYED_HEADER: &str = "Idempotent-Replayed";
+
+#[derive(Debug)]
+pub enum IdempotencyError {
+    // The key is empty or too long
+    Invalid,
+    // Keys belong to an API key, so the caller has to be known
+    Unauthenticated,
+    // The key was used for another route
+    Mismatch,
+    // The key was used with another request body
+    BodyMismatch,
+    // The body could not be read or is no valid JSON
+    Body,
+    // The first request with the key has not finished yet
+    InProgress,
+    Database,
+}
+
+// Guard of routes that may be retried with an `Idempotency-Key` header.
+// The first request with a key claims it and runs, its successful response is
+// stored by the fairing; retries within the TTL get the stored response back.
+// Failed requests release the key, so they can be retried for real. A claim
+// whose response never got stored, e.g. because the server stopped, is given
+// up after the claim timeout. Routes with a body take it as `IdempotentJson`,
+// retries have to send the same body.
+pub struct Idempotency {
+    replay: Option<StoredResponse>,
+}
+
+// The row claimed by the request, read back by the fairing once the response is ready
+struct Claimed(Option<i32>);
+
+// Body hash of the stored response a retry replays, checked by `IdempotentJson`
+struct Replaying(Option<String>);
+
+impl Idempotency {
+    // The stored response when this request is a retry, the route returns it as is
+    pub fn replayed<R>(self) -> Option<Idempotent<R>> {
+        self.replay.map(Idempotent::Replayed)
+    }
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Idempotency {
+    type Error = IdempotencyError;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        let Some(key) = request.headers().get_one(HEADER) else {
+            return Outcome::Success(Idempotency { replay: None });
+        };
+
+        if key.trim().is_empty() || key.len() > 255 {
+            return Outcome::Error((Status::BadRequest, IdempotencyError::Invalid));
+        }
+
+        // The caller is cached by the request, so the API key is not looked up again
+        let caller = match request.guard::<Caller>().await {
+            Outcome::Success(caller) => caller,
+            Outcome::Error((status, _)) => {
+                return Outcome::Error((status, IdempotencyError::Unauthenticated));
+            }
+            Outcome::Forward(status) => return Outcome::Forward(status),
+        };
+
+        let (db, config) = match (
+            request.guard::<&State<DatabaseConnection>>().await,
+            request.guard::<&State<Config>>().await,
+        ) {
+            (Outcome::Success(db), Outcome::Success(config)) => (db.inner(), config.inner()),
+            _ => return Outcome::Error((Status::InternalServerError, IdempotencyError::Database)),
+        };
+
+        let method = request.method().as_str();
+        let path = request.uri().to_string();
+        let now = Utc::now();
+
+        match claim(db, caller.key_id, key, method, &path, now, KeyLifetime::from(config)).await {
+            Ok(Claim::New(id)) => {
+                request.local_cache(|| Claimed(Some(id)));
+                Outcome::Success(Idempotency { replay: None })
+            }
+            Ok(Claim::Done(entry)) if entry.method != method || entry.path != path => {
+                Outcome::Error((Status::UnprocessableEntity, IdempotencyError::Mismatch))
+            }
+            Ok(Claim::Done(entry)) => match entry.status {
+                Some(status) => {
+                    request.local_cache(|| Replaying(entry.body_hash.clone()));
+                    Outcome::Success(Idempotency {
+                        replay: Some(StoredResponse {
+                            status: Status::new(status as u16),
+                            location: entry.location,
+                            body: entry.body.unwrap_or_default(),
+                        }),
+                    })
+                }
+                None => Outcome::Error((Status::Conflict, IdempotencyError::InProgress)),
+            },
+            // Another request claimed the key in the meantime
+            Err(err) if matches!(err.sql_err(), Some(sea_orm::SqlErr::UniqueConstraintViolation(_))) => {
+                Outcome::Error((Status::Conflict, IdempotencyError::InProgress))
+            }
+            Err(err) => {
+                rocket::error!("Database error on {}: {}", request.uri(), err);
+                Outcome::Error((Status::InternalServerError, IdempotencyError::Database))
+            }
+        }
+    }
+}
+
+pub enum Claim {
+    // The key is new, the id is the row holding it
+    New(i32),
+    // The key was used before, the row may still wait for its response
+    Done(idempotency::Model),
+}
+
+// How long keys last: stored responses for `ttl`, claims still waiting for theirs for `claim_timeout`
+#[derive(Clone, Copy)]
+pub struct KeyLifetime {
+    pub ttl: TimeDelta,
+    pub claim_timeout: TimeDelta,
+}
+
+impl From<&Config> for KeyLifetime {
+    fn from(config: &Config) -> Self {
+        KeyLifetime {
+            ttl: seconds(config.idempotency_ttl_secs),
+            claim_timeout: seconds(config.idempotency_claim_timeout_secs),
+        }
+    }
+}
+
+// Claims `key` for the request, unless an earlier one within the TTL did
+pub async fn claim<C: ConnectionTrait>(
+    db: &C,
+    key_id: i32,
+    key: &str,
+    method: &str,
+    path: &str,
+    now: DateTime<Utc>,
+    lifetime: KeyLifetime,
+) -> Result<Claim, DbErr> {
+    let existing = IdempotencyEntry::find()
+        .filter(idempotency::Column::ApiKey.eq(key_id))
+        .filter(idempotency::Column::IdempotencyKey.eq(key))
+        .one(db)
+        .await?;
+
+    let abandoned = |entry: &idempotency::Model| {
+        entry.status.is_none() && entry.created_at + lifetime.claim_timeout <= now
+    };
+    match existing {
+        Some(entry) if entry.expires_at > now && !abandoned(&entry) => return Ok(Claim::Done(entry)),
+        // Expired keys and claims that never got their response may be used again
+        Some(entry) => {
+            IdempotencyEntry::delete_by_id(entry.id).exec(db).await?;
+        }
+        None => {}
+    }
+
+    let entry = idempotency::ActiveModel {
+        api_key: ActiveValue::set(key_id),
+        idempotency_key: ActiveValue::set(key.to_string()),
+        method: ActiveValue::set(method.to_string()),
+        path: ActiveValue::set(path.to_string()),
+        created_at: ActiveValue::set(now),
+        expires_at: ActiveValue::set(
+            now.checked_add_signed(lifetime.ttl).unwrap_or(DateTime::<Utc>::MAX_UTC),
+        ),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(Claim::New(entry.id))
+}
+
+fn seconds(secs: u64) -> TimeDelta {
+    TimeDelta::try_seconds(i64::try_from(secs).unwrap_or(i64::MAX)).unwrap_or(TimeDelta::MAX)
+}
+
+// Removes keys past their TTL, run by the scheduler
+pub async fn purge_expired<C: ConnectionTrait>(db: &C, now: DateTime<Utc>) -> Result<u64, DbErr> {
+    let purged = IdempotencyEntry::delete_many()
+        .filter(idempotency::Column::ExpiresAt.lte(now))
+        .exec(db)
+        .await?;
+    Ok(purged.rows_affected)
+}
+
+// Stores the response for the key claimed by the request, or releases the key when it failed
+pub fn fairing() -> AdHoc {
+    AdHoc::on_response("Idempotency keys", |request, response| {
+        Box::pin(async move {
+            let Claimed(Some(id)) = request.local_cache(|| Claimed(None)) else {
+                return;
+            };
+            let Some(db) = request.rocket().state::<DatabaseConnection>() else {
+                return;
+            };
+
+            let stored = if response.status().class().is_success() {
+                let body = match response.body_mut().to_bytes().await {
+                    Ok(body) => body,
+                    Err(err) => {
+                        rocket::error!(
+                            "Could not read the response to store for {}: {}",
+                            request.uri(),
+                            err
+                        );
+                        Vec::new()
+                    }
+                };
+                let entry = idempotency::ActiveModel {
+                    id: ActiveValue::unchanged(*id),
+                    status: ActiveValue::set(Some(i32::from(response.status().code))),
+                    location: ActiveValue::set(response.headers().get_one("Location").map(str::to_string)),
+                    body: ActiveValue::set(Some(String::from_utf8_lossy(&body).into_owned())),
+                    ..Default::default()
+                };
+                response.set_sized_body(body.len(), Cursor::new(body));
+                entry.update(db).await.map(|_| ())
+            } else {
+                IdempotencyEntry::delete_by_id(*id).exec(db).await.map(|_| ())
+            };
+
+            if let Err(err) = stored {
+                rocket::error!("Could not store the response for idempotency key {}: {}", id, err);
+            }
+        })
+    })
+}
+
+// JSON body of a route guarded by `Idempotency`. Its hash is kept with the claimed key,
+// a retry sending another body is refused instead of getting the first response.
+pub struct IdempotentJson<T>(pub T);
+
+impl<T> Deref for IdempotentJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: DeserializeOwned> FromData<'r> for IdempotentJson<T> {
+    type Error = IdempotencyError;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let limit = request.limits().get("json").unwrap_or(Limits::JSON);
+        let bytes = match data.open(limit).into_bytes().await {
+            Ok(bytes) if bytes.is_complete() => bytes.into_inner(),
+            Ok(_) => return data::Outcome::Error((Status::PayloadTooLarge, IdempotencyError::Body)),
+            Err(_) => return data::Outcome::Error((Status::BadRequest, IdempotencyError::Body)),
+        };
+        let hash = hex::encode(Sha256::digest(&bytes));
+
+        if let Replaying(Some(stored)) = request.local_cache(|| Replaying(None))
+            && *stored != hash
+        {
+            return data::Outcome::Error((Status::UnprocessableEntity, IdempotencyError::BodyMismatch));
+        }
+
+        if let Claimed(Some(id)) = request.local_cache(|| Claimed(None))
+            && let Some(db) = request.rocket().state::<DatabaseConnection>()
+        {
+            let entry = idempotency::ActiveModel {
+                id: ActiveValue::unchanged(*id),
+                body_hash: ActiveValue::set(Some(hash)),
+                ..Default::default()
+            };
+            if let Err(err) = entry.update(db).await {
+                rocket::error!("Could not store the body hash for idempotency key {}: {}", id, err);
+                return data::Outcome::Error((Status::InternalServerError, IdempotencyError::Database));
+            }
+        }
+
+        match serde_json::from_slice(&bytes) {
+            Ok(value) => data::Outcome::Success(IdempotentJson(value)),
+            Err(err) if err.is_data() => {
+                data::Outcome::Error((Status::UnprocessableEntity, IdempotencyError::Body))
+            }
+            Err(_) => data::Outcome::Error((Status::BadRequest, IdempotencyError::Body)),
+        }
+    }
+}
+
+// A response stored for an idempotency key
+pub struct StoredResponse {
+    status: Status,
+    location: Option<String>,
+    body: String,
+}
+
+impl<'r> Responder<'r, 'static> for StoredResponse {
+    fn respond_to(self, _request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = Response::build();
+        response
+            .status(self.status)
+            .header(Header::new(REPLAYED_HEADER, "true"));
+        if let Some(location) = self.location {
+            response.header(Header::new("Location", location));
+        }
+        if !self.body.is_empty() {
+            response
+                .header(ContentType::JSON)
+                .sized_body(self.body.len(), Cursor::new(self.body));
+        }
+        response.ok()
+    }
+}
+
+// Response of a route guarded by `Idempotency`, either produced now or stored by an earlier request
+pub enum Idempotent<R> {
+    Fresh(R),
+    Replayed(StoredResponse),
+}
+
+impl<R> From<R> for Idempotent<R> {
+    fn from(response: R) -> Self {
+        Idempotent::Fresh(response)
+    }
+}
+
+impl<'r, R: Responder<'r, 'static>> Responder<'r, 'static> for Idempotent<R> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        match self {
+            Idempotent::Fresh(response) => response.respond_to(request),
+            Idempotent::Replayed(stored) => stored.respond_to(request),
+        }
+    }
+}
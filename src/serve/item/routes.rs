@@ -1,14 +1,32 @@
-use crate::db::entities::{item, prelude::Item};
+use crate::config::Config;
+use crate::db::entities::item::{Quality, Rarity};
+use crate::db::entities::item_attribute::AttributeKind;
+use crate::db::entities::{
+    item, item_attribute, loot_entry, loot_table, possession,
+    prelude::{Item, ItemAttribute, LootEntry, LootTable, Possession},
+};
+use crate::serve::auth::Admin;
+use crate::serve::deletion::{self, DeletePolicy};
+use crate::serve::error::{ErrorResponse, VentilError};
+use crate::serve::pagination::{Page, PageRequest, SortOrder};
+use crate::serve::possession::stacks;
+use crate::serve::item::schema::{
+    ImportReport, ItemChange, SchemaAttribute, SchemaError, SchemaFile, SchemaItem,
+};
 use rocket::{
-    Build, Rocket, State, delete, get,
+    Build, FromForm, Rocket, State, delete, get,
     http::Status,
     post, put,
-    response::status::{Created, NotFound},
+    response::status::Created,
     routes,
-    serde::{Deserialize, Serialize, json::Json},
+    serde::{Deserialize, Serialize, json::{Json, Value}},
 };
-use sea_orm::{ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, ModelTrait};
-use utoipa::{ToSchema, OpenApi};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, LoaderTrait, ModelTrait, PaginatorTrait, QueryFilter, TransactionTrait,
+};
+use std::collections::HashSet;
+use utoipa::{IntoParams, ToSchema, OpenApi};
 
 pub trait ItemRoutes {
     fn mount_items(self) -> Self;
@@ -23,35 +41,201 @@ impl ItemRoutes for Rocket<Build> {
                 get_item_by_id,
                 create_item,
                 update_item,
-                delete_item
+                delete_item,
+                restore_item,
+                export_schema,
+                import_schema
             ],
         )
     }
 }
 
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemAttributeResponse {
+    pub name: String,
+    pub kind: AttributeKind,
+    #[schema(value_type = Object)]
+    pub value: Value,
+}
+
 #[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct ItemResponse {
     pub item_type: String,
     pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub quality: Quality,
+    pub rarity: Rarity,
+    pub slot: Option<String>,
+    pub tradable: bool,
+    pub marketable: bool,
+    pub stackable: bool,
+    pub attributes: Vec<ItemAttributeResponse>,
 }
 
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemAttributeRequest {
+    pub name: String,
+    pub kind: AttributeKind,
+    #[schema(value_type = Object)]
+    pub value: Value,
+}
+
+// Everything but the type is optional, the name falls back to the item type
 #[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct CreateItemRequest {
     pub item_type: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub quality: Quality,
+    #[serde(default)]
+    pub rarity: Rarity,
+    #[serde(default)]
+    pub slot: Option<String>,
+    #[serde(default = "default_true")]
+    pub tradable: bool,
+    #[serde(default = "default_true")]
+    pub marketable: bool,
+    #[serde(default)]
+    pub stackable: bool,
+    #[serde(default)]
+    pub attributes: Vec<ItemAttributeRequest>,
 }
 
+// Replaces the whole definition, attributes included
 #[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct UpdateItemRequest {
     pub item_type: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub quality: Quality,
+    #[serde(default)]
+    pub rarity: Rarity,
+    #[serde(default)]
+    pub slot: Option<String>,
+    #[serde(default = "default_true")]
+    pub tradable: bool,
+    #[serde(default = "default_true")]
+    pub marketable: bool,
+    #[serde(default)]
+    pub stackable: bool,
+    #[serde(default)]
+    pub attributes: Vec<ItemAttributeRequest>,
 }
 
-#[derive(Serialize, Deserialize, ToSchema)]
-#[serde(crate = "rocket::serde")]
-pub struct ApiResponse {
-    pub message: String,
+fn default_true() -> bool {
+    true
+}
+
+fn to_response(item: item::Model, attributes: Vec<item_attribute::Model>) -> ItemResponse {
+    ItemResponse {
+        item_type: item.item_type,
+        id: item.id,
+        name: item.name,
+        description: item.description,
+        quality: item.quality,
+        rarity: item.rarity,
+        slot: item.slot,
+        tradable: item.tradable,
+        marketable: item.marketable,
+        stackable: item.stackable,
+        attributes: attributes
+            .into_iter()
+            .map(|a| ItemAttributeResponse {
+                name: a.name,
+                kind: a.kind,
+                value: a.value,
+            })
+            .collect(),
+    }
+}
+
+// Every attribute name at most once, with a value matching its kind
+fn validate_attributes(attributes: &[ItemAttributeRequest]) -> Result<(), VentilError> {
+    let mut names = HashSet::new();
+    for attribute in attributes {
+        if !names.insert(attribute.name.as_str()) {
+            return Err(VentilError::BadRequest(format!(
+                "Attribute {} is listed more than once",
+                attribute.name
+            )));
+        }
+        if !attribute.kind.accepts(&attribute.value) {
+            return Err(VentilError::BadRequest(format!(
+                "Attribute {} does not hold a {:?} value",
+                attribute.name, attribute.kind
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Replaces the attributes of an item with the given list
+async fn save_attributes<C: ConnectionTrait>(
+    db: &C,
+    item_id: i32,
+    attributes: &[ItemAttributeRequest],
+) -> Result<Vec<item_attribute::Model>, DbErr> {
+    ItemAttribute::delete_many()
+        .filter(item_attribute::Column::Item.eq(item_id))
+        .exec(db)
+        .await?;
+
+    let mut saved = Vec::new();
+    for attribute in attributes {
+        let new_attribute = item_attribute::ActiveModel {
+            item: ActiveValue::set(item_id),
+            name: ActiveValue::set(attribute.name.clone()),
+            kind: ActiveValue::set(attribute.kind.clone()),
+            value: ActiveValue::set(attribute.value.clone()),
+            ..Default::default()
+        };
+        saved.push(new_attribute.insert(db).await?);
+    }
+    Ok(saved)
+}
+
+fn not_found(id: i32) -> VentilError {
+    VentilError::NotFound(format!("Item with id {} not found", id))
+}
+
+// The item with this id, unless it was deleted
+async fn find_active<C: ConnectionTrait>(db: &C, id: i32) -> Result<item::Model, VentilError> {
+    Item::find_by_id(id)
+        .filter(item::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or_else(|| not_found(id))
+}
+
+// Query of the item list, filters are combined
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ItemQuery {
+    /// Maximum number of items to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return items after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Only items of this type
+    pub item_type: Option<String>,
+    /// Only items equipped in this slot
+    pub slot: Option<String>,
+    /// Only tradable or untradable items
+    pub tradable: Option<bool>,
 }
 
 /// Get all items
@@ -59,27 +243,44 @@ pub struct ApiResponse {
     get,
     path = "/items",
     tags = ["items"],  // Add this line to assign tag
+    params(ItemQuery),
     responses(
-        (status = 200, description = "List all items successfully", body = [ItemResponse])
+        (status = 200, description = "List items successfully", body = Page<ItemResponse>),
+        (status = 500, description = "Database error", body = ErrorResponse)
     )
 )]
-#[get("/")]
-pub async fn get_all_items(database: &State<DatabaseConnection>) -> Json<Vec<ItemResponse>> {
-    // Implementation remains the same
+#[get("/?<query..>")]
+pub async fn get_all_items(
+    query: ItemQuery,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Page<ItemResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
+    let page = PageRequest::new(query.limit, query.after, query.order);
 
-    let items = Item::find()
-        .all(db)
-        .await
-        .unwrap_or_default()
+    let mut select = Item::find().filter(item::Column::DeletedAt.is_null());
+    if let Some(item_type) = query.item_type {
+        select = select.filter(item::Column::ItemType.eq(item_type));
+    }
+    if let Some(slot) = query.slot {
+        select = select.filter(item::Column::Slot.eq(slot));
+    }
+    if let Some(tradable) = query.tradable {
+        select = select.filter(item::Column::Tradable.eq(tradable));
+    }
+
+    let items = page.apply(select, item::Column::Id).all(db).await?;
+    let (items, next) = page.finish(items, |i| i.id);
+
+    // Attributes are loaded separately, a joined query would count them against the limit
+    let attributes = items.load_many(ItemAttribute, db).await?;
+
+    let items = items
         .into_iter()
-        .map(|i| ItemResponse {
-            item_type: i.item_type,
-            id: i.id,
-        })
+        .zip(attributes)
+        .map(|(i, attributes)| to_response(i, attributes))
         .collect::<Vec<ItemResponse>>();
 
-    Json(items)
+    Ok(Json(Page { items, next }))
 }
 
 /// Get item by ID
@@ -92,25 +293,26 @@ pub async fn get_all_items(database: &State<DatabaseConnection>) -> Json<Vec<Ite
     ),
     responses(
         (status = 200, description = "Item found successfully", body = ItemResponse),
-        (status = 404, description = "Item not found", body = ApiResponse)
+        (status = 404, description = "Item not found", body = ErrorResponse)
     )
 )]
 #[get("/<id>")]
 pub async fn get_item_by_id(
     id: i32,
     database: &State<DatabaseConnection>,
-) -> Result<Json<ItemResponse>, NotFound<Json<ApiResponse>>> {
+) -> Result<Json<ItemResponse>, VentilError> {
     // Implementation remains the same
     let db = database as &DatabaseConnection;
 
-    match Item::find_by_id(id).one(db).await {
-        Ok(Some(item)) => Ok(Json(ItemResponse {
-            item_type: item.item_type,
-            id: item.id,
-        })),
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Item with id {} not found", id),
-        }))),
+    let mut found = Item::find_by_id(id)
+        .filter(item::Column::DeletedAt.is_null())
+        .find_with_related(ItemAttribute)
+        .all(db)
+        .await?;
+
+    match found.pop() {
+        Some((item, attributes)) => Ok(Json(to_response(item, attributes))),
+        None => Err(not_found(id)),
     }
 }
 
@@ -120,29 +322,51 @@ pub async fn get_item_by_id(
     path = "/items",
     tags = ["items"],  // Add this line
     request_body = CreateItemRequest,
+    security(("api_key" = [])),
     responses(
-        (status = 201, description = "Item created successfully", body = ItemResponse)
+        (status = 201, description = "Item created successfully", body = ItemResponse),
+        (status = 400, description = "Invalid attribute list", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
 #[post("/", data = "<item_data>")]
 pub async fn create_item(
+    _admin: Admin,
     item_data: Json<CreateItemRequest>,
     database: &State<DatabaseConnection>,
-) -> Created<Json<ItemResponse>> {
-    // Implementation remains the same
+) -> Result<Created<Json<ItemResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
 
+    validate_attributes(&item_data.attributes)?;
+
     let new_item = item::ActiveModel {
         item_type: ActiveValue::set(item_data.item_type.clone()),
+        name: ActiveValue::set(
+            item_data.name.clone().unwrap_or_else(|| item_data.item_type.clone()),
+        ),
+        description: ActiveValue::set(item_data.description.clone()),
+        quality: ActiveValue::set(item_data.quality.clone()),
+        rarity: ActiveValue::set(item_data.rarity.clone()),
+        slot: ActiveValue::set(item_data.slot.clone()),
+        tradable: ActiveValue::set(item_data.tradable),
+        marketable: ActiveValue::set(item_data.marketable),
+        stackable: ActiveValue::set(item_data.stackable),
         ..Default::default()
     };
 
-    let insert_result = new_item.insert(db).await.unwrap();
+    // The item and its attributes are stored together
+    let created = async {
+        let txn = db.begin().await?;
+        let item = new_item.insert(&txn).await?;
+        let attributes = save_attributes(&txn, item.id, &item_data.attributes).await?;
+        txn.commit().await?;
+        Ok::<_, DbErr>((item, attributes))
+    };
+
+    let (item, attributes) = created.await?;
 
-    Created::new("/").body(Json(ItemResponse {
-        item_type: item_data.item_type.clone(),
-        id: insert_result.id,
-    }))
+    Ok(Created::new(format!("/items/{}", item.id)).body(Json(to_response(item, attributes))))
 }
 
 /// Update an existing item
@@ -154,43 +378,65 @@ pub async fn create_item(
         ("id" = i32, Path, description = "Item identifier")
     ),
     request_body = UpdateItemRequest,
+    security(("api_key" = [])),
     responses(
         (status = 200, description = "Item updated successfully", body = ItemResponse),
-        (status = 404, description = "Item not found", body = ApiResponse)
+        (status = 400, description = "Invalid attribute list", body = ErrorResponse),
+        (status = 404, description = "Item not found", body = ErrorResponse),
+        (status = 409, description = "Item is held in stacks and can not stop being stackable", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
 #[put("/<id>", data = "<item_data>")]
 pub async fn update_item(
+    _admin: Admin,
     id: i32,
     item_data: Json<UpdateItemRequest>,
     database: &State<DatabaseConnection>,
-) -> Result<Json<ItemResponse>, NotFound<Json<ApiResponse>>> {
-    // Implementation remains the same
+) -> Result<Json<ItemResponse>, VentilError> {
     let db = database as &DatabaseConnection;
 
+    validate_attributes(&item_data.attributes)?;
+
     // Find the item to update
-    let item_result = Item::find_by_id(id).one(db).await;
+    let item = find_active(db, id).await?;
 
-    match item_result {
-        Ok(Some(item)) => {
-            // Create an active model from the found item
-            let mut item_active: item::ActiveModel = item.into();
+    if item.stackable && !item_data.stackable && stacks::is_stacked(db, id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Item {} is held in stacks of more than one, it has to stay stackable",
+            id
+        )));
+    }
 
-            // Update fields
-            item_active.item_type = ActiveValue::set(item_data.item_type.clone());
+    // Create an active model from the found item
+    let mut item_active: item::ActiveModel = item.into();
 
-            // Save changes
-            let updated_item = item_active.update(db).await.unwrap();
+    // Update fields
+    item_active.item_type = ActiveValue::set(item_data.item_type.clone());
+    item_active.name = ActiveValue::set(
+        item_data.name.clone().unwrap_or_else(|| item_data.item_type.clone()),
+    );
+    item_active.description = ActiveValue::set(item_data.description.clone());
+    item_active.quality = ActiveValue::set(item_data.quality.clone());
+    item_active.rarity = ActiveValue::set(item_data.rarity.clone());
+    item_active.slot = ActiveValue::set(item_data.slot.clone());
+    item_active.tradable = ActiveValue::set(item_data.tradable);
+    item_active.marketable = ActiveValue::set(item_data.marketable);
+    item_active.stackable = ActiveValue::set(item_data.stackable);
 
-            Ok(Json(ItemResponse {
-                item_type: updated_item.item_type,
-                id: updated_item.id,
-            }))
-        }
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Item with id {} not found", id),
-        }))),
-    }
+    // Save changes
+    let updated = async {
+        let txn = db.begin().await?;
+        let item = item_active.update(&txn).await?;
+        let attributes = save_attributes(&txn, item.id, &item_data.attributes).await?;
+        txn.commit().await?;
+        Ok::<_, DbErr>((item, attributes))
+    };
+
+    let (item, attributes) = updated.await?;
+
+    Ok(Json(to_response(item, attributes)))
 }
 
 /// Delete an item
@@ -199,34 +445,162 @@ pub async fn update_item(
     path = "/items/{id}",
     tags = ["items"],  // Add this line
     params(
-        ("id" = i32, Path, description = "Item identifier")
+        ("id" = i32, Path, description = "Item identifier"),
+        ("policy" = Option<DeletePolicy>, Query, description = "What happens to the possessions of the item, refuse by default")
     ),
+    security(("api_key" = [])),
     responses(
         (status = 204, description = "Item deleted successfully"),
-        (status = 404, description = "Item not found", body = ApiResponse)
+        (status = 400, description = "No graveyard owner is configured", body = ErrorResponse),
+        (status = 404, description = "Item not found", body = ErrorResponse),
+        (status = 409, description = "Item is still held as a possession or dropped by a loot box, or possessions are locked or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
-#[delete("/<id>")]
+#[delete("/<id>?<policy>")]
 pub async fn delete_item(
+    _admin: Admin,
     id: i32,
+    policy: Option<DeletePolicy>,
     database: &State<DatabaseConnection>,
-) -> Result<Status, NotFound<Json<ApiResponse>>> {
-    // Implementation remains the same
+    config: &State<Config>,
+) -> Result<Status, VentilError> {
     let db = database as &DatabaseConnection;
 
+    // Everything is undone when one step fails
+    let txn = db.begin().await?;
+
     // Find the item to delete
-    let item_result = Item::find_by_id(id).one(db).await;
+    let item = find_active(&txn, id).await?;
 
-    match item_result {
-        Ok(Some(item)) => {
-            // Delete the item
-            item.delete(db).await.unwrap();
-            Ok(Status::NoContent)
-        }
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Item with id {} not found", id),
-        }))),
+    // Loot boxes would go on handing out the item, they have to be changed first
+    let crates = LootTable::find()
+        .filter(loot_table::Column::CrateItem.eq(item.id))
+        .count(&txn)
+        .await?;
+    let drops = LootEntry::find()
+        .filter(loot_entry::Column::Item.eq(item.id))
+        .count(&txn)
+        .await?;
+    if crates + drops > 0 {
+        return Err(VentilError::Conflict(format!(
+            "Item {} is used by a loot box",
+            item.id
+        )));
+    }
+
+    let possessions = Possession::find()
+        .filter(possession::Column::Item.eq(item.id))
+        .all(&txn)
+        .await?;
+    deletion::settle_possessions(&txn, possessions, policy.unwrap_or_default(), config.graveyard_owner_id)
+        .await?;
+
+    let mut active_model: item::ActiveModel = item.into();
+    active_model.deleted_at = ActiveValue::set(Some(chrono::Utc::now()));
+    active_model.update(&txn).await?;
+
+    txn.commit().await?;
+    Ok(Status::NoContent)
+}
+
+/// Undo the deletion of an item
+#[utoipa::path(
+    post,
+    path = "/items/{id}/restore",
+    tags = ["items"],
+    params(
+        ("id" = i32, Path, description = "Item identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Item restored, possessions removed by the deletion stay gone", body = ItemResponse),
+        (status = 404, description = "Item not found", body = ErrorResponse),
+        (status = 409, description = "Item is not deleted", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/<id>/restore")]
+pub async fn restore_item(
+    _admin: Admin,
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<ItemResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let item = Item::find_by_id(id).one(db).await?.ok_or_else(|| not_found(id))?;
+
+    if item.deleted_at.is_none() {
+        return Err(VentilError::Conflict(format!("Item {} is not deleted", id)));
     }
+
+    let mut active_model: item::ActiveModel = item.into();
+    active_model.deleted_at = ActiveValue::set(None);
+    let restored = active_model.update(db).await?;
+    let attributes = restored.find_related(ItemAttribute).all(db).await?;
+
+    Ok(Json(to_response(restored, attributes)))
+}
+
+/// Export every item definition as a schema file
+#[utoipa::path(
+    get,
+    path = "/items/schema",
+    tags = ["items"],
+    responses(
+        (status = 200, description = "Item schema exported successfully", body = SchemaFile),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+)]
+#[get("/schema")]
+pub async fn export_schema(
+    database: &State<DatabaseConnection>,
+) -> Result<Json<SchemaFile>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    Ok(Json(SchemaFile::export(db).await?))
+}
+
+/// Create or update item definitions from a schema file
+#[utoipa::path(
+    post,
+    path = "/items/schema",
+    tags = ["items"],
+    params(
+        ("dry_run" = Option<bool>, Query, description = "Only report what would change")
+    ),
+    request_body = SchemaFile,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Item schema imported successfully", body = ImportReport),
+        (status = 400, description = "Invalid schema", body = ErrorResponse),
+        (status = 409, description = "The schema makes an item that is held in stacks non-stackable", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/schema?<dry_run>", data = "<schema>")]
+pub async fn import_schema(
+    _admin: Admin,
+    dry_run: Option<bool>,
+    schema: Json<SchemaFile>,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<ImportReport>, VentilError> {
+    let db = database as &DatabaseConnection;
+    let dry_run = dry_run.unwrap_or(false);
+
+    let imported = async {
+        let txn = db.begin().await?;
+        let report = schema.import(&txn, dry_run).await?;
+        if !dry_run {
+            txn.commit().await?;
+        }
+        Ok::<_, SchemaError>(report)
+    };
+
+    Ok(Json(imported.await?))
 }
 
 // Create the OpenAPI documentation using the utoipa macro
@@ -237,10 +611,28 @@ pub async fn delete_item(
         get_item_by_id,
         create_item,
         update_item,
-        delete_item
+        delete_item,
+        restore_item,
+        export_schema,
+        import_schema
     ),
     components(
-        schemas(ItemResponse, CreateItemRequest, UpdateItemRequest, ApiResponse)
+        schemas(
+            ItemResponse,
+            ItemAttributeResponse,
+            CreateItemRequest,
+            UpdateItemRequest,
+            ItemAttributeRequest,
+            DeletePolicy,
+            Quality,
+            Rarity,
+            AttributeKind,
+            SchemaFile,
+            SchemaItem,
+            SchemaAttribute,
+            ImportReport,
+            ItemChange
+        )
     ),
     tags(
         (name = "items", description = "Item management API")
@@ -0,0 +1,402 @@
+use crate::db::entities::item::{Quality, Rarity};
+use crate::db::entities::item_attribute::AttributeKind;
+use crate::db::entities::prelude::{Item, ItemAttribute};
+use crate::db::entities::{item, item_attribute};
+use crate::serve::possession::stacks;
+use rocket::serde::{Deserialize, Serialize, json};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, prelude::Json,
+};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+use utoipa::ToSchema;
+
+// Version written by the exporter and the only one the importer reads
+pub const SCHEMA_VERSION: u32 = 1;
+
+// An item definition file as kept by game designers, in JSON or TOML
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SchemaFile {
+    pub version: u32,
+    #[serde(default)]
+    pub items: Vec<SchemaItem>,
+}
+
+// Items are matched on their name, which is unique within a schema
+#[derive(Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SchemaItem {
+    pub name: String,
+    pub item_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub quality: Quality,
+    #[serde(default)]
+    pub rarity: Rarity,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slot: Option<String>,
+    #[serde(default = "default_true")]
+    pub tradable: bool,
+    #[serde(default = "default_true")]
+    pub marketable: bool,
+    #[serde(default)]
+    pub stackable: bool,
+    #[serde(default)]
+    pub attributes: Vec<SchemaAttribute>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SchemaAttribute {
+    pub name: String,
+    pub kind: AttributeKind,
+    #[schema(value_type = Object)]
+    pub value: Json,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+// An item whose stored definition differs from the schema, with the fields that differ
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ItemChange {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub created: Vec<String>,
+    pub updated: Vec<ItemChange>,
+    pub unchanged: Vec<String>,
+}
+
+impl fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.created {
+            writeln!(f, "   created    {name}")?;
+        }
+        for change in &self.updated {
+            writeln!(f, "   updated    {} ({})", change.name, change.fields.join(", "))?;
+        }
+        write!(
+            f,
+            "{} created, {} updated, {} unchanged{}",
+            self.created.len(),
+            self.updated.len(),
+            self.unchanged.len(),
+            if self.dry_run { " (dry run, nothing was written)" } else { "" }
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum SchemaError {
+    Io(std::io::Error),
+    Parse(String),
+    UnsupportedFormat(String),
+    UnsupportedVersion(u32),
+    DuplicateItem(String),
+    DuplicateAttribute { item: String, attribute: String },
+    InvalidValue { item: String, attribute: String, kind: AttributeKind },
+    // Possessions of the item are stacked, so it can not stop being stackable
+    Stacked(String),
+    Database(DbErr),
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SchemaError::Io(err) => write!(f, "Could not access the schema file: {}", err),
+            SchemaError::Parse(err) => write!(f, "Could not parse the schema: {}", err),
+            SchemaError::UnsupportedFormat(path) => {
+                write!(f, "{} is not a .json or .toml file", path)
+            }
+            SchemaError::UnsupportedVersion(version) => write!(
+                f,
+                "Schema version {} is not supported, expected {}",
+                version, SCHEMA_VERSION
+            ),
+            SchemaError::DuplicateItem(name) => {
+                write!(f, "Item {} is defined more than once", name)
+            }
+            SchemaError::DuplicateAttribute { item, attribute } => {
+                write!(f, "Attribute {} of item {} is listed more than once", attribute, item)
+            }
+            SchemaError::InvalidValue { item, attribute, kind } => write!(
+                f,
+                "Attribute {} of item {} does not hold a {:?} value",
+                attribute, item, kind
+            ),
+            SchemaError::Stacked(item) => write!(
+                f,
+                "Item {} is held in stacks of more than one, it has to stay stackable",
+                item
+            ),
+            SchemaError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+impl From<DbErr> for SchemaError {
+    fn from(err: DbErr) -> Self {
+        SchemaError::Database(err)
+    }
+}
+
+impl From<std::io::Error> for SchemaError {
+    fn from(err: std::io::Error) -> Self {
+        SchemaError::Io(err)
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum SchemaFormat {
+    Json,
+    Toml,
+}
+
+impl SchemaFormat {
+    pub fn from_path(path: &Path) -> Result<SchemaFormat, SchemaError> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(SchemaFormat::Json),
+            Some("toml") => Ok(SchemaFormat::Toml),
+            _ => Err(SchemaError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+}
+
+impl SchemaFile {
+    pub fn parse(contents: &str, format: SchemaFormat) -> Result<SchemaFile, SchemaError> {
+        let schema: SchemaFile = match format {
+            SchemaFormat::Json => {
+                json::from_str(contents).map_err(|err| SchemaError::Parse(err.to_string()))?
+            }
+            SchemaFormat::Toml => {
+                toml::from_str(contents).map_err(|err| SchemaError::Parse(err.to_string()))?
+            }
+        };
+        schema.validate()?;
+        Ok(schema)
+    }
+
+    pub fn render(&self, format: SchemaFormat) -> Result<String, SchemaError> {
+        match format {
+            SchemaFormat::Json => {
+                json::to_pretty_string(self).map_err(|err| SchemaError::Parse(err.to_string()))
+            }
+            SchemaFormat::Toml => {
+                toml::to_string_pretty(self).map_err(|err| SchemaError::Parse(err.to_string()))
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), SchemaError> {
+        if self.version != SCHEMA_VERSION {
+            return Err(SchemaError::UnsupportedVersion(self.version));
+        }
+
+        let mut names = HashSet::new();
+        for item in &self.items {
+            if !names.insert(item.name.as_str()) {
+                return Err(SchemaError::DuplicateItem(item.name.clone()));
+            }
+
+            let mut attributes = HashSet::new();
+            for attribute in &item.attributes {
+                if !attributes.insert(attribute.name.as_str()) {
+                    return Err(SchemaError::DuplicateAttribute {
+                        item: item.name.clone(),
+                        attribute: attribute.name.clone(),
+                    });
+                }
+                if !attribute.kind.accepts(&attribute.value) {
+                    return Err(SchemaError::InvalidValue {
+                        item: item.name.clone(),
+                        attribute: attribute.name.clone(),
+                        kind: attribute.kind.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // Every item definition that is not deleted, ordered by ID
+    pub async fn export<C: ConnectionTrait>(db: &C) -> Result<SchemaFile, DbErr> {
+        let items = Item::find()
+            .filter(item::Column::DeletedAt.is_null())
+            .order_by_asc(item::Column::Id)
+            .find_with_related(ItemAttribute)
+            .all(db)
+            .await?;
+
+        Ok(SchemaFile {
+            version: SCHEMA_VERSION,
+            items: items
+                .into_iter()
+                .map(|(item, attributes)| SchemaItem::from_rows(item, attributes))
+                .collect(),
+        })
+    }
+
+    // Creates or updates every item of the schema, matched by name among the items not deleted.
+    // Items missing from the schema are left alone, possessions may still refer to them.
+    // Run it in a transaction and only commit when the report is not a dry run.
+    pub async fn import<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        dry_run: bool,
+    ) -> Result<ImportReport, SchemaError> {
+        self.validate()?;
+
+        let mut report = ImportReport {
+            dry_run,
+            created: Vec::new(),
+            updated: Vec::new(),
+            unchanged: Vec::new(),
+        };
+
+        for schema_item in &self.items {
+            let stored = Item::find()
+                .filter(item::Column::Name.eq(&schema_item.name))
+                .filter(item::Column::DeletedAt.is_null())
+                .order_by_asc(item::Column::Id)
+                .find_with_related(ItemAttribute)
+                .all(db)
+                .await?
+                .into_iter()
+                .next();
+
+            let Some((stored, attributes)) = stored else {
+                if !dry_run {
+                    schema_item.store(db, None).await?;
+                }
+                report.created.push(schema_item.name.clone());
+                continue;
+            };
+
+            let fields = schema_item.changed_fields(&SchemaItem::from_rows(stored.clone(), attributes));
+            if fields.is_empty() {
+                report.unchanged.push(schema_item.name.clone());
+                continue;
+            }
+
+            if stored.stackable && !schema_item.stackable && stacks::is_stacked(db, stored.id).await? {
+                return Err(SchemaError::Stacked(schema_item.name.clone()));
+            }
+
+            if !dry_run {
+                schema_item.store(db, Some(stored)).await?;
+            }
+            report.updated.push(ItemChange {
+                name: schema_item.name.clone(),
+                fields,
+            });
+        }
+
+        Ok(report)
+    }
+}
+
+impl SchemaItem {
+    fn from_rows(item: item::Model, attributes: Vec<item_attribute::Model>) -> SchemaItem {
+        SchemaItem {
+            name: item.name,
+            item_type: item.item_type,
+            description: item.description,
+            quality: item.quality,
+            rarity: item.rarity,
+            slot: item.slot,
+            tradable: item.tradable,
+            marketable: item.marketable,
+            stackable: item.stackable,
+            attributes: attributes
+                .into_iter()
+                .map(|a| SchemaAttribute {
+                    name: a.name,
+                    kind: a.kind,
+                    value: a.value,
+                })
+                .collect(),
+        }
+    }
+
+    fn changed_fields(&self, stored: &SchemaItem) -> Vec<String> {
+        let mut fields = Vec::new();
+        let mut check = |changed: bool, field: &str| {
+            if changed {
+                fields.push(field.to_string());
+            }
+        };
+
+        check(self.item_type != stored.item_type, "item_type");
+        check(self.description != stored.description, "description");
+        check(self.quality != stored.quality, "quality");
+        check(self.rarity != stored.rarity, "rarity");
+        check(self.slot != stored.slot, "slot");
+        check(self.tradable != stored.tradable, "tradable");
+        check(self.marketable != stored.marketable, "marketable");
+        check(self.stackable != stored.stackable, "stackable");
+
+        // Attribute order carries no meaning
+        let mut wanted = self.attributes.clone();
+        let mut current = stored.attributes.clone();
+        wanted.sort_by(|a, b| a.name.cmp(&b.name));
+        current.sort_by(|a, b| a.name.cmp(&b.name));
+        check(wanted != current, "attributes");
+
+        fields
+    }
+
+    // Writes the definition over `existing`, or as a new item
+    async fn store<C: ConnectionTrait>(
+        &self,
+        db: &C,
+        existing: Option<item::Model>,
+    ) -> Result<(), DbErr> {
+        let mut active: item::ActiveModel = match existing {
+            Some(existing) => existing.into(),
+            None => Default::default(),
+        };
+        active.name = ActiveValue::set(self.name.clone());
+        active.item_type = ActiveValue::set(self.item_type.clone());
+        active.description = ActiveValue::set(self.description.clone());
+        active.quality = ActiveValue::set(self.quality.clone());
+        active.rarity = ActiveValue::set(self.rarity.clone());
+        active.slot = ActiveValue::set(self.slot.clone());
+        active.tradable = ActiveValue::set(self.tradable);
+        active.marketable = ActiveValue::set(self.marketable);
+        active.stackable = ActiveValue::set(self.stackable);
+        let stored = active.save(db).await?;
+        let item_id = stored.id.unwrap();
+
+        ItemAttribute::delete_many()
+            .filter(item_attribute::Column::Item.eq(item_id))
+            .exec(db)
+            .await?;
+
+        for attribute in &self.attributes {
+            item_attribute::ActiveModel {
+                item: ActiveValue::set(item_id),
+                name: ActiveValue::set(attribute.name.clone()),
+                kind: ActiveValue::set(attribute.kind.clone()),
+                value: ActiveValue::set(attribute.value.clone()),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?;
+        }
+
+        Ok(())
+    }
+}
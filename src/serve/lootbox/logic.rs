@@ -0,0 +1,73 @@
+use crate::db::entities::loot_entry::Model as LootEntryModel;
+use crate::serve::error::VentilError;
+use rand::Rng;
+use rand::distributions::{Distribution, WeightedIndex};
+
+// Rejects loot boxes rolling more often than `max_rolls` allows
+pub fn check_rolls(rolls: i32, max_rolls: i32) -> Result<(), VentilError> {
+    if rolls > max_rolls {
+        return Err(VentilError::BadRequest(format!(
+            "A loot box may roll at most {} times, not {}",
+            max_rolls, rolls
+        )));
+    }
+    Ok(())
+}
+
+// Picks `rolls` item ids from the entries, weighted by each entry's weight
+pub fn roll_items<R: Rng>(
+    entries: &[LootEntryModel],
+    rolls: i32,
+    max_rolls: i32,
+    rng: &mut R,
+) -> Result<Vec<i32>, VentilError> {
+    check_rolls(rolls, max_rolls)?;
+
+    let weights = entries.iter().map(|entry| entry.weight.max(0) as u32);
+
+    let distribution = match WeightedIndex::new(weights) {
+        Ok(distribution) => distribution,
+        Err(_) => return Ok(Vec::new()),
+    };
+
+    Ok((0..rolls)
+        .map(|_| entries[distribution.sample(rng)].item)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    fn entry(item: i32, weight: i32) -> LootEntryModel {
+        LootEntryModel { id: item, loot_table: 1, item, weight }
+    }
+
+    #[test]
+    fn roll_items_test() {
+        let entries = [entry(1, 1), entry(2, 3)];
+
+        // The same seed rolls the same drops
+        let rolled = roll_items(&entries, 400, 1000, &mut StdRng::seed_from_u64(7)).unwrap();
+        let again = roll_items(&entries, 400, 1000, &mut StdRng::seed_from_u64(7)).unwrap();
+        assert_eq!(rolled, again);
+        assert_eq!(rolled.len(), 400);
+
+        // Both entries drop, the heavier one about three times as often
+        let common = rolled.iter().filter(|&&item| item == 2).count();
+        let rare = rolled.iter().filter(|&&item| item == 1).count();
+        assert_eq!(common + rare, 400);
+        assert!(rare > 50 && common > 2 * rare);
+
+        // Entries without weight never drop
+        let rolled = roll_items(&[entry(1, 0), entry(2, 5)], 20, 1000, &mut StdRng::seed_from_u64(7)).unwrap();
+        assert!(rolled.iter().all(|&item| item == 2));
+
+        assert!(matches!(
+            roll_items(&entries, 11, 10, &mut StdRng::seed_from_u64(7)),
+            Err(VentilError::BadRequest(_))
+        ));
+    }
+}
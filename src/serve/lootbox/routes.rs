@@ -0,0 +1,481 @@
+use crate::config::Config;
+use crate::db::entities::possession::Origin;
+use crate::db::entities::{item, loot_entry, loot_table, owner, prelude::*};
+use crate::serve::auth::{Admin, Caller};
+use crate::serve::error::{ErrorResponse, VentilError};
+use crate::serve::idempotency::{Idempotency, Idempotent, IdempotentJson};
+use crate::serve::lootbox::logic::{check_rolls, roll_items};
+use crate::serve::possession::routes::{PossessionResponse, to_responses};
+use crate::serve::possession::stacks;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::logic::Trade;
+use rocket::{
+    Build, Rocket, State, delete, get,
+    http::Status,
+    post,
+    response::status::Created,
+    routes,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait,
+    QueryFilter, TransactionTrait,
+};
+use std::collections::HashMap;
+use utoipa::{OpenApi, ToSchema};
+
+pub trait LootboxRoutes {
+    fn mount_lootboxes(self) -> Self;
+}
+
+impl LootboxRoutes for Rocket<Build> {
+    fn mount_lootboxes(self) -> Self {
+        self.mount(
+            "/lootboxes",
+            routes![
+                get_all_lootboxes,
+                get_lootbox_by_id,
+                create_lootbox,
+                delete_lootbox,
+                open_lootbox
+            ],
+        )
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct LootEntryResponse {
+    pub id: i32,
+    pub item_id: i32,
+    pub weight: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct LootboxResponse {
+    pub id: i32,
+    pub name: String,
+    pub crate_item_id: i32,
+    pub rolls: i32,
+    pub entries: Vec<LootEntryResponse>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateLootEntryRequest {
+    pub item_id: i32,
+    pub weight: i32,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct CreateLootboxRequest {
+    pub name: String,
+    pub crate_item_id: i32,
+    pub rolls: i32,
+    pub entries: Vec<CreateLootEntryRequest>,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct OpenLootboxRequest {
+    pub owner_id: i32,
+    pub possession_id: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct OpenLootboxResponse {
+    pub consumed_possession_id: i32,
+    pub possessions: Vec<PossessionResponse>,
+}
+
+fn to_response(table: loot_table::Model, entries: Vec<loot_entry::Model>) -> LootboxResponse {
+    LootboxResponse {
+        id: table.id,
+        name: table.name,
+        crate_item_id: table.crate_item,
+        rolls: table.rolls,
+        entries: entries
+            .into_iter()
+            .map(|e| LootEntryResponse {
+                id: e.id,
+                item_id: e.item,
+                weight: e.weight,
+            })
+            .collect(),
+    }
+}
+
+fn not_found(id: i32) -> VentilError {
+    VentilError::NotFound(format!("Loot box with id {} not found", id))
+}
+
+/// Get all loot boxes
+#[utoipa::path(
+    get,
+    path = "/lootboxes",
+    tags = ["lootboxes"],
+    responses(
+        (status = 200, description = "List all loot boxes successfully", body = [LootboxResponse]),
+        (status = 500, description = "Database error", body = ErrorResponse)
+    )
+)]
+#[get("/")]
+pub async fn get_all_lootboxes(
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Vec<LootboxResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let tables = LootTable::find()
+        .find_with_related(LootEntry)
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|(table, entries)| to_response(table, entries))
+        .collect::<Vec<LootboxResponse>>();
+
+    Ok(Json(tables))
+}
+
+/// Get loot box by ID
+#[utoipa::path(
+    get,
+    path = "/lootboxes/{id}",
+    tags = ["lootboxes"],
+    params(
+        ("id" = i32, Path, description = "Loot box identifier")
+    ),
+    responses(
+        (status = 200, description = "Loot box found successfully", body = LootboxResponse),
+        (status = 404, description = "Loot box not found", body = ErrorResponse)
+    )
+)]
+#[get("/<id>")]
+pub async fn get_lootbox_by_id(
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<LootboxResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let mut results = LootTable::find_by_id(id)
+        .find_with_related(LootEntry)
+        .all(db)
+        .await?;
+
+    match results.pop() {
+        Some((table, entries)) => Ok(Json(to_response(table, entries))),
+        None => Err(not_found(id)),
+    }
+}
+
+/// Create a new loot box with its drop table
+#[utoipa::path(
+    post,
+    path = "/lootboxes",
+    tags = ["lootboxes"],
+    request_body = CreateLootboxRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 201, description = "Loot box created successfully", body = LootboxResponse),
+        (status = 400, description = "Invalid request data, or more rolls than allowed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/", data = "<lootbox_data>")]
+pub async fn create_lootbox(
+    _admin: Admin,
+    lootbox_data: Json<CreateLootboxRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+) -> Result<Created<Json<LootboxResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    if lootbox_data.rolls < 1 {
+        return Err(VentilError::BadRequest("A loot box must roll at least once".to_string()));
+    }
+    check_rolls(lootbox_data.rolls, config.max_lootbox_rolls)?;
+
+    if lootbox_data.entries.is_empty() {
+        return Err(VentilError::BadRequest(
+            "A loot box needs at least one drop table entry".to_string(),
+        ));
+    }
+
+    if let Some(entry) = lootbox_data.entries.iter().find(|e| e.weight < 1) {
+        return Err(VentilError::BadRequest(format!(
+            "Entry for item {} must have a positive weight",
+            entry.item_id
+        )));
+    }
+
+    // Validate that the crate and every drop refer to existing items
+    let item_ids = std::iter::once(lootbox_data.crate_item_id)
+        .chain(lootbox_data.entries.iter().map(|e| e.item_id));
+
+    for item_id in item_ids {
+        let item = Item::find_by_id(item_id)
+            .filter(item::Column::DeletedAt.is_null())
+            .one(db)
+            .await?;
+        if item.is_none() {
+            return Err(VentilError::BadRequest(format!("Item with id {} not found", item_id)));
+        }
+    }
+
+    let txn = db.begin().await?;
+
+    let new_table = loot_table::ActiveModel {
+        name: ActiveValue::set(lootbox_data.name.clone()),
+        crate_item: ActiveValue::set(lootbox_data.crate_item_id),
+        rolls: ActiveValue::set(lootbox_data.rolls),
+        ..Default::default()
+    };
+
+    let table = new_table.insert(&txn).await?;
+
+    let mut entries = Vec::with_capacity(lootbox_data.entries.len());
+    for entry in &lootbox_data.entries {
+        let new_entry = loot_entry::ActiveModel {
+            loot_table: ActiveValue::set(table.id),
+            item: ActiveValue::set(entry.item_id),
+            weight: ActiveValue::set(entry.weight),
+            ..Default::default()
+        };
+
+        entries.push(new_entry.insert(&txn).await?);
+    }
+
+    txn.commit().await?;
+
+    Ok(Created::new(format!("/lootboxes/{}", table.id)).body(Json(to_response(table, entries))))
+}
+
+/// Delete a loot box and its drop table
+#[utoipa::path(
+    delete,
+    path = "/lootboxes/{id}",
+    tags = ["lootboxes"],
+    params(
+        ("id" = i32, Path, description = "Loot box identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "Loot box deleted successfully"),
+        (status = 404, description = "Loot box not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[delete("/<id>")]
+pub async fn delete_lootbox(
+    _admin: Admin,
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Status, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let table = LootTable::find_by_id(id).one(db).await?.ok_or_else(|| not_found(id))?;
+
+    let txn = db.begin().await?;
+    LootEntry::delete_many()
+        .filter(loot_entry::Column::LootTable.eq(table.id))
+        .exec(&txn)
+        .await?;
+    table.delete(&txn).await?;
+    txn.commit().await?;
+
+    Ok(Status::NoContent)
+}
+
+/// Open a loot box, consuming the crate possession and granting the rolled drops
+#[utoipa::path(
+    post,
+    path = "/lootboxes/{id}/open",
+    tags = ["lootboxes"],
+    params(
+        ("id" = i32, Path, description = "Loot box identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = OpenLootboxRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 201, description = "Loot box opened successfully", body = OpenLootboxResponse),
+        (status = 400, description = "Possession cannot open this loot box, or it rolls more often than allowed", body = ErrorResponse),
+        (status = 404, description = "Loot box, owner, crate item or possession not found", body = ErrorResponse),
+        (status = 409, description = "Possession is locked by an open trade or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner", body = ErrorResponse)
+    )
+)]
+#[post("/<id>/open", data = "<open_data>")]
+pub async fn open_lootbox(
+    caller: Caller,
+    idempotency: Idempotency,
+    id: i32,
+    open_data: IdempotentJson<OpenLootboxRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+) -> Result<Idempotent<Created<Json<OpenLootboxResponse>>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if !caller.can_act_for(open_data.owner_id) {
+        return Err(VentilError::Forbidden(format!(
+            "Not allowed to act for owner {}",
+            open_data.owner_id
+        )));
+    }
+
+    let txn = db.begin().await?;
+
+    let table = LootTable::find_by_id(id).one(&txn).await?.ok_or_else(|| not_found(id))?;
+
+    // Deleted owners can not be granted drops, and crates of deleted items can not be opened
+    let owner = Owner::find_by_id(open_data.owner_id)
+        .filter(owner::Column::DeletedAt.is_null())
+        .one(&txn)
+        .await?;
+    if owner.is_none() {
+        return Err(VentilError::NotFound(format!("Owner with id {} not found", open_data.owner_id)));
+    }
+    let crate_item = Item::find_by_id(table.crate_item)
+        .filter(item::Column::DeletedAt.is_null())
+        .one(&txn)
+        .await?;
+    if crate_item.is_none() {
+        return Err(VentilError::NotFound(format!("Item with id {} not found", table.crate_item)));
+    }
+
+    let crate_possession = Possession::find_by_id(open_data.possession_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| {
+            VentilError::NotFound(format!(
+                "Possession with id {} not found",
+                open_data.possession_id
+            ))
+        })?;
+
+    // Verify ownership and that the possession is this loot box's crate
+    if crate_possession.owner != open_data.owner_id {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} is not owned by owner {}",
+            crate_possession.id, open_data.owner_id
+        )));
+    }
+
+    if crate_possession.item != table.crate_item {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} is not a crate for loot box {}",
+            crate_possession.id, table.id
+        )));
+    }
+
+    // A crate offered in a trade or held in escrow can not be opened
+    if let Some(trade_id) = Trade::find_locking(&txn, crate_possession.id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Possession {} is locked by trade {}",
+            crate_possession.id, trade_id
+        )));
+    }
+    if let Some(entry) = Escrow::find_holding(&txn, crate_possession.id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Possession {} is held in escrow until {}",
+            crate_possession.id,
+            entry.release_at.to_rfc3339()
+        )));
+    }
+
+    // Deleted items do not drop anymore
+    let entries = table
+        .find_related(LootEntry)
+        .inner_join(Item)
+        .filter(item::Column::DeletedAt.is_null())
+        .all(&txn)
+        .await?;
+
+    // Loot boxes created before the limit was lowered can not be opened anymore
+    let rolled_items = roll_items(&entries, table.rolls, config.max_lootbox_rolls, &mut rand::thread_rng())?;
+
+    if rolled_items.is_empty() {
+        return Err(VentilError::BadRequest(format!(
+            "Loot box {} has no drops to roll",
+            table.id
+        )));
+    }
+
+    // A stack of crates loses one, the last one is deleted
+    let consumed_possession_id = crate_possession.id;
+    stacks::take(&txn, crate_possession, 1).await?;
+
+    // Drops of the same item are granted together, so stackable ones end up in a single stack
+    let mut drops: Vec<(i32, i32)> = Vec::new();
+    for item_id in rolled_items {
+        match drops.iter_mut().find(|(id, _)| *id == item_id) {
+            Some((_, count)) => *count += 1,
+            None => drops.push((item_id, 1)),
+        }
+    }
+    let items: HashMap<i32, item::Model> = Item::find()
+        .filter(item::Column::Id.is_in(drops.iter().map(|(id, _)| *id)))
+        .all(&txn)
+        .await?
+        .into_iter()
+        .map(|i| (i.id, i))
+        .collect();
+
+    let mut possessions = Vec::new();
+    for (item_id, count) in drops {
+        let Some(item) = items.get(&item_id) else {
+            return Err(VentilError::NotFound(format!("Item with id {} not found", item_id)));
+        };
+        let granted = stacks::grant(&txn, open_data.owner_id, item, count, Origin::Unboxed).await?;
+        possessions.extend(granted);
+    }
+
+    let possessions = to_responses(&txn, possessions).await?;
+
+    txn.commit().await?;
+
+    Ok(
+        Created::new(format!("/possessions/owner/{}", open_data.owner_id))
+            .body(Json(OpenLootboxResponse {
+                consumed_possession_id,
+                possessions,
+            }))
+            .into(),
+    )
+}
+
+// Create the OpenAPI documentation struct
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        get_all_lootboxes,
+        get_lootbox_by_id,
+        create_lootbox,
+        delete_lootbox,
+        open_lootbox
+    ),
+    components(
+        schemas(
+            LootboxResponse,
+            LootEntryResponse,
+            CreateLootboxRequest,
+            CreateLootEntryRequest,
+            OpenLootboxRequest,
+            OpenLootboxResponse
+        )
+    ),
+    tags(
+        (name = "lootboxes", description = "Loot box management API")
+    )
+)]
+pub struct LootboxApiDoc;
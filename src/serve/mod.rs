@@ -1,5 +1,12 @@
 pub mod serve_main;
-mod possession;
+pub mod possession;
 mod owner;
-mod item;
-mod trade;
\ No newline at end of file
+pub mod item;
+mod lootbox;
+pub mod trade;
+pub mod wallet;
+pub mod auth;
+pub mod pagination;
+pub mod deletion;
+pub mod idempotency;
+pub mod error;
\ No newline at end of file
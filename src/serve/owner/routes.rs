@@ -1,14 +1,29 @@
-use crate::db::entities::{owner, prelude::Owner};
+use crate::config::Config;
+use crate::db::entities::trade::TradeState;
+use crate::db::entities::{api_key::{self, Role}, owner, possession, trade_history, prelude::{ApiKey, Owner, Possession}};
+use crate::serve::auth::{self, Admin, Caller};
+use crate::serve::deletion::{self, DeletePolicy};
+use crate::serve::error::{ErrorResponse, VentilError};
+use crate::serve::pagination::{Page, PageRequest, SortOrder};
+use crate::serve::trade::events::TradeEvents;
+use crate::serve::trade::history::TradeRecord;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::logic::Trade;
+use crate::serve::trade::routes::{EscrowResponse, TradeHistoryQuery, TradeHistoryResponse};
+use chrono::{DateTime, Utc};
 use rocket::{
-    Build, Rocket, State, delete, get,
+    Build, FromForm, Rocket, State, delete, get,
     http::Status,
-    post,
-    response::status::{Created, NotFound},
+    patch, post,
+    response::status::Created,
     routes,
-    serde::{Deserialize, Serialize, json::Json},
+    serde::{Deserialize, Serialize, json::{Json, Value}},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait,
+    QueryFilter, TransactionTrait,
 };
-use sea_orm::{ActiveModelTrait, DatabaseConnection, EntityTrait, ModelTrait};
-use utoipa::{ToSchema, OpenApi};
+use utoipa::{IntoParams, ToSchema, OpenApi};
 
 pub trait OwnerRoutes {
     fn mount_owners(self) -> Self;
@@ -18,7 +33,19 @@ impl OwnerRoutes for Rocket<Build> {
     fn mount_owners(self) -> Self {
         self.mount(
             "/owners",
-            routes![get_all_owners, get_owner_by_id, create_owner, delete_owner],
+            routes![
+                get_all_owners,
+                get_owner_by_id,
+                get_owner_by_external_id,
+                get_owner_trades,
+                get_owner_escrow,
+                create_owner,
+                update_owner,
+                delete_owner,
+                restore_owner,
+                create_owner_key,
+                delete_owner_key
+            ],
         )
     }
 }
@@ -27,19 +54,108 @@ impl OwnerRoutes for Rocket<Build> {
 #[serde(crate = "rocket::serde")]
 pub struct OwnerResponse {
     pub id: i32,
+    /// Account of the owner in the game
+    pub external_id: Option<String>,
+    pub display_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub banned: bool,
+    pub trade_banned: bool,
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Value>,
 }
 
+// The id and creation time are handed out by the server, everything else is optional
 #[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct CreateOwnerRequest {
-    // Empty since Owner doesn't have any fields other than ID
-    // which is auto-generated
+    #[serde(default)]
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub banned: bool,
+    #[serde(default)]
+    pub trade_banned: bool,
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Value>,
+}
+
+// Only the fields that are sent change, the others keep their value
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct UpdateOwnerRequest {
+    #[serde(default)]
+    pub external_id: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub banned: Option<bool>,
+    #[serde(default)]
+    pub trade_banned: Option<bool>,
+    /// Replaces the whole metadata object
+    #[serde(default)]
+    #[schema(value_type = Option<Object>)]
+    pub metadata: Option<Value>,
 }
 
+// Returned once when a key is issued, only its hash is stored
 #[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
-pub struct ApiResponse {
-    pub message: String,
+pub struct ApiKeyResponse {
+    pub id: i32,
+    pub owner_id: i32,
+    pub key: String,
+}
+
+impl From<owner::Model> for OwnerResponse {
+    fn from(owner: owner::Model) -> Self {
+        OwnerResponse {
+            id: owner.id,
+            external_id: owner.external_id,
+            display_name: owner.display_name,
+            created_at: owner.created_at,
+            banned: owner.banned,
+            trade_banned: owner.trade_banned,
+            metadata: owner.metadata,
+        }
+    }
+}
+
+fn not_found(id: i32) -> VentilError {
+    VentilError::NotFound(format!("Owner with id {} not found", id))
+}
+
+// The owner with this id, unless it was deleted
+async fn find_active<C: ConnectionTrait>(db: &C, id: i32) -> Result<owner::Model, VentilError> {
+    Owner::find_by_id(id)
+        .filter(owner::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or_else(|| not_found(id))
+}
+
+// External ids name an account, so an empty one is a mistake of the caller
+fn validate_external_id(external_id: Option<&str>) -> Result<(), VentilError> {
+    match external_id {
+        Some(id) if id.trim().is_empty() => Err(VentilError::BadRequest(
+            "external_id can not be empty".to_string(),
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Query of the owner list
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct OwnerQuery {
+    /// Maximum number of owners to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return owners after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending
+    #[param(inline)]
+    pub order: Option<SortOrder>,
 }
 
 /// Get all owners
@@ -47,23 +163,31 @@ pub struct ApiResponse {
     get,
     path = "/owners",
     tags = ["owners"],
+    params(OwnerQuery),
     responses(
-        (status = 200, description = "List all owners successfully", body = [OwnerResponse])
+        (status = 200, description = "List owners successfully", body = Page<OwnerResponse>),
+        (status = 500, description = "Database error", body = ErrorResponse)
     )
 )]
-#[get("/")]
-pub async fn get_all_owners(database: &State<DatabaseConnection>) -> Json<Vec<OwnerResponse>> {
+#[get("/?<query..>")]
+pub async fn get_all_owners(
+    query: OwnerQuery,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Page<OwnerResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
+    let page = PageRequest::new(query.limit, query.after, query.order);
 
-    let owners = Owner::find()
-        .all(db)
-        .await
-        .unwrap_or_default()
+    // Deleted owners only come back through the restore route
+    let select = Owner::find().filter(owner::Column::DeletedAt.is_null());
+    let owners = page.apply(select, owner::Column::Id).all(db).await?;
+    let (owners, next) = page.finish(owners, |o| o.id);
+
+    let items = owners
         .into_iter()
-        .map(|o| OwnerResponse { id: o.id })
+        .map(OwnerResponse::from)
         .collect::<Vec<OwnerResponse>>();
 
-    Json(owners)
+    Ok(Json(Page { items, next }))
 }
 
 /// Get owner by ID
@@ -76,22 +200,140 @@ pub async fn get_all_owners(database: &State<DatabaseConnection>) -> Json<Vec<Ow
     ),
     responses(
         (status = 200, description = "Owner found successfully", body = OwnerResponse),
-        (status = 404, description = "Owner not found", body = ApiResponse)
+        (status = 404, description = "Owner not found", body = ErrorResponse)
     )
 )]
 #[get("/<id>")]
 pub async fn get_owner_by_id(
     id: i32,
     database: &State<DatabaseConnection>,
-) -> Result<Json<OwnerResponse>, NotFound<Json<ApiResponse>>> {
+) -> Result<Json<OwnerResponse>, VentilError> {
     let db = database as &DatabaseConnection;
 
-    match Owner::find_by_id(id).one(db).await {
-        Ok(Some(owner)) => Ok(Json(OwnerResponse { id: owner.id })),
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Owner with id {} not found", id),
-        }))),
+    let owner = find_active(db, id).await?;
+
+    Ok(Json(OwnerResponse::from(owner)))
+}
+
+/// Get owner by the id of their account in the game
+#[utoipa::path(
+    get,
+    path = "/owners/by-external/{external_id}",
+    tags = ["owners"],
+    params(
+        ("external_id" = String, Path, description = "Account identifier in the game")
+    ),
+    responses(
+        (status = 200, description = "Owner found successfully", body = OwnerResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse)
+    )
+)]
+#[get("/by-external/<external_id>", rank = 2)]
+pub async fn get_owner_by_external_id(
+    external_id: &str,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<OwnerResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let owner = Owner::find()
+        .filter(owner::Column::ExternalId.eq(external_id))
+        .filter(owner::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            VentilError::NotFound(format!("Owner with external id {} not found", external_id))
+        })?;
+
+    Ok(Json(OwnerResponse::from(owner)))
+}
+
+/// Get the executed trades an owner took part in
+#[utoipa::path(
+    get,
+    path = "/owners/{id}/trades",
+    tags = ["owners"],
+    params(
+        ("id" = i32, Path, description = "Owner identifier"),
+        TradeHistoryQuery
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Trade history found successfully", body = Page<TradeHistoryResponse>),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not view this owner's trades", body = ErrorResponse)
+    )
+)]
+#[get("/<id>/trades?<query..>")]
+pub async fn get_owner_trades(
+    caller: Caller,
+    id: i32,
+    query: TradeHistoryQuery,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Page<TradeHistoryResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+    let order = query.order.unwrap_or(SortOrder::Desc);
+    let page = PageRequest::new(query.limit, query.after, Some(order));
+
+    // Owners see their own trades, support staff use an admin key
+    if !caller.can_act_for(id) {
+        return Err(VentilError::Forbidden(format!(
+            "Not allowed to view the trades of owner {}",
+            id
+        )));
     }
+
+    let owner = find_active(db, id).await?;
+
+    let select = page.apply(TradeRecord::owner_query(owner.id), trade_history::Column::Id);
+    let records = TradeRecord::find_with(db, select).await?;
+    let (records, next) = page.finish(records, |r| r.history.id);
+
+    let items = records.iter().map(TradeHistoryResponse::from).collect();
+
+    Ok(Json(Page { items, next }))
+}
+
+/// Get the possessions held in escrow for or from an owner
+#[utoipa::path(
+    get,
+    path = "/owners/{id}/escrow",
+    tags = ["owners"],
+    params(
+        ("id" = i32, Path, description = "Owner identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Held possessions found successfully", body = [EscrowResponse]),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not view this owner's escrow", body = ErrorResponse)
+    )
+)]
+#[get("/<id>/escrow")]
+pub async fn get_owner_escrow(
+    caller: Caller,
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Vec<EscrowResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    if !caller.can_act_for(id) {
+        return Err(VentilError::Forbidden(format!(
+            "Not allowed to view the escrow of owner {}",
+            id
+        )));
+    }
+
+    let owner = find_active(db, id).await?;
+
+    let responses = Escrow::find_by_owner(db, owner.id)
+        .await?
+        .iter()
+        .map(EscrowResponse::from)
+        .collect();
+
+    Ok(Json(responses))
 }
 
 /// Create a new owner
@@ -100,29 +342,98 @@ pub async fn get_owner_by_id(
     path = "/owners",
     tags = ["owners"],
     request_body = CreateOwnerRequest,
+    security(("api_key" = [])),
     responses(
-        (status = 201, description = "Owner created successfully", body = OwnerResponse)
+        (status = 201, description = "Owner created successfully", body = OwnerResponse),
+        (status = 400, description = "Invalid request data", body = ErrorResponse),
+        (status = 409, description = "Another owner has the external id", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
-#[post("/", data = "<_owner_data>")]
+#[post("/", data = "<owner_data>")]
 pub async fn create_owner(
-    _owner_data: Json<CreateOwnerRequest>,
+    _admin: Admin,
+    owner_data: Json<CreateOwnerRequest>,
     database: &State<DatabaseConnection>,
-) -> Created<Json<OwnerResponse>> {
+) -> Result<Created<Json<OwnerResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
+    let owner_data = owner_data.into_inner();
+
+    validate_external_id(owner_data.external_id.as_deref())?;
 
-    // Create active model
+    // Create active model, the unique index rejects a taken external id
     let new_owner = owner::ActiveModel {
+        created_at: ActiveValue::set(chrono::Utc::now()),
+        external_id: ActiveValue::set(owner_data.external_id),
+        display_name: ActiveValue::set(owner_data.display_name),
+        banned: ActiveValue::set(owner_data.banned),
+        trade_banned: ActiveValue::set(owner_data.trade_banned),
+        metadata: ActiveValue::set(owner_data.metadata),
         ..Default::default()
     };
 
     // Insert and get the created owner
-    let insert_result = new_owner.insert(db).await.unwrap();
+    let insert_result = new_owner.insert(db).await?;
 
     // Return with 201 Created status
-    Created::new("/").body(Json(OwnerResponse {
-        id: insert_result.id,
-    }))
+    Ok(Created::new(format!("/owners/{}", insert_result.id))
+        .body(Json(OwnerResponse::from(insert_result))))
+}
+
+/// Change the profile of an owner
+#[utoipa::path(
+    patch,
+    path = "/owners/{id}",
+    tags = ["owners"],
+    params(
+        ("id" = i32, Path, description = "Owner identifier")
+    ),
+    request_body = UpdateOwnerRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Owner updated successfully", body = OwnerResponse),
+        (status = 400, description = "Invalid request data", body = ErrorResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 409, description = "Another owner has the external id", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[patch("/<id>", data = "<owner_data>")]
+pub async fn update_owner(
+    _admin: Admin,
+    id: i32,
+    owner_data: Json<UpdateOwnerRequest>,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<OwnerResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+    let owner_data = owner_data.into_inner();
+
+    validate_external_id(owner_data.external_id.as_deref())?;
+
+    let owner = find_active(db, id).await?;
+
+    let mut active_model: owner::ActiveModel = owner.into();
+    if let Some(external_id) = owner_data.external_id {
+        active_model.external_id = ActiveValue::set(Some(external_id));
+    }
+    if let Some(display_name) = owner_data.display_name {
+        active_model.display_name = ActiveValue::set(Some(display_name));
+    }
+    if let Some(banned) = owner_data.banned {
+        active_model.banned = ActiveValue::set(banned);
+    }
+    if let Some(trade_banned) = owner_data.trade_banned {
+        active_model.trade_banned = ActiveValue::set(trade_banned);
+    }
+    if let Some(metadata) = owner_data.metadata {
+        active_model.metadata = ActiveValue::set(Some(metadata));
+    }
+
+    let updated = active_model.update(db).await?;
+
+    Ok(Json(OwnerResponse::from(updated)))
 }
 
 /// Delete an owner
@@ -131,33 +442,196 @@ pub async fn create_owner(
     path = "/owners/{id}",
     tags = ["owners"],
     params(
-        ("id" = i32, Path, description = "Owner identifier")
+        ("id" = i32, Path, description = "Owner identifier"),
+        ("policy" = Option<DeletePolicy>, Query, description = "What happens to the owner's possessions and balances, refuse by default")
     ),
+    security(("api_key" = [])),
     responses(
-        (status = 204, description = "Owner deleted successfully"),
-        (status = 404, description = "Owner not found", body = ApiResponse)
+        (status = 204, description = "Owner deleted successfully, their open trades are cancelled and their keys revoked"),
+        (status = 400, description = "No graveyard owner is configured", body = ErrorResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 409, description = "Owner still holds possessions or currency, or possessions are locked or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
-#[delete("/<id>")]
+#[delete("/<id>?<policy>")]
 pub async fn delete_owner(
+    _admin: Admin,
     id: i32,
+    policy: Option<DeletePolicy>,
     database: &State<DatabaseConnection>,
-) -> Result<Status, NotFound<Json<ApiResponse>>> {
+    config: &State<Config>,
+    events: &State<TradeEvents>,
+) -> Result<Status, VentilError> {
     let db = database as &DatabaseConnection;
 
+    if config.graveyard_owner_id == Some(id) {
+        return Err(VentilError::Conflict(format!(
+            "Owner {} is the graveyard owner and can not be deleted",
+            id
+        )));
+    }
+
+    // Everything is undone when one step fails
+    let txn = db.begin().await?;
+
     // Find the owner to delete
-    let owner_result = Owner::find_by_id(id).one(db).await;
+    let owner = find_active(&txn, id).await?;
 
-    match owner_result {
-        Ok(Some(owner)) => {
-            // Delete the owner
-            owner.delete(db).await.unwrap();
-            Ok(Status::NoContent)
-        }
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Owner with id {} not found", id),
-        }))),
+    // Held possessions are delivered or returned by the escrow, which needs both owners
+    if !Escrow::find_by_owner(&txn, owner.id).await?.is_empty() {
+        return Err(VentilError::Conflict(format!(
+            "Owner {} has possessions held in escrow",
+            owner.id
+        )));
+    }
+
+    // Open trades can not go on without the owner, cancelling them releases their offers
+    let now = chrono::Utc::now();
+    let mut cancelled = Vec::new();
+    for mut trade in Trade::find_open_by_owner(&txn, owner.id).await? {
+        trade.close(&txn, TradeState::Cancelled, now).await?;
+        cancelled.extend(trade.take_events());
+    }
+
+    let possessions = Possession::find()
+        .filter(possession::Column::Owner.eq(owner.id))
+        .all(&txn)
+        .await?;
+    let policy = policy.unwrap_or_default();
+    deletion::settle_possessions(&txn, possessions, policy, config.graveyard_owner_id).await?;
+    deletion::settle_wallets(&txn, owner.id, policy, config.graveyard_owner_id).await?;
+
+    ApiKey::delete_many()
+        .filter(api_key::Column::Owner.eq(owner.id))
+        .exec(&txn)
+        .await?;
+
+    let mut active_model: owner::ActiveModel = owner.into();
+    active_model.deleted_at = ActiveValue::set(Some(now));
+    active_model.update(&txn).await?;
+
+    txn.commit().await?;
+    events.publish(cancelled);
+
+    Ok(Status::NoContent)
+}
+
+/// Undo the deletion of an owner
+#[utoipa::path(
+    post,
+    path = "/owners/{id}/restore",
+    tags = ["owners"],
+    params(
+        ("id" = i32, Path, description = "Owner identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Owner restored, possessions and keys removed by the deletion stay gone", body = OwnerResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 409, description = "Owner is not deleted", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/<id>/restore")]
+pub async fn restore_owner(
+    _admin: Admin,
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<OwnerResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let owner = Owner::find_by_id(id).one(db).await?.ok_or_else(|| not_found(id))?;
+
+    if owner.deleted_at.is_none() {
+        return Err(VentilError::Conflict(format!("Owner {} is not deleted", id)));
     }
+
+    let mut active_model: owner::ActiveModel = owner.into();
+    active_model.deleted_at = ActiveValue::set(None);
+    let restored = active_model.update(db).await?;
+
+    Ok(Json(OwnerResponse::from(restored)))
+}
+
+/// Issue a new API key for an owner
+#[utoipa::path(
+    post,
+    path = "/owners/{id}/keys",
+    tags = ["owners"],
+    params(
+        ("id" = i32, Path, description = "Owner identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 201, description = "API key issued successfully", body = ApiKeyResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse)
+    )
+)]
+#[post("/<id>/keys")]
+pub async fn create_owner_key(
+    _admin: Admin,
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Created<Json<ApiKeyResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let owner = find_active(db, id).await?;
+
+    let (api_key, key) = auth::issue_key(db, Some(owner.id), Role::Owner).await?;
+
+    Ok(Created::new(format!("/owners/{}/keys/{}", owner.id, api_key.id))
+        .body(Json(ApiKeyResponse {
+            id: api_key.id,
+            owner_id: owner.id,
+            key,
+        })))
+}
+
+/// Revoke an API key of an owner
+#[utoipa::path(
+    delete,
+    path = "/owners/{id}/keys/{key_id}",
+    tags = ["owners"],
+    params(
+        ("id" = i32, Path, description = "Owner identifier"),
+        ("key_id" = i32, Path, description = "API key identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 204, description = "API key revoked successfully"),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse),
+        (status = 404, description = "API key not found", body = ErrorResponse)
+    )
+)]
+#[delete("/<id>/keys/<key_id>")]
+pub async fn delete_owner_key(
+    _admin: Admin,
+    id: i32,
+    key_id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Status, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let deleted = ApiKey::delete_many()
+        .filter(api_key::Column::Id.eq(key_id))
+        .filter(api_key::Column::Owner.eq(id))
+        .exec(db)
+        .await?;
+
+    if deleted.rows_affected == 0 {
+        return Err(VentilError::NotFound(format!(
+            "API key {} of owner {} not found",
+            key_id, id
+        )));
+    }
+
+    Ok(Status::NoContent)
 }
 
 // Create the OpenAPI documentation using the utoipa macro
@@ -166,11 +640,18 @@ pub async fn delete_owner(
     paths(
         get_all_owners,
         get_owner_by_id,
+        get_owner_by_external_id,
+        get_owner_trades,
+        get_owner_escrow,
         create_owner,
-        delete_owner
+        update_owner,
+        delete_owner,
+        restore_owner,
+        create_owner_key,
+        delete_owner_key
     ),
     components(
-        schemas(OwnerResponse, CreateOwnerRequest, ApiResponse)
+        schemas(OwnerResponse, CreateOwnerRequest, UpdateOwnerRequest, ApiKeyResponse, DeletePolicy)
     ),
     tags(
         (name = "owners", description = "Owner management API")
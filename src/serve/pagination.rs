@@ -0,0 +1,68 @@
+use rocket::{FromFormField, serde::Serialize};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder, QuerySelect, Select};
+use utoipa::ToSchema;
+
+pub const DEFAULT_LIMIT: u64 = 100;
+pub const MAX_LIMIT: u64 = 1000;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, FromFormField, ToSchema)]
+#[schema(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+// One page of a list endpoint, `next` is passed as `after` to fetch the following page
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub next: Option<i32>,
+}
+
+// Keyset pagination over the id column, so pages stay stable while rows are inserted
+#[derive(Clone, Copy, Debug)]
+pub struct PageRequest {
+    pub limit: u64,
+    pub after: Option<i32>,
+    pub order: SortOrder,
+}
+
+impl PageRequest {
+    pub fn new(limit: Option<u64>, after: Option<i32>, order: Option<SortOrder>) -> Self {
+        PageRequest {
+            limit: limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT),
+            after,
+            order: order.unwrap_or_default(),
+        }
+    }
+
+    // Orders the query by id and skips to the cursor. One extra row is fetched
+    // to tell whether another page follows.
+    pub fn apply<E: EntityTrait>(&self, query: Select<E>, id: E::Column) -> Select<E> {
+        let query = match (self.order, self.after) {
+            (SortOrder::Asc, Some(after)) => query.filter(id.gt(after)),
+            (SortOrder::Desc, Some(after)) => query.filter(id.lt(after)),
+            (_, None) => query,
+        };
+
+        let query = match self.order {
+            SortOrder::Asc => query.order_by_asc(id),
+            SortOrder::Desc => query.order_by_desc(id),
+        };
+
+        query.limit(self.limit + 1)
+    }
+
+    // Drops the extra row fetched by `apply` and returns the cursor of the next page
+    pub fn finish<T>(&self, mut rows: Vec<T>, id: impl Fn(&T) -> i32) -> (Vec<T>, Option<i32>) {
+        if rows.len() as u64 <= self.limit {
+            return (rows, None);
+        }
+
+        rows.truncate(self.limit as usize);
+        let next = rows.last().map(id);
+        (rows, next)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::db::entities::item_attribute::AttributeKind;
+use crate::db::entities::possession_attribute;
+use crate::db::entities::prelude::PossessionAttribute;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    prelude::Json,
+};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum AttributeError {
+    // The value does not match the declared kind
+    InvalidValue { name: String, kind: AttributeKind },
+    // Only integer attributes can be incremented
+    NotCounter(String),
+    Database(DbErr),
+}
+
+impl fmt::Display for AttributeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttributeError::InvalidValue { name, kind } => {
+                write!(f, "Attribute {} does not hold a {:?} value", name, kind)
+            }
+            AttributeError::NotCounter(name) => {
+                write!(f, "Attribute {} is not an integer counter", name)
+            }
+            AttributeError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+impl From<DbErr> for AttributeError {
+    fn from(err: DbErr) -> Self {
+        AttributeError::Database(err)
+    }
+}
+
+async fn find<C: ConnectionTrait>(
+    db: &C,
+    possession_id: i32,
+    name: &str,
+) -> Result<Option<possession_attribute::Model>, DbErr> {
+    PossessionAttribute::find()
+        .filter(possession_attribute::Column::Possession.eq(possession_id))
+        .filter(possession_attribute::Column::Name.eq(name))
+        .one(db)
+        .await
+}
+
+// Stores the attribute on the possession, replacing any previous value
+pub async fn set<C: ConnectionTrait>(
+    db: &C,
+    possession_id: i32,
+    name: &str,
+    kind: AttributeKind,
+    value: Json,
+) -> Result<possession_attribute::Model, AttributeError> {
+    if !kind.accepts(&value) {
+        return Err(AttributeError::InvalidValue {
+            name: name.to_string(),
+            kind,
+        });
+    }
+
+    let attribute = match find(db, possession_id, name).await? {
+        Some(existing) => {
+            let mut active: possession_attribute::ActiveModel = existing.into();
+            active.kind = ActiveValue::set(kind);
+            active.value = ActiveValue::set(value);
+            active.update(db).await?
+        }
+        None => {
+            possession_attribute::ActiveModel {
+                possession: ActiveValue::set(possession_id),
+                name: ActiveValue::set(name.to_string()),
+                kind: ActiveValue::set(kind),
+                value: ActiveValue::set(value),
+                ..Default::default()
+            }
+            .insert(db)
+            .await?
+        }
+    };
+
+    Ok(attribute)
+}
+
+// Adds `by` to an integer attribute, a missing counter starts at zero
+pub async fn increment<C: ConnectionTrait>(
+    db: &C,
+    possession_id: i32,
+    name: &str,
+    by: i64,
+) -> Result<possession_attribute::Model, AttributeError> {
+    let current = match find(db, possession_id, name).await? {
+        Some(existing) if existing.kind == AttributeKind::Integer => {
+            existing.value.as_i64().unwrap_or_default()
+        }
+        Some(_) => return Err(AttributeError::NotCounter(name.to_string())),
+        None => 0,
+    };
+
+    set(
+        db,
+        possession_id,
+        name,
+        AttributeKind::Integer,
+        Json::from(current.saturating_add(by)),
+    )
+    .await
+}
+
+pub async fn remove<C: ConnectionTrait>(
+    db: &C,
+    possession_id: i32,
+    name: &str,
+) -> Result<(), AttributeError> {
+    PossessionAttribute::delete_many()
+        .filter(possession_attribute::Column::Possession.eq(possession_id))
+        .filter(possession_attribute::Column::Name.eq(name))
+        .exec(db)
+        .await?;
+    Ok(())
+}
@@ -1 +1,3 @@
-pub mod routes;
\ No newline at end of file
+pub mod routes;
+pub mod attributes;
+pub mod stacks;
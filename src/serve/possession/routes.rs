@@ -1,17 +1,28 @@
-use crate::db::entities::{possession, prelude::*};
+use crate::db::entities::item_attribute::AttributeKind;
+use crate::db::entities::possession::Origin;
+use crate::db::entities::{item, owner, possession, prelude::*};
+use crate::serve::auth::{Admin, Caller};
+use crate::serve::error::{ErrorResponse, VentilError};
+use crate::serve::idempotency::{Idempotency, Idempotent, IdempotentJson};
+use crate::serve::pagination::{Page, PageRequest, SortOrder};
+use crate::serve::possession::attributes::{self, AttributeError};
+use crate::serve::possession::stacks;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::logic::Trade;
 use rocket::{
-    Build, Rocket, State, delete, get,
+    Build, FromForm, Rocket, State, delete, get,
     http::Status,
-    post, put,
-    response::status::{Created, NotFound},
+    patch, post, put,
+    response::status::Created,
     routes,
-    serde::{Deserialize, Serialize, json::Json},
+    serde::{Deserialize, Serialize, json::{Json, Value}},
 };
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, EntityTrait, ModelTrait,
-    QueryFilter,
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection, DbErr,
+    EntityTrait, LoaderTrait, ModelTrait, QueryFilter, QueryOrder, TransactionTrait,
 };
-use utoipa::{ToSchema, OpenApi};
+use std::collections::{HashMap, HashSet};
+use utoipa::{IntoParams, ToSchema, OpenApi};
 
 pub trait PossessionRoutes {
     fn mount_possessions(self) -> Self;
@@ -27,6 +38,11 @@ impl PossessionRoutes for Rocket<Build> {
                 create_possession,
                 update_possession,
                 delete_possession,
+                grant_possessions,
+                revoke_possessions,
+                update_possession_attributes,
+                split_possession,
+                merge_possessions,
                 get_possessions_by_owner,
                 get_possessions_by_item
             ],
@@ -35,35 +51,361 @@ impl PossessionRoutes for Rocket<Build> {
 }
 
 // Response model with related data
-#[derive(Serialize, ToSchema)]
+#[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct PossessionResponse {
     pub id: i32,
     pub owner_id: i32,
     pub item_id: i32,
     pub item_type: Option<String>, // Include item data
+    pub origin: Origin,
+    pub quantity: i32,
+    pub attributes: Vec<PossessionAttributeResponse>,
 }
 
-// Request model for creating a possession
+// A per-instance attribute, e.g. wear, paint seed or a kill counter
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct PossessionAttributeResponse {
+    pub name: String,
+    pub kind: AttributeKind,
+    #[schema(value_type = Object)]
+    pub value: Value,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct PossessionAttributeRequest {
+    pub name: String,
+    pub kind: AttributeKind,
+    #[schema(value_type = Object)]
+    pub value: Value,
+}
+
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct AttributeIncrement {
+    pub name: String,
+    pub by: i64,
+}
+
+// Request model for changing possession attributes, applied as set, increment, remove
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct UpdatePossessionAttributesRequest {
+    #[serde(default)]
+    pub set: Vec<PossessionAttributeRequest>,
+    #[serde(default)]
+    pub increment: Vec<AttributeIncrement>,
+    #[serde(default)]
+    pub remove: Vec<String>,
+}
+
+// Request model for creating a possession.
+// Stackable items without attributes are added to the owner's existing stack when there is one.
 #[derive(Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct CreatePossessionRequest {
     pub owner_id: i32,
     pub item_id: i32,
+    #[serde(default)]
+    pub origin: Origin,
+    #[serde(default = "default_quantity")]
+    pub quantity: i32,
+    #[serde(default)]
+    pub attributes: Vec<PossessionAttributeRequest>,
+}
+
+fn default_quantity() -> i32 {
+    1
 }
 
-// Request model for updating a possession
+// Request model for updating a possession, the quantity is kept when left out
 #[derive(Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct UpdatePossessionRequest {
     pub owner_id: i32,
     pub item_id: i32,
+    #[serde(default)]
+    pub quantity: Option<i32>,
+}
+
+// Request model for splitting part of a stack off into a new possession
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct SplitPossessionRequest {
+    pub owner_id: i32,
+    pub quantity: i32,
+}
+
+// Request model for merging another stack of the same owner and item into a possession
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct MergePossessionsRequest {
+    pub owner_id: i32,
+    pub possession_id: i32,
+}
+
+// One owner getting or losing `quantity` possessions of an item
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BulkEntry {
+    pub owner_id: i32,
+    pub item_id: i32,
+    pub quantity: u32,
+}
+
+// Most possessions a single batch may grant or revoke
+pub const MAX_BATCH_POSSESSIONS: u32 = 1000;
+
+// Request model for granting possessions to many owners at once.
+// Retrying with the same Idempotency-Key header returns the first response instead of granting again.
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BulkGrantRequest {
+    #[serde(default)]
+    pub origin: Origin,
+    pub entries: Vec<BulkEntry>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BulkGrantResponse {
+    pub possessions: Vec<PossessionResponse>,
+}
+
+// Request model for revoking possessions from many owners at once, newest possessions go first.
+// Retrying with the same Idempotency-Key header returns the first response instead of revoking again.
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BulkRevokeRequest {
+    pub entries: Vec<BulkEntry>,
 }
 
-#[derive(Serialize, ToSchema)]
+// Stacks that only lost part of their quantity are kept and listed as reduced
+#[derive(Serialize, Deserialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
-pub struct ApiResponse {
-    pub message: String,
+pub struct BulkRevokeResponse {
+    pub revoked: Vec<i32>,
+    #[serde(default)]
+    pub reduced: Vec<i32>,
+}
+
+// Builds responses with the item type and the attributes of every possession
+pub async fn to_responses<C: ConnectionTrait>(
+    db: &C,
+    possessions: Vec<possession::Model>,
+) -> Result<Vec<PossessionResponse>, DbErr> {
+    let items = possessions.load_one(Item, db).await?;
+    let attributes = possessions.load_many(PossessionAttribute, db).await?;
+
+    Ok(possessions
+        .into_iter()
+        .zip(items)
+        .zip(attributes)
+        .map(|((p, item), attributes)| PossessionResponse {
+            id: p.id,
+            owner_id: p.owner,
+            item_id: p.item,
+            item_type: item.map(|i| i.item_type),
+            origin: p.origin,
+            quantity: p.quantity,
+            attributes: attributes
+                .into_iter()
+                .map(|a| PossessionAttributeResponse {
+                    name: a.name,
+                    kind: a.kind,
+                    value: a.value,
+                })
+                .collect(),
+        })
+        .collect())
+}
+
+async fn to_response<C: ConnectionTrait>(
+    db: &C,
+    possession: possession::Model,
+) -> Result<PossessionResponse, DbErr> {
+    let mut responses = to_responses(db, vec![possession]).await?;
+    Ok(responses.remove(0))
+}
+
+fn not_found(id: i32) -> VentilError {
+    VentilError::NotFound(format!("Possession with id {} not found", id))
+}
+
+// A possession the caller may act for, `owner_id` has to be its owner
+async fn find_owned<C: ConnectionTrait>(
+    db: &C,
+    caller: &Caller,
+    id: i32,
+    owner_id: i32,
+) -> Result<possession::Model, VentilError> {
+    if !caller.can_act_for(owner_id) {
+        return Err(VentilError::Forbidden(format!("Not allowed to act for owner {}", owner_id)));
+    }
+
+    let possession = Possession::find_by_id(id).one(db).await?.ok_or_else(|| not_found(id))?;
+    if possession.owner != owner_id {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} is not owned by owner {}",
+            id, owner_id
+        )));
+    }
+    Ok(possession)
+}
+
+// Rejects changes to a possession while it is offered in a trade or held in escrow
+async fn ensure_unlocked<C: ConnectionTrait>(db: &C, id: i32) -> Result<(), VentilError> {
+    if let Some(trade_id) = Trade::find_locking(db, id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Possession with id {} is locked by trade {}",
+            id, trade_id
+        )));
+    }
+    if let Some(entry) = Escrow::find_holding(db, id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Possession with id {} is held in escrow until {}",
+            id,
+            entry.release_at.to_rfc3339()
+        )));
+    }
+    Ok(())
+}
+
+// Looks up the owner and item a possession refers to, `missing` builds the error.
+// Deleted owners and items can not be given new possessions.
+async fn ensure_references(
+    db: &DatabaseConnection,
+    owner_id: i32,
+    item_id: i32,
+    missing: fn(String) -> VentilError,
+) -> Result<item::Model, VentilError> {
+    let owner = Owner::find_by_id(owner_id)
+        .filter(owner::Column::DeletedAt.is_null())
+        .one(db)
+        .await?;
+    if owner.is_none() {
+        return Err(missing(format!("Owner with id {} not found", owner_id)));
+    }
+    Item::find_by_id(item_id)
+        .filter(item::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or_else(|| missing(format!("Item with id {} not found", item_id)))
+}
+
+// Only stacks of stackable items hold more than one
+fn validate_quantity(item: &item::Model, quantity: i32) -> Result<(), VentilError> {
+    if quantity < 1 {
+        return Err(VentilError::BadRequest("Quantity must be positive".to_string()));
+    }
+    if quantity > 1 && !item.stackable {
+        return Err(VentilError::BadRequest(format!(
+            "Item {} is not stackable, its possessions hold one each",
+            item.id
+        )));
+    }
+    Ok(())
+}
+
+// Every owner and item pair at most once, with a positive quantity and within the batch limit
+fn validate_entries(entries: &[BulkEntry]) -> Result<(), VentilError> {
+    if entries.is_empty() {
+        return Err(VentilError::BadRequest("A batch needs at least one entry".to_string()));
+    }
+
+    let mut pairs = HashSet::new();
+    let mut total: u32 = 0;
+    for entry in entries {
+        if !pairs.insert((entry.owner_id, entry.item_id)) {
+            return Err(VentilError::BadRequest(format!(
+                "Owner {} and item {} are listed more than once",
+                entry.owner_id, entry.item_id
+            )));
+        }
+        if entry.quantity == 0 {
+            return Err(VentilError::BadRequest(format!(
+                "Entry for owner {} and item {} must have a positive quantity",
+                entry.owner_id, entry.item_id
+            )));
+        }
+        total = total.saturating_add(entry.quantity);
+    }
+
+    if total > MAX_BATCH_POSSESSIONS {
+        return Err(VentilError::BadRequest(format!(
+            "A batch covers at most {} possessions, not {}",
+            MAX_BATCH_POSSESSIONS, total
+        )));
+    }
+    Ok(())
+}
+
+// Like `ensure_references`, for every entry of a batch with one query per table
+async fn ensure_batch_references<C: ConnectionTrait>(
+    db: &C,
+    entries: &[BulkEntry],
+) -> Result<HashMap<i32, item::Model>, VentilError> {
+    let owner_ids: HashSet<i32> = entries.iter().map(|e| e.owner_id).collect();
+    let item_ids: HashSet<i32> = entries.iter().map(|e| e.item_id).collect();
+
+    let owners: HashSet<i32> = Owner::find()
+        .filter(owner::Column::Id.is_in(owner_ids.iter().copied()))
+        .filter(owner::Column::DeletedAt.is_null())
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|o| o.id)
+        .collect();
+    if let Some(missing) = entries.iter().find(|e| !owners.contains(&e.owner_id)) {
+        return Err(VentilError::BadRequest(format!("Owner with id {} not found", missing.owner_id)));
+    }
+
+    let items: HashMap<i32, item::Model> = Item::find()
+        .filter(item::Column::Id.is_in(item_ids.iter().copied()))
+        .filter(item::Column::DeletedAt.is_null())
+        .all(db)
+        .await?
+        .into_iter()
+        .map(|i| (i.id, i))
+        .collect();
+    if let Some(missing) = entries.iter().find(|e| !items.contains_key(&e.item_id)) {
+        return Err(VentilError::BadRequest(format!("Item with id {} not found", missing.item_id)));
+    }
+    Ok(items)
+}
+
+// Query of the possession list, filters are combined
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PossessionQuery {
+    /// Maximum number of possessions to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return possessions after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Only possessions of this owner
+    pub owner_id: Option<i32>,
+    /// Only possessions of this item
+    pub item_id: Option<i32>,
+    /// Only possessions of items of this type
+    pub item_type: Option<String>,
+}
+
+// Query of the possession lists of a single owner or item
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PossessionPageQuery {
+    /// Maximum number of possessions to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return possessions after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending
+    #[param(inline)]
+    pub order: Option<SortOrder>,
 }
 
 // GET /possessions - Get all possessions
@@ -71,40 +413,40 @@ pub struct ApiResponse {
     get,
     path = "/possessions",
     tags = ["possessions"],
+    params(PossessionQuery),
     responses(
-        (status = 200, description = "List all possessions successfully", body = [PossessionResponse])
+        (status = 200, description = "List possessions successfully", body = Page<PossessionResponse>),
+        (status = 500, description = "Database error", body = ErrorResponse)
     )
 )]
-#[get("/")]
+#[get("/?<query..>")]
 pub async fn get_all_possessions(
+    query: PossessionQuery,
     database: &State<DatabaseConnection>,
-) -> Json<Vec<PossessionResponse>> {
+) -> Result<Json<Page<PossessionResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
+    let page = PageRequest::new(query.limit, query.after, query.order);
 
-    // Find all possessions with their related items
-    let possessions = Possession::find()
-        .find_with_related(Item)
-        .all(db)
-        .await
-        .unwrap_or_default();
+    let mut select = Possession::find();
+    if let Some(owner_id) = query.owner_id {
+        select = select.filter(possession::Column::Owner.eq(owner_id));
+    }
+    if let Some(item_id) = query.item_id {
+        select = select.filter(possession::Column::Item.eq(item_id));
+    }
+    if let Some(item_type) = query.item_type {
+        select = select
+            .inner_join(Item)
+            .filter(item::Column::ItemType.eq(item_type));
+    }
 
-    // Map to response format
-    let responses = possessions
-        .into_iter()
-        .map(|(p, items)| {
-            // Get the first (and only) related item
-            let item_type = items.first().map(|i| i.item_type.clone());
-
-            PossessionResponse {
-                id: p.id,
-                owner_id: p.owner,
-                item_id: p.item,
-                item_type,
-            }
-        })
-        .collect();
+    let possessions = page.apply(select, possession::Column::Id).all(db).await?;
+    let (possessions, next) = page.finish(possessions, |p| p.id);
+
+    // Related items and attributes are loaded for the page only
+    let items = to_responses(db, possessions).await?;
 
-    Json(responses)
+    Ok(Json(Page { items, next }))
 }
 
 // GET /possessions/<id> - Get possession by ID
@@ -117,40 +459,20 @@ pub async fn get_all_possessions(
     ),
     responses(
         (status = 200, description = "Possession found successfully", body = PossessionResponse),
-        (status = 404, description = "Possession not found", body = ApiResponse)
+        (status = 404, description = "Possession not found", body = ErrorResponse)
     )
 )]
 #[get("/<id>")]
 pub async fn get_possession_by_id(
     id: i32,
     database: &State<DatabaseConnection>,
-) -> Result<Json<PossessionResponse>, NotFound<Json<ApiResponse>>> {
+) -> Result<Json<PossessionResponse>, VentilError> {
     let db = database as &DatabaseConnection;
 
     // Try to find possession with given ID
-    let possession_result = Possession::find_by_id(id)
-        .find_with_related(Item)
-        .all(db)
-        .await;
-
-    match possession_result {
-        Ok(results) if !results.is_empty() => {
-            let (possession, items) = &results[0];
+    let possession = Possession::find_by_id(id).one(db).await?.ok_or_else(|| not_found(id))?;
 
-            // Get item type if item exists
-            let item_type = items.first().map(|i| i.item_type.clone());
-
-            Ok(Json(PossessionResponse {
-                id: possession.id,
-                owner_id: possession.owner,
-                item_id: possession.item,
-                item_type,
-            }))
-        }
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Possession with id {} not found", id),
-        }))),
-    }
+    Ok(Json(to_response(db, possession).await?))
 }
 
 // POST /possessions - Create a new possession
@@ -158,61 +480,92 @@ pub async fn get_possession_by_id(
     post,
     path = "/possessions",
     tags = ["possessions"],
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
     request_body = CreatePossessionRequest,
+    security(("api_key" = [])),
     responses(
-        (status = 201, description = "Possession created successfully", body = PossessionResponse),
-        (status = 400, description = "Invalid request data", body = ApiResponse)
+        (status = 201, description = "Possession created, or added to the owner's stack", body = PossessionResponse),
+        (status = 400, description = "Invalid request data, quantity or attribute value", body = ErrorResponse),
+        (status = 409, description = "The owner's stack can not hold that much more", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
 #[post("/", data = "<possession_data>")]
 pub async fn create_possession(
-    possession_data: Json<CreatePossessionRequest>,
+    _admin: Admin,
+    idempotency: Idempotency,
+    possession_data: IdempotentJson<CreatePossessionRequest>,
     database: &State<DatabaseConnection>,
-) -> Result<Created<Json<PossessionResponse>>, Json<ApiResponse>> {
+) -> Result<Idempotent<Created<Json<PossessionResponse>>>, VentilError> {
     let db = database as &DatabaseConnection;
 
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
     // Validate owner and item exist
-    let owner_exists = Owner::find_by_id(possession_data.owner_id)
-        .one(db)
-        .await
-        .unwrap_or(None)
-        .is_some();
-    let item_exists = Item::find_by_id(possession_data.item_id)
-        .one(db)
-        .await
-        .unwrap_or(None);
+    let item = ensure_references(
+        db,
+        possession_data.owner_id,
+        possession_data.item_id,
+        VentilError::BadRequest,
+    )
+    .await?;
+    validate_quantity(&item, possession_data.quantity)?;
 
-    if !owner_exists {
-        return Err(Json(ApiResponse {
-            message: format!("Owner with id {} not found", possession_data.owner_id),
-        }));
-    }
+    let txn = db.begin().await?;
 
-    if item_exists.is_none() {
-        return Err(Json(ApiResponse {
-            message: format!("Item with id {} not found", possession_data.item_id),
-        }));
+    // Possessions with attributes of their own always start a new stack
+    if possession_data.attributes.is_empty() {
+        let mut granted = stacks::grant(
+            &txn,
+            possession_data.owner_id,
+            &item,
+            possession_data.quantity,
+            possession_data.origin.clone(),
+        )
+        .await?;
+        let response = to_response(&txn, granted.remove(0)).await?;
+        txn.commit().await?;
+
+        return Ok(Created::new(format!("/possessions/{}", response.id)).body(Json(response)).into());
     }
 
     // Create active model
     let new_possession = possession::ActiveModel {
         owner: ActiveValue::set(possession_data.owner_id),
         item: ActiveValue::set(possession_data.item_id),
+        origin: ActiveValue::set(possession_data.origin.clone()),
+        quantity: ActiveValue::set(possession_data.quantity),
         ..Default::default()
     };
 
-    // Insert and get the created possession
-    let insert_result = new_possession.insert(db).await.unwrap();
+    // Insert the possession together with its attributes
+    let created = async {
+        let inserted = new_possession.insert(&txn).await?;
+        for attribute in &possession_data.attributes {
+            attributes::set(
+                &txn,
+                inserted.id,
+                &attribute.name,
+                attribute.kind.clone(),
+                attribute.value.clone(),
+            )
+            .await?;
+        }
+        let response = to_response(&txn, inserted).await?;
+        txn.commit().await?;
+        Ok::<_, AttributeError>(response)
+    };
+
+    let response = created.await?;
 
     // Return with 201 Created status
-    Ok(
-        Created::new(format!("/possessions/{}", insert_result.id)).body(Json(PossessionResponse {
-            id: insert_result.id,
-            owner_id: insert_result.owner,
-            item_id: insert_result.item,
-            item_type: item_exists.map(|i| i.item_type),
-        })),
-    )
+    Ok(Created::new(format!("/possessions/{}", response.id)).body(Json(response)).into())
 }
 
 // PUT /possessions/<id> - Update a possession
@@ -224,68 +577,60 @@ pub async fn create_possession(
         ("id" = i32, Path, description = "Possession identifier")
     ),
     request_body = UpdatePossessionRequest,
+    security(("api_key" = [])),
     responses(
         (status = 200, description = "Possession updated successfully", body = PossessionResponse),
-        (status = 404, description = "Possession not found", body = ApiResponse)
+        (status = 400, description = "Invalid quantity for the item", body = ErrorResponse),
+        (status = 404, description = "Possession, owner or item not found", body = ErrorResponse),
+        (status = 409, description = "Possession is locked by an open trade or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
 #[put("/<id>", data = "<possession_data>")]
 pub async fn update_possession(
+    _admin: Admin,
     id: i32,
     possession_data: Json<UpdatePossessionRequest>,
     database: &State<DatabaseConnection>,
-) -> Result<Json<PossessionResponse>, NotFound<Json<ApiResponse>>> {
+) -> Result<Json<PossessionResponse>, VentilError> {
     let db = database as &DatabaseConnection;
 
     // Validate owner and item exist
-    let owner_exists = Owner::find_by_id(possession_data.owner_id)
-        .one(db)
-        .await
-        .unwrap_or(None)
-        .is_some();
-    let item_exists = Item::find_by_id(possession_data.item_id)
-        .one(db)
-        .await
-        .unwrap_or(None);
+    let item = ensure_references(
+        db,
+        possession_data.owner_id,
+        possession_data.item_id,
+        VentilError::NotFound,
+    )
+    .await?;
 
-    if !owner_exists {
-        return Err(NotFound(Json(ApiResponse {
-            message: format!("Owner with id {} not found", possession_data.owner_id),
-        })));
-    }
+    // The lock check and the write share a transaction, so no trade can take the possession in between
+    let txn = db.begin().await?;
 
-    if item_exists.is_none() {
-        return Err(NotFound(Json(ApiResponse {
-            message: format!("Item with id {} not found", possession_data.item_id),
-        })));
-    }
+    // Possessions offered in a trade can not change hands or item
+    ensure_unlocked(&txn, id).await?;
 
     // Find the possession to update
-    let possession_result = Possession::find_by_id(id).one(db).await;
-
-    match possession_result {
-        Ok(Some(possession)) => {
-            // Create an active model from the found possession
-            let mut possession_active: possession::ActiveModel = possession.into();
-
-            // Update fields
-            possession_active.owner = ActiveValue::set(possession_data.owner_id);
-            possession_active.item = ActiveValue::set(possession_data.item_id);
-
-            // Save changes
-            let updated_possession = possession_active.update(db).await.unwrap();
-
-            Ok(Json(PossessionResponse {
-                id: updated_possession.id,
-                owner_id: updated_possession.owner,
-                item_id: updated_possession.item,
-                item_type: item_exists.map(|i| i.item_type),
-            }))
-        }
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Possession with id {} not found", id),
-        }))),
-    }
+    let possession = Possession::find_by_id(id).one(&txn).await?.ok_or_else(|| not_found(id))?;
+
+    let quantity = possession_data.quantity.unwrap_or(possession.quantity);
+    validate_quantity(&item, quantity)?;
+
+    // Create an active model from the found possession
+    let mut possession_active: possession::ActiveModel = possession.into();
+
+    // Update fields
+    possession_active.owner = ActiveValue::set(possession_data.owner_id);
+    possession_active.item = ActiveValue::set(possession_data.item_id);
+    possession_active.quantity = ActiveValue::set(quantity);
+
+    // Save changes
+    let updated_possession = possession_active.update(&txn).await?;
+    let response = to_response(&txn, updated_possession).await?;
+    txn.commit().await?;
+
+    Ok(Json(response))
 }
 
 // DELETE /possessions/<id> - Delete a possession
@@ -296,31 +641,384 @@ pub async fn update_possession(
     params(
         ("id" = i32, Path, description = "Possession identifier")
     ),
+    security(("api_key" = [])),
     responses(
         (status = 204, description = "Possession deleted successfully"),
-        (status = 404, description = "Possession not found", body = ApiResponse)
+        (status = 404, description = "Possession not found", body = ErrorResponse),
+        (status = 409, description = "Possession is locked by an open trade or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
 #[delete("/<id>")]
 pub async fn delete_possession(
+    _admin: Admin,
     id: i32,
     database: &State<DatabaseConnection>,
-) -> Result<Status, NotFound<Json<ApiResponse>>> {
+) -> Result<Status, VentilError> {
     let db = database as &DatabaseConnection;
 
+    let txn = db.begin().await?;
+
+    // Possessions offered in a trade can not be deleted
+    ensure_unlocked(&txn, id).await?;
+
     // Find the possession to delete
-    let possession_result = Possession::find_by_id(id).one(db).await;
+    let possession = Possession::find_by_id(id).one(&txn).await?.ok_or_else(|| not_found(id))?;
+
+    possession.delete(&txn).await?;
+    txn.commit().await?;
+    Ok(Status::NoContent)
+}
+
+// POST /possessions/bulk - Grant possessions to many owners at once
+#[utoipa::path(
+    post,
+    path = "/possessions/bulk",
+    tags = ["possessions"],
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = BulkGrantRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Possessions granted, or the response of the earlier batch with this key", body = BulkGrantResponse),
+        (status = 400, description = "Invalid entries, owner or item not found, or a key in the body without the header", body = ErrorResponse),
+        (status = 409, description = "A batch with this key is running", body = ErrorResponse),
+        (status = 422, description = "The key was used for another route or body", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/bulk", data = "<grant_data>")]
+pub async fn grant_possessions(
+    _admin: Admin,
+    idempotency: Idempotency,
+    grant_data: IdempotentJson<BulkGrantRequest>,
+    database: &State<DatabaseConnection>,
+) -> Result<Idempotent<Json<BulkGrantResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the possessions granted the first time
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    validate_entries(&grant_data.entries)?;
+
+    // Every entry is granted or none of them
+    let txn = db.begin().await?;
+
+    let items = ensure_batch_references(&txn, &grant_data.entries).await?;
+
+    // Stackable items go onto the owner's stack, the others get a possession each
+    let mut possessions = Vec::new();
+    for entry in &grant_data.entries {
+        let granted = stacks::grant(
+            &txn,
+            entry.owner_id,
+            &items[&entry.item_id],
+            entry.quantity as i32,
+            grant_data.origin.clone(),
+        )
+        .await?;
+        possessions.extend(granted);
+    }
+
+    let response = BulkGrantResponse {
+        possessions: to_responses(&txn, possessions).await?,
+    };
+
+    txn.commit().await?;
+
+    Ok(Json(response).into())
+}
+
+// POST /possessions/bulk/revoke - Revoke possessions from many owners at once
+#[utoipa::path(
+    post,
+    path = "/possessions/bulk/revoke",
+    tags = ["possessions"],
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = BulkRevokeRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Possessions revoked, or the response of the earlier batch with this key", body = BulkRevokeResponse),
+        (status = 400, description = "Invalid entries, or a key in the body without the header", body = ErrorResponse),
+        (status = 409, description = "An owner holds too few possessions that are not locked or in escrow, or a batch with this key is running", body = ErrorResponse),
+        (status = 422, description = "The key was used for another route or body", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/bulk/revoke", data = "<revoke_data>")]
+pub async fn revoke_possessions(
+    _admin: Admin,
+    idempotency: Idempotency,
+    revoke_data: IdempotentJson<BulkRevokeRequest>,
+    database: &State<DatabaseConnection>,
+) -> Result<Idempotent<Json<BulkRevokeResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the possessions revoked the first time
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+
+    validate_entries(&revoke_data.entries)?;
+
+    // Every entry is revoked or none of them
+    let txn = db.begin().await?;
 
-    match possession_result {
-        Ok(Some(possession)) => {
-            // Delete the possession
-            possession.delete(db).await.unwrap();
-            Ok(Status::NoContent)
+    let mut revoked = Vec::new();
+    let mut reduced = Vec::new();
+    for entry in &revoke_data.entries {
+        let held = Possession::find()
+            .filter(possession::Column::Owner.eq(entry.owner_id))
+            .filter(possession::Column::Item.eq(entry.item_id))
+            .order_by_desc(possession::Column::Id)
+            .all(&txn)
+            .await?;
+
+        // Possessions offered in a trade or held in escrow are left alone,
+        // stacks lose what is still missing and are only deleted once empty
+        let mut taken = 0;
+        for possession in held {
+            if taken == entry.quantity {
+                break;
+            }
+            if stacks::is_locked(&txn, possession.id).await? {
+                continue;
+            }
+            let amount = (entry.quantity - taken).min(possession.quantity as u32);
+            let id = possession.id;
+            match stacks::take(&txn, possession, amount as i32).await? {
+                Some(_) => reduced.push(id),
+                None => revoked.push(id),
+            }
+            taken += amount;
+        }
+
+        if taken < entry.quantity {
+            return Err(VentilError::Conflict(format!(
+                "Owner {} holds {} revocable possessions of item {}, not {}",
+                entry.owner_id, taken, entry.item_id, entry.quantity
+            )));
         }
-        _ => Err(NotFound(Json(ApiResponse {
-            message: format!("Possession with id {} not found", id),
-        }))),
     }
+
+    let response = BulkRevokeResponse { revoked, reduced };
+
+    txn.commit().await?;
+
+    Ok(Json(response).into())
+}
+
+// PATCH /possessions/<id>/attributes - Change the attributes of a possession
+#[utoipa::path(
+    patch,
+    path = "/possessions/{id}/attributes",
+    tags = ["possessions"],
+    params(
+        ("id" = i32, Path, description = "Possession identifier")
+    ),
+    request_body = UpdatePossessionAttributesRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Attributes updated successfully", body = PossessionResponse),
+        (status = 400, description = "Invalid attribute value or counter", body = ErrorResponse),
+        (status = 404, description = "Possession not found", body = ErrorResponse),
+        (status = 409, description = "Possession is locked by an open trade or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[patch("/<id>/attributes", data = "<attribute_data>")]
+pub async fn update_possession_attributes(
+    _admin: Admin,
+    id: i32,
+    attribute_data: Json<UpdatePossessionAttributesRequest>,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<PossessionResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // All changes are applied or none of them
+    let txn = db.begin().await?;
+
+    let possession = Possession::find_by_id(id).one(&txn).await?.ok_or_else(|| not_found(id))?;
+
+    // What is offered in a trade or held in escrow has to stay as it was agreed on
+    ensure_unlocked(&txn, possession.id).await?;
+
+    for attribute in &attribute_data.set {
+        attributes::set(
+            &txn,
+            possession.id,
+            &attribute.name,
+            attribute.kind.clone(),
+            attribute.value.clone(),
+        )
+        .await?;
+    }
+    for counter in &attribute_data.increment {
+        attributes::increment(&txn, possession.id, &counter.name, counter.by).await?;
+    }
+    for name in &attribute_data.remove {
+        attributes::remove(&txn, possession.id, name).await?;
+    }
+
+    let response = to_response(&txn, possession).await?;
+    txn.commit().await?;
+
+    Ok(Json(response))
+}
+
+// POST /possessions/<id>/split - Split part of a stack off into a new possession
+#[utoipa::path(
+    post,
+    path = "/possessions/{id}/split",
+    tags = ["possessions"],
+    params(
+        ("id" = i32, Path, description = "Possession identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = SplitPossessionRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 201, description = "New possession holding the split off quantity", body = PossessionResponse),
+        (status = 400, description = "Item is not stackable, or the quantity does not leave both stacks with some", body = ErrorResponse),
+        (status = 404, description = "Possession not found", body = ErrorResponse),
+        (status = 409, description = "Possession is locked by an open trade or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner", body = ErrorResponse)
+    )
+)]
+#[post("/<id>/split", data = "<split_data>")]
+pub async fn split_possession(
+    caller: Caller,
+    idempotency: Idempotency,
+    id: i32,
+    split_data: IdempotentJson<SplitPossessionRequest>,
+    database: &State<DatabaseConnection>,
+) -> Result<Idempotent<Created<Json<PossessionResponse>>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    // Checked inside the transaction, so no trade can lock the stack before it is split
+    let txn = db.begin().await?;
+
+    let stack = find_owned(&txn, &caller, id, split_data.owner_id).await?;
+    let item = Item::find_by_id(stack.item).one(&txn).await?.ok_or_else(|| not_found(id))?;
+
+    if !item.stackable {
+        return Err(VentilError::BadRequest(format!("Item {} is not stackable", item.id)));
+    }
+    if split_data.quantity < 1 || split_data.quantity >= stack.quantity {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} holds {}, a split has to take between 1 and {}",
+            id,
+            stack.quantity,
+            stack.quantity - 1
+        )));
+    }
+
+    ensure_unlocked(&txn, id).await?;
+
+    let split = stacks::split_off(&txn, stack, split_data.quantity).await?;
+    let response = to_response(&txn, split).await?;
+    txn.commit().await?;
+
+    Ok(Created::new(format!("/possessions/{}", response.id)).body(Json(response)).into())
+}
+
+// POST /possessions/<id>/merge - Merge another stack into this one
+#[utoipa::path(
+    post,
+    path = "/possessions/{id}/merge",
+    tags = ["possessions"],
+    params(
+        ("id" = i32, Path, description = "Possession identifier of the stack that is kept"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = MergePossessionsRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Stacks merged, the other possession is deleted", body = PossessionResponse),
+        (status = 400, description = "Possessions are not stacks of the same stackable item with the same attributes", body = ErrorResponse),
+        (status = 404, description = "Possession not found", body = ErrorResponse),
+        (status = 409, description = "A possession is locked by an open trade or held in escrow, or the stack can not hold that much", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner", body = ErrorResponse)
+    )
+)]
+#[post("/<id>/merge", data = "<merge_data>")]
+pub async fn merge_possessions(
+    caller: Caller,
+    idempotency: Idempotency,
+    id: i32,
+    merge_data: IdempotentJson<MergePossessionsRequest>,
+    database: &State<DatabaseConnection>,
+) -> Result<Idempotent<Json<PossessionResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the response of the first request, the other stack is gone by then
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if merge_data.possession_id == id {
+        return Err(VentilError::BadRequest(format!("Possession {} can not be merged into itself", id)));
+    }
+
+    // Checked inside the transaction, so no trade can lock either stack before the merge
+    let txn = db.begin().await?;
+
+    let into = find_owned(&txn, &caller, id, merge_data.owner_id).await?;
+    let from = find_owned(&txn, &caller, merge_data.possession_id, merge_data.owner_id).await?;
+
+    if into.item != from.item {
+        return Err(VentilError::BadRequest(format!(
+            "Possessions {} and {} are not of the same item",
+            into.id, from.id
+        )));
+    }
+    let item = Item::find_by_id(into.item).one(&txn).await?.ok_or_else(|| not_found(id))?;
+    if !item.stackable {
+        return Err(VentilError::BadRequest(format!("Item {} is not stackable", item.id)));
+    }
+
+    // The merged stack keeps the attributes, so they have to agree
+    let mut into_attributes = into.find_related(PossessionAttribute).all(&txn).await?;
+    let mut from_attributes = from.find_related(PossessionAttribute).all(&txn).await?;
+    into_attributes.sort_by(|a, b| a.name.cmp(&b.name));
+    from_attributes.sort_by(|a, b| a.name.cmp(&b.name));
+    let same_attributes = into_attributes.len() == from_attributes.len()
+        && into_attributes
+            .iter()
+            .zip(&from_attributes)
+            .all(|(a, b)| a.name == b.name && a.kind == b.kind && a.value == b.value);
+    if !same_attributes {
+        return Err(VentilError::BadRequest(format!(
+            "Possessions {} and {} have different attributes",
+            into.id, from.id
+        )));
+    }
+
+    ensure_unlocked(&txn, into.id).await?;
+    ensure_unlocked(&txn, from.id).await?;
+
+    let merged = stacks::merge(&txn, into, from).await?;
+    let response = to_response(&txn, merged).await?;
+    txn.commit().await?;
+
+    Ok(Json(response).into())
 }
 
 // Additional helper endpoints for relationships
@@ -331,57 +1029,40 @@ pub async fn delete_possession(
     path = "/possessions/owner/{owner_id}",
     tags = ["possessions"],
     params(
-        ("owner_id" = i32, Path, description = "Owner identifier")
+        ("owner_id" = i32, Path, description = "Owner identifier"),
+        PossessionPageQuery
     ),
     responses(
-        (status = 200, description = "Possessions found successfully", body = [PossessionResponse]),
-        (status = 404, description = "Owner not found", body = ApiResponse)
+        (status = 200, description = "Possessions found successfully", body = Page<PossessionResponse>),
+        (status = 404, description = "Owner not found", body = ErrorResponse)
     )
 )]
-#[get("/owner/<owner_id>")]
+#[get("/owner/<owner_id>?<query..>")]
 pub async fn get_possessions_by_owner(
     owner_id: i32,
+    query: PossessionPageQuery,
     database: &State<DatabaseConnection>,
-) -> Result<Json<Vec<PossessionResponse>>, NotFound<Json<ApiResponse>>> {
+) -> Result<Json<Page<PossessionResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
+    let page = PageRequest::new(query.limit, query.after, query.order);
 
-    // Check if owner exists
-    let owner_exists = Owner::find_by_id(owner_id)
+    // Deleted owners are not found, like everywhere else
+    let owner = Owner::find_by_id(owner_id)
+        .filter(owner::Column::DeletedAt.is_null())
         .one(db)
-        .await
-        .unwrap_or(None)
-        .is_some();
-
-    if !owner_exists {
-        return Err(NotFound(Json(ApiResponse {
-            message: format!("Owner with id {} not found", owner_id),
-        })));
+        .await?;
+    if owner.is_none() {
+        return Err(VentilError::NotFound(format!("Owner with id {} not found", owner_id)));
     }
 
     // Find possessions by owner
-    let possessions = Possession::find()
-        .filter(possession::Column::Owner.eq(owner_id))
-        .find_with_related(Item)
-        .all(db)
-        .await
-        .unwrap_or_default();
+    let select = Possession::find().filter(possession::Column::Owner.eq(owner_id));
+    let possessions = page.apply(select, possession::Column::Id).all(db).await?;
+    let (possessions, next) = page.finish(possessions, |p| p.id);
 
-    // Map to response format
-    let responses = possessions
-        .into_iter()
-        .map(|(p, items)| {
-            let item_type = items.first().map(|i| i.item_type.clone());
-
-            PossessionResponse {
-                id: p.id,
-                owner_id: p.owner,
-                item_id: p.item,
-                item_type,
-            }
-        })
-        .collect();
+    let items = to_responses(db, possessions).await?;
 
-    Ok(Json(responses))
+    Ok(Json(Page { items, next }))
 }
 
 // GET /possessions/item/<item_id> - Get all possessions for an item
@@ -390,48 +1071,40 @@ pub async fn get_possessions_by_owner(
     path = "/possessions/item/{item_id}",
     tags = ["possessions"],
     params(
-        ("item_id" = i32, Path, description = "Item identifier")
+        ("item_id" = i32, Path, description = "Item identifier"),
+        PossessionPageQuery
     ),
     responses(
-        (status = 200, description = "Possessions found successfully", body = [PossessionResponse]),
-        (status = 404, description = "Item not found", body = ApiResponse)
+        (status = 200, description = "Possessions found successfully", body = Page<PossessionResponse>),
+        (status = 404, description = "Item not found", body = ErrorResponse)
     )
 )]
-#[get("/item/<item_id>")]
+#[get("/item/<item_id>?<query..>")]
 pub async fn get_possessions_by_item(
     item_id: i32,
+    query: PossessionPageQuery,
     database: &State<DatabaseConnection>,
-) -> Result<Json<Vec<PossessionResponse>>, NotFound<Json<ApiResponse>>> {
+) -> Result<Json<Page<PossessionResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
+    let page = PageRequest::new(query.limit, query.after, query.order);
 
-    // Check if item exists
-    let item_exists = Item::find_by_id(item_id).one(db).await.unwrap_or(None);
-
-    if item_exists.is_none() {
-        return Err(NotFound(Json(ApiResponse {
-            message: format!("Item with id {} not found", item_id),
-        })));
+    // Deleted items are not found, like everywhere else
+    let item = Item::find_by_id(item_id)
+        .filter(item::Column::DeletedAt.is_null())
+        .one(db)
+        .await?;
+    if item.is_none() {
+        return Err(VentilError::NotFound(format!("Item with id {} not found", item_id)));
     }
 
     // Find possessions by item
-    let possessions = Possession::find()
-        .filter(possession::Column::Item.eq(item_id))
-        .all(db)
-        .await
-        .unwrap_or_default();
+    let select = Possession::find().filter(possession::Column::Item.eq(item_id));
+    let possessions = page.apply(select, possession::Column::Id).all(db).await?;
+    let (possessions, next) = page.finish(possessions, |p| p.id);
 
-    // Map to response format
-    let responses = possessions
-        .into_iter()
-        .map(|p| PossessionResponse {
-            id: p.id,
-            owner_id: p.owner,
-            item_id: p.item,
-            item_type: item_exists.as_ref().map(|i| i.item_type.clone()),
-        })
-        .collect();
+    let items = to_responses(db, possessions).await?;
 
-    Ok(Json(responses))
+    Ok(Json(Page { items, next }))
 }
 
 // Create the OpenAPI documentation struct
@@ -443,11 +1116,32 @@ pub async fn get_possessions_by_item(
         create_possession,
         update_possession,
         delete_possession,
+        grant_possessions,
+        revoke_possessions,
+        update_possession_attributes,
+        split_possession,
+        merge_possessions,
         get_possessions_by_owner,
         get_possessions_by_item
     ),
     components(
-        schemas(PossessionResponse, CreatePossessionRequest, UpdatePossessionRequest, ApiResponse)
+        schemas(
+            PossessionResponse,
+            PossessionAttributeResponse,
+            CreatePossessionRequest,
+            UpdatePossessionRequest,
+            PossessionAttributeRequest,
+            AttributeIncrement,
+            UpdatePossessionAttributesRequest,
+            BulkEntry,
+            BulkGrantRequest,
+            BulkGrantResponse,
+            BulkRevokeRequest,
+            BulkRevokeResponse,
+            SplitPossessionRequest,
+            MergePossessionsRequest,
+            Origin
+        )
     ),
     tags(
         (name = "possessions", description = "Possession management API")
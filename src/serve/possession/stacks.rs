@@ -0,0 +1,161 @@
+use crate::db::entities::possession::Origin;
+use crate::db::entities::prelude::{Possession, PossessionAttribute};
+use crate::db::entities::{item, possession, possession_attribute};
+use crate::serve::error::VentilError;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::logic::Trade;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    PaginatorTrait, QueryFilter, QueryOrder,
+};
+
+// Possessions offered in an open trade or held in escrow keep their quantity
+pub async fn is_locked<C: ConnectionTrait>(db: &C, id: i32) -> Result<bool, DbErr> {
+    Ok(Trade::find_locking(db, id).await?.is_some() || Escrow::find_holding(db, id).await?.is_some())
+}
+
+// Whether any possession of the item holds more than one, it then has to stay stackable
+pub async fn is_stacked<C: ConnectionTrait>(db: &C, item_id: i32) -> Result<bool, DbErr> {
+    let stacks = Possession::find()
+        .filter(possession::Column::Item.eq(item_id))
+        .filter(possession::Column::Quantity.gt(1))
+        .count(db)
+        .await?;
+    Ok(stacks > 0)
+}
+
+// The stack a grant adds to: the owner's oldest unlocked one without attributes of its own
+async fn find_open_stack<C: ConnectionTrait>(
+    db: &C,
+    owner_id: i32,
+    item_id: i32,
+) -> Result<Option<possession::Model>, DbErr> {
+    let stacks = Possession::find()
+        .filter(possession::Column::Owner.eq(owner_id))
+        .filter(possession::Column::Item.eq(item_id))
+        .order_by_asc(possession::Column::Id)
+        .all(db)
+        .await?;
+
+    for stack in stacks {
+        if is_locked(db, stack.id).await? {
+            continue;
+        }
+        let attributes = PossessionAttribute::find()
+            .filter(possession_attribute::Column::Possession.eq(stack.id))
+            .count(db)
+            .await?;
+        if attributes == 0 {
+            return Ok(Some(stack));
+        }
+    }
+    Ok(None)
+}
+
+fn add(stack: &possession::Model, quantity: i32) -> Result<i32, VentilError> {
+    stack.quantity.checked_add(quantity).ok_or_else(|| {
+        VentilError::Conflict(format!("Possession {} can not hold {} more", stack.id, quantity))
+    })
+}
+
+// Gives an owner `quantity` of an item: one possession each, or a single stack for stackable items.
+// Returns the possessions that were created or grew.
+pub async fn grant<C: ConnectionTrait>(
+    db: &C,
+    owner_id: i32,
+    item: &item::Model,
+    quantity: i32,
+    origin: Origin,
+) -> Result<Vec<possession::Model>, VentilError> {
+    if item.stackable
+        && let Some(stack) = find_open_stack(db, owner_id, item.id).await?
+    {
+        let total = add(&stack, quantity)?;
+        let mut stack: possession::ActiveModel = stack.into();
+        stack.quantity = ActiveValue::set(total);
+        return Ok(vec![stack.update(db).await?]);
+    }
+
+    let (count, size) = if item.stackable { (1, quantity) } else { (quantity, 1) };
+    let mut possessions = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let new_possession = possession::ActiveModel {
+            owner: ActiveValue::set(owner_id),
+            item: ActiveValue::set(item.id),
+            origin: ActiveValue::set(origin.clone()),
+            quantity: ActiveValue::set(size),
+            ..Default::default()
+        };
+        possessions.push(new_possession.insert(db).await?);
+    }
+    Ok(possessions)
+}
+
+// Takes `quantity` off a stack, the possession is deleted once nothing is left.
+// Returns what is left of it.
+pub async fn take<C: ConnectionTrait>(
+    db: &C,
+    stack: possession::Model,
+    quantity: i32,
+) -> Result<Option<possession::Model>, DbErr> {
+    if quantity >= stack.quantity {
+        Possession::delete_by_id(stack.id).exec(db).await?;
+        return Ok(None);
+    }
+
+    let left = stack.quantity - quantity;
+    let mut stack: possession::ActiveModel = stack.into();
+    stack.quantity = ActiveValue::set(left);
+    Ok(Some(stack.update(db).await?))
+}
+
+// Moves `quantity` of a stack into a new possession of the same owner, attributes included.
+// The caller makes sure the stack holds more than `quantity`.
+pub async fn split_off<C: ConnectionTrait>(
+    db: &C,
+    stack: possession::Model,
+    quantity: i32,
+) -> Result<possession::Model, DbErr> {
+    let split = possession::ActiveModel {
+        owner: ActiveValue::set(stack.owner),
+        item: ActiveValue::set(stack.item),
+        origin: ActiveValue::set(stack.origin.clone()),
+        quantity: ActiveValue::set(quantity),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    let attributes = PossessionAttribute::find()
+        .filter(possession_attribute::Column::Possession.eq(stack.id))
+        .all(db)
+        .await?;
+    for attribute in attributes {
+        possession_attribute::ActiveModel {
+            possession: ActiveValue::set(split.id),
+            name: ActiveValue::set(attribute.name),
+            kind: ActiveValue::set(attribute.kind),
+            value: ActiveValue::set(attribute.value),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+
+    take(db, stack, quantity).await?;
+    Ok(split)
+}
+
+// Moves everything in `from` onto `into` and deletes `from`, both must be stacks of the same item
+pub async fn merge<C: ConnectionTrait>(
+    db: &C,
+    into: possession::Model,
+    from: possession::Model,
+) -> Result<possession::Model, VentilError> {
+    let total = add(&into, from.quantity)?;
+    Possession::delete_by_id(from.id).exec(db).await?;
+
+    let mut into: possession::ActiveModel = into.into();
+    into.quantity = ActiveValue::set(total);
+    Ok(into.update(db).await?)
+}
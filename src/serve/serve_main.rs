@@ -1,12 +1,20 @@
+use crate::config::Config;
 use crate::db::database::set_up_db;
 use rocket::*;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
+use super::auth::SecurityAddon;
+use super::error::{self, ErrorCode, ErrorResponse};
+use super::idempotency;
 use super::item::routes::{ItemApiDoc, ItemRoutes};
+use super::lootbox::routes::{LootboxApiDoc, LootboxRoutes};
 use super::owner::routes::{OwnerApiDoc, OwnerRoutes};
 use super::possession::routes::{PossessionApiDoc, PossessionRoutes};
+use super::trade::events::TradeEvents;
+use super::trade::scheduler;
 use super::trade::routes::{TradeApiDoc, TradeRoutes};
-use super::trade::logic::get_trades_mutex;
+use super::wallet::routes::{WalletApiDoc, WalletRoutes};
+use std::time::Duration;
 
 #[get("/")]
 async fn index() -> &'static str {
@@ -20,48 +28,76 @@ async fn index() -> &'static str {
         // You can list specific paths here if needed
     ),
     components(
-        // You can list specific components here if needed
+        schemas(ErrorResponse, ErrorCode)
     ),
     tags(
         (name = "items", description = "Item management API"),
+        (name = "lootboxes", description = "Loot box management API"),
         (name = "owners", description = "Owner management API"),
         (name = "possessions", description = "Possession management API"),
-        (name = "trades", description = "Trade management API")
-    )
+        (name = "trades", description = "Trade management API"),
+        (name = "wallets", description = "Wallet and currency ledger API")
+    ),
+    modifiers(&SecurityAddon)
 )]
 struct ApiDoc;
 
-async fn rocket() -> Rocket<Build> {
-    let database = match set_up_db().await {
+pub(crate) async fn rocket(config: Config) -> Rocket<Build> {
+    let database = match set_up_db(&config).await {
         Ok(db) => db,
         Err(err) => panic!("{}", err),
     };
 
-    rocket::build()
+    let sweep_interval = Duration::from_secs(config.trade_sweep_interval_secs.max(1));
+
+    rocket::custom(Config::figment(&config.source))
         .manage(database)
-        .manage(get_trades_mutex())
+        .manage(config)
+        .manage(TradeEvents::default())
+        .attach(scheduler::fairing(sweep_interval))
+        .attach(idempotency::fairing())
+        .register("/", error::catchers())
         .mount("/", routes![index])
         .mount_items()
+        .mount_lootboxes()
         .mount_owners()
         .mount_possessions()
         .mount_trades()
+        .mount_wallets()
         .mount(
             "/",
             SwaggerUi::new("/docs/<_..>").url(
                 "/docs/api.json",
                 ApiDoc::openapi()
                     .merge_from(ItemApiDoc::openapi())
+                    .merge_from(LootboxApiDoc::openapi())
                     .merge_from(OwnerApiDoc::openapi())
                     .merge_from(PossessionApiDoc::openapi())
-                    .merge_from(TradeApiDoc::openapi()),
+                    .merge_from(TradeApiDoc::openapi())
+                    .merge_from(WalletApiDoc::openapi()),
             ),
         )
 }
 
-pub async fn start_server() {
+pub async fn start_server(config: Config) {
     println!("Starting Ventil server...");
-    match rocket().await.launch().await {
+    match rocket(config).await.launch().await {
         Ok(_) => println!("Server shutdown successfully"),
         Err(e) => eprintln!("Server error: {}", e),
     }
 }
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    // Route collisions only show up once the app is ignited
+    #[tokio::test]
+    async fn ignite_test() {
+        let config = Config {
+            database_url: "sqlite::memory:".to_string(),
+            ..Default::default()
+        };
+        assert!(rocket(config).await.ignite().await.is_ok());
+    }
+}
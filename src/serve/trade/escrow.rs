@@ -0,0 +1,120 @@
+use crate::db::entities::escrow::{self, EscrowState};
+use crate::db::entities::possession;
+use crate::db::entities::prelude::{Escrow as EscrowEntity, Possession};
+use crate::serve::trade::history::TradeRecord;
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter, QueryOrder,
+};
+
+// Possessions of an executed trade that are held back before delivery.
+// While held a possession stays with its previous owner and is locked like an
+// offered one, once the hold is over it is delivered to the new owner.
+pub struct Escrow;
+
+impl Escrow {
+    // Holds every possession that changed hands in `record` until `release_at`
+    pub async fn hold<C: ConnectionTrait>(
+        db: &C,
+        record: &TradeRecord,
+        release_at: DateTime<Utc>,
+    ) -> Result<Vec<escrow::Model>, DbErr> {
+        let now = Utc::now();
+        let mut held = Vec::new();
+        for item in &record.items {
+            let new_escrow = escrow::ActiveModel {
+                trade_history: ActiveValue::set(record.history.id),
+                possession: ActiveValue::set(item.possession),
+                from_owner: ActiveValue::set(item.from_owner),
+                to_owner: ActiveValue::set(item.to_owner),
+                state: ActiveValue::set(EscrowState::Held),
+                held_at: ActiveValue::set(now),
+                release_at: ActiveValue::set(release_at),
+                settled_at: ActiveValue::set(None),
+                ..Default::default()
+            };
+            held.push(new_escrow.insert(db).await?);
+        }
+        Ok(held)
+    }
+
+    // The escrow entry holding the possession, if any
+    pub async fn find_holding<C: ConnectionTrait>(
+        db: &C,
+        possession_id: i32,
+    ) -> Result<Option<escrow::Model>, DbErr> {
+        EscrowEntity::find()
+            .filter(escrow::Column::Possession.eq(possession_id))
+            .filter(escrow::Column::State.eq(EscrowState::Held))
+            .one(db)
+            .await
+    }
+
+    // Possessions held for or from `owner_id`, the ones released first come first
+    pub async fn find_by_owner<C: ConnectionTrait>(
+        db: &C,
+        owner_id: i32,
+    ) -> Result<Vec<escrow::Model>, DbErr> {
+        EscrowEntity::find()
+            .filter(escrow::Column::State.eq(EscrowState::Held))
+            .filter(
+                Condition::any()
+                    .add(escrow::Column::FromOwner.eq(owner_id))
+                    .add(escrow::Column::ToOwner.eq(owner_id)),
+            )
+            .order_by_asc(escrow::Column::ReleaseAt)
+            .order_by_asc(escrow::Column::Id)
+            .all(db)
+            .await
+    }
+
+    // Delivers every held possession whose hold is over, returning the escrow ids
+    pub async fn release_due<C: ConnectionTrait>(
+        db: &C,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<i32>, DbErr> {
+        let due = EscrowEntity::find()
+            .filter(escrow::Column::State.eq(EscrowState::Held))
+            .filter(escrow::Column::ReleaseAt.lte(now))
+            .order_by_asc(escrow::Column::Id)
+            .all(db)
+            .await?;
+
+        let mut released = Vec::new();
+        for entry in due {
+            // The possession was locked the whole time, so it still belongs to the sender
+            if let Some(possession) = Possession::find_by_id(entry.possession).one(db).await? {
+                let mut active_model: possession::ActiveModel = possession.into();
+                active_model.owner = ActiveValue::set(entry.to_owner);
+                active_model.update(db).await?;
+            }
+
+            released.push(entry.id);
+            Self::settle(db, entry, EscrowState::Delivered, now).await?;
+        }
+        Ok(released)
+    }
+
+    // Settles a held entry without delivering it, the possession stays with the sender
+    pub async fn return_held<C: ConnectionTrait>(
+        db: &C,
+        entry: escrow::Model,
+        now: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        Self::settle(db, entry, EscrowState::Returned, now).await
+    }
+
+    async fn settle<C: ConnectionTrait>(
+        db: &C,
+        entry: escrow::Model,
+        state: EscrowState,
+        now: DateTime<Utc>,
+    ) -> Result<(), DbErr> {
+        let mut active_model: escrow::ActiveModel = entry.into();
+        active_model.state = ActiveValue::set(state);
+        active_model.settled_at = ActiveValue::set(Some(now));
+        active_model.update(db).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+use crate::db::entities::trade::TradeState;
+use crate::serve::trade::logic::TradeId;
+use rocket::serde::Serialize;
+use tokio::sync::broadcast;
+use utoipa::ToSchema;
+
+// Events a slow subscriber may fall behind by before it misses some
+const CAPACITY: usize = 256;
+
+// A change to a trade, pushed to everyone following it
+#[derive(Clone, Debug, Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeEvent {
+    pub trade_id: TradeId,
+    #[serde(flatten)]
+    pub kind: TradeEventKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(crate = "rocket::serde", tag = "type", rename_all = "snake_case")]
+pub enum TradeEventKind {
+    ItemAdded { owner_id: i32, possession_id: i32, to_owner_id: i32, quantity: i32 },
+    ItemRemoved { owner_id: i32, possession_id: i32 },
+    // An amount of 0 withdraws the currency offer
+    CurrencyOffered { owner_id: i32, to_owner_id: i32, currency: String, amount: i64 },
+    // Changing the items withdraws everyone's readiness, so it has no event of its own
+    AcceptToggled { owner_id: i32, ready: bool },
+    Confirmed { owner_id: i32 },
+    Executed,
+    Cancelled,
+    Expired,
+}
+
+impl TradeEventKind {
+    // Name of the event in the stream, the same as `type` in its data
+    pub fn name(&self) -> &'static str {
+        match self {
+            TradeEventKind::ItemAdded { .. } => "item_added",
+            TradeEventKind::ItemRemoved { .. } => "item_removed",
+            TradeEventKind::CurrencyOffered { .. } => "currency_offered",
+            TradeEventKind::AcceptToggled { .. } => "accept_toggled",
+            TradeEventKind::Confirmed { .. } => "confirmed",
+            TradeEventKind::Executed => "executed",
+            TradeEventKind::Cancelled => "cancelled",
+            TradeEventKind::Expired => "expired",
+        }
+    }
+
+    // The event of a trade closing in `state`, open states have none
+    pub fn closed(state: TradeState) -> Option<Self> {
+        match state {
+            TradeState::Executed => Some(TradeEventKind::Executed),
+            TradeState::Cancelled => Some(TradeEventKind::Cancelled),
+            TradeState::Expired => Some(TradeEventKind::Expired),
+            TradeState::Proposed | TradeState::Negotiating | TradeState::Accepted => None,
+        }
+    }
+
+    // Nothing follows once the trade is closed
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self,
+            TradeEventKind::Executed | TradeEventKind::Cancelled | TradeEventKind::Expired
+        )
+    }
+}
+
+// Hands trade events to the streams following the trades.
+// Events are only published once the change behind them is committed,
+// subscribers that are not connected at the time miss them.
+#[derive(Clone)]
+pub struct TradeEvents {
+    sender: broadcast::Sender<TradeEvent>,
+}
+
+impl Default for TradeEvents {
+    fn default() -> Self {
+        TradeEvents {
+            sender: broadcast::channel(CAPACITY).0,
+        }
+    }
+}
+
+impl TradeEvents {
+    pub fn publish(&self, events: impl IntoIterator<Item = TradeEvent>) {
+        for event in events {
+            // Sending only fails when nobody is listening
+            let _ = self.sender.send(event);
+        }
+    }
+
+    // Receives the events of every trade, the caller picks out the one it follows
+    pub fn subscribe(&self) -> broadcast::Receiver<TradeEvent> {
+        self.sender.subscribe()
+    }
+}
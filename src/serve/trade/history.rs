@@ -0,0 +1,325 @@
+use crate::db::entities::ledger_transaction::LedgerKind;
+use crate::db::entities::prelude::{Owner, Possession, TradeHistory, TradeHistoryItem};
+use crate::db::entities::{possession, trade_history, trade_history_item};
+use crate::serve::possession::stacks;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::events::TradeEvent;
+use crate::serve::trade::logic::{CurrencyOffer, Offer, Trade, TradeId, TradeLogic};
+use crate::serve::wallet::ledger::{self, Account, LedgerError, Transfer};
+use chrono::{DateTime, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, ConnectionTrait, DbErr, EntityTrait,
+    LoaderTrait, QueryFilter, Select,
+};
+use std::fmt;
+
+// An executed trade as recorded in the append-only history.
+// Rows are never deleted, the only change to a recorded trade is marking it rolled back.
+pub struct TradeRecord {
+    pub history: trade_history::Model,
+    pub items: Vec<trade_history_item::Model>,
+    // Currency that changed hands, settled right away even when the items are held in escrow
+    pub currency: Vec<Transfer>,
+}
+
+// Why a possession of a rolled back trade could not be returned
+pub enum Unrecoverable {
+    Missing,
+    // The recipient passed the possession on
+    OwnedBy(i32),
+    // The possession is held in escrow for a later trade
+    HeldInEscrow(i32),
+    // The possession is offered in an open trade that could not give it up
+    Locked(TradeId),
+    // The recipient no longer holds every traded unit of the stack, the rest was returned
+    Shortfall { held: i32, traded: i32 },
+}
+
+impl fmt::Display for Unrecoverable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Unrecoverable::Missing => write!(f, "The possession no longer exists"),
+            Unrecoverable::OwnedBy(owner) => write!(f, "The possession now belongs to owner {}", owner),
+            Unrecoverable::HeldInEscrow(entry) => {
+                write!(f, "The possession is held in escrow entry {} of a later trade", entry)
+            }
+            Unrecoverable::Locked(trade) => {
+                write!(f, "The possession is locked by trade {}", trade)
+            }
+            Unrecoverable::Shortfall { held, traded } => {
+                write!(f, "Only {} of the {} traded units were left to return", held, traded)
+            }
+        }
+    }
+}
+
+// Outcome of rolling back a recorded trade
+pub struct Rollback {
+    pub restored: Vec<i32>,
+    pub unrecoverable: Vec<(i32, Unrecoverable)>,
+    // Currency paid back, and currency the recipient no longer holds all of
+    pub refunded: Vec<Transfer>,
+    pub unrefunded: Vec<Transfer>,
+    // Open trades that gave up an offer, published once the rollback is committed
+    pub events: Vec<TradeEvent>,
+}
+
+impl TradeRecord {
+    pub fn is_rolled_back(&self) -> bool {
+        self.history.rolled_back_at.is_some()
+    }
+
+    // Reverses the trade as far as possible and marks it rolled back.
+    // Possessions still held in escrow for this trade stay with the sender,
+    // the others move back when the recipient still owns them. Only the traded
+    // quantity of a stack moves back, offers of it in open trades are withdrawn
+    // first unless the rest of the stack still covers them.
+    pub async fn roll_back<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        now: DateTime<Utc>,
+    ) -> Result<Rollback, DbErr> {
+        let mut rollback = Rollback {
+            restored: Vec::new(),
+            unrecoverable: Vec::new(),
+            refunded: Vec::new(),
+            unrefunded: Vec::new(),
+            events: Vec::new(),
+        };
+
+        for item in &self.items {
+            match Self::restore(db, self.history.id, item, now, &mut rollback.events).await? {
+                None => rollback.restored.push(item.possession),
+                Some(reason) => rollback.unrecoverable.push((item.possession, reason)),
+            }
+        }
+
+        for transfer in &self.currency {
+            if Self::refund(db, self.history.id, transfer).await? {
+                rollback.refunded.push(transfer.clone());
+            } else {
+                rollback.unrefunded.push(transfer.clone());
+            }
+        }
+
+        let mut active_model: trade_history::ActiveModel = self.history.clone().into();
+        active_model.rolled_back_at = ActiveValue::set(Some(now));
+        self.history = active_model.update(db).await?;
+
+        Ok(rollback)
+    }
+
+    // Returns one possession to its sender, or the reason it can not be
+    async fn restore<C: ConnectionTrait>(
+        db: &C,
+        history_id: i32,
+        item: &trade_history_item::Model,
+        now: DateTime<Utc>,
+        events: &mut Vec<TradeEvent>,
+    ) -> Result<Option<Unrecoverable>, DbErr> {
+        if let Some(entry) = Escrow::find_holding(db, item.possession).await? {
+            if entry.trade_history != history_id {
+                return Ok(Some(Unrecoverable::HeldInEscrow(entry.id)));
+            }
+            Escrow::return_held(db, entry, now).await?;
+            return Ok(None);
+        }
+
+        let Some(possession) = Possession::find_by_id(item.possession).one(db).await? else {
+            return Ok(Some(Unrecoverable::Missing));
+        };
+        if possession.owner != item.to_owner {
+            return Ok(Some(Unrecoverable::OwnedBy(possession.owner)));
+        }
+
+        // Units granted to the recipient since the trade may have grown the stack
+        let kept = possession.quantity - item.quantity;
+
+        if let Some(trade_id) = Trade::find_locking(db, possession.id).await? {
+            let withdrawn = match (
+                Trade::find_by_id(db, trade_id).await?,
+                Owner::find_by_id(item.to_owner).one(db).await?,
+            ) {
+                (Some(trade), _)
+                    if trade
+                        .offers
+                        .iter()
+                        .any(|offer| offer.possession == possession.id && offer.quantity <= kept) =>
+                {
+                    true
+                }
+                (Some(mut trade), Some(owner)) => {
+                    let removed = matches!(trade.remove_from_trade(db, &owner, &possession).await, Ok(true));
+                    events.extend(trade.take_events());
+                    removed
+                }
+                _ => false,
+            };
+            if !withdrawn {
+                return Ok(Some(Unrecoverable::Locked(trade_id)));
+            }
+        }
+
+        let (returned, shortfall) = if kept > 0 {
+            (stacks::split_off(db, possession, item.quantity).await?, None)
+        } else if kept < 0 {
+            let held = possession.quantity;
+            (possession, Some(Unrecoverable::Shortfall { held, traded: item.quantity }))
+        } else {
+            (possession, None)
+        };
+
+        let mut active_model: possession::ActiveModel = returned.into();
+        active_model.owner = ActiveValue::set(item.from_owner);
+        active_model.update(db).await?;
+        Ok(shortfall)
+    }
+
+    // Pays a trade transfer back when the recipient still holds the amount, spent currency stays spent
+    async fn refund<C: ConnectionTrait>(
+        db: &C,
+        history_id: i32,
+        transfer: &Transfer,
+    ) -> Result<bool, DbErr> {
+        let Account::Owner(recipient) = transfer.to else {
+            return Ok(false);
+        };
+        if ledger::balance(db, recipient, &transfer.currency).await? < transfer.amount {
+            return Ok(false);
+        }
+
+        let reversal = Transfer {
+            from: transfer.to,
+            to: transfer.from,
+            currency: transfer.currency.clone(),
+            amount: transfer.amount,
+        };
+        match ledger::post(db, LedgerKind::Reversal, &reversal, Some(history_id), None).await {
+            Ok(_) => Ok(true),
+            Err(LedgerError::Database(err)) => Err(err),
+            Err(err) => Err(DbErr::Custom(err.to_string())),
+        }
+    }
+
+    // Possessions that moved away from `owner_id`
+    pub fn items_given_by(&self, owner_id: i32) -> Vec<i32> {
+        self.items
+            .iter()
+            .filter(|item| item.from_owner == owner_id)
+            .map(|item| item.possession)
+            .collect()
+    }
+
+    // Appends the trade and the possessions that changed hands to the history.
+    // `delivered` are the offers of the trade, naming the possessions split off partial offers.
+    pub async fn record<C: ConnectionTrait>(
+        db: &C,
+        trade: &Trade,
+        delivered: &[Offer],
+    ) -> Result<TradeRecord, DbErr> {
+        let new_history = trade_history::ActiveModel {
+            trade: ActiveValue::set(trade.id),
+            trader_1: ActiveValue::set(trade.trader_1),
+            trader_2: ActiveValue::set(trade.trader_2),
+            executed_at: ActiveValue::set(Utc::now()),
+            rolled_back_at: ActiveValue::set(None),
+            ..Default::default()
+        };
+        let history = new_history.insert(db).await?;
+
+        let mut items = Vec::new();
+        for offer in delivered {
+            let new_item = trade_history_item::ActiveModel {
+                trade_history: ActiveValue::set(history.id),
+                possession: ActiveValue::set(offer.possession),
+                from_owner: ActiveValue::set(offer.owner),
+                to_owner: ActiveValue::set(offer.recipient),
+                quantity: ActiveValue::set(offer.quantity),
+                ..Default::default()
+            };
+            items.push(new_item.insert(db).await?);
+        }
+
+        Ok(TradeRecord {
+            history,
+            items,
+            currency: Vec::new(),
+        })
+    }
+
+    // Moves the offered currency between the wallets of the traders and books it on the recorded trade
+    pub async fn settle<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        offers: &[CurrencyOffer],
+    ) -> Result<(), LedgerError> {
+        for offer in offers {
+            let transfer = Transfer {
+                from: Account::Owner(offer.owner),
+                to: Account::Owner(offer.recipient),
+                currency: offer.currency.clone(),
+                amount: offer.amount,
+            };
+            ledger::post(db, LedgerKind::Trade, &transfer, Some(self.history.id), None).await?;
+            self.currency.push(transfer);
+        }
+        Ok(())
+    }
+
+    pub async fn find_by_id<C: ConnectionTrait>(
+        db: &C,
+        id: i32,
+    ) -> Result<Option<TradeRecord>, DbErr> {
+        let mut records = Self::find_with(db, TradeHistory::find_by_id(id)).await?;
+        Ok(records.pop())
+    }
+
+    // Selects the recorded trades `owner_id` took part in, unordered
+    pub fn owner_query(owner_id: i32) -> Select<TradeHistory> {
+        // Participants of multi-party trades beyond the first two only show up in the items
+        TradeHistory::find().filter(
+            Condition::any()
+                .add(trade_history::Column::Trader1.eq(owner_id))
+                .add(trade_history::Column::Trader2.eq(owner_id))
+                .add(
+                    trade_history::Column::Id.in_subquery(
+                        sea_orm::sea_query::Query::select()
+                            .column(trade_history_item::Column::TradeHistory)
+                            .from(TradeHistoryItem)
+                            .cond_where(
+                                Condition::any()
+                                    .add(trade_history_item::Column::FromOwner.eq(owner_id))
+                                    .add(trade_history_item::Column::ToOwner.eq(owner_id)),
+                            )
+                            .to_owned(),
+                    ),
+                ),
+        )
+    }
+
+    // Loads the items and currency of the recorded trades `query` selects, in its order
+    pub async fn find_with<C: ConnectionTrait>(
+        db: &C,
+        query: Select<TradeHistory>,
+    ) -> Result<Vec<TradeRecord>, DbErr> {
+        let histories = query.all(db).await?;
+        let items = histories.load_many(TradeHistoryItem, db).await?;
+
+        let mut transfers = ledger::trade_transfers(db, histories.iter().map(|history| history.id)).await?;
+        transfers.retain(|(transaction, _)| transaction.kind == LedgerKind::Trade);
+
+        Ok(histories
+            .into_iter()
+            .zip(items)
+            .map(|(history, mut items)| {
+                items.sort_by_key(|item| item.id);
+                let currency = transfers
+                    .iter()
+                    .filter(|(transaction, _)| transaction.trade_history == Some(history.id))
+                    .map(|(_, transfer)| transfer.clone())
+                    .collect();
+                TradeRecord { history, items, currency }
+            })
+            .collect())
+    }
+}
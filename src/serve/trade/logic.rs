@@ -1,81 +1,764 @@
 use crate::db::entities::owner::Model as OwnerModel;
 use crate::db::entities::possession::Model as PossessionModel;
-use std::sync::Arc;
-use tokio::sync::Mutex;
-use std::collections::HashMap;
+use crate::db::entities::prelude::{
+    Trade as TradeEntity, TradeOfferCurrency, TradeOfferItem, TradeParticipant,
+};
+use crate::db::entities::trade::TradeState;
+use crate::db::entities::{trade, trade_offer_currency, trade_offer_item, trade_participant};
+use crate::serve::trade::events::{TradeEvent, TradeEventKind};
+use chrono::{DateTime, TimeDelta, Utc};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, LoaderTrait,
+    QueryFilter, QueryOrder, Select,
+};
+use sha2::{Digest, Sha256};
+use std::fmt;
 
-pub type TradeId = u64;
-pub type TradesMutex = Arc<Mutex<HashMap<TradeId,Trade>>>;
+pub type TradeId = i32;
 
-pub fn get_trades_mutex() -> TradesMutex {
-    return Arc::new(Mutex::new(HashMap::new()));
+pub trait TradeLogic {
+    async fn add_to_trade<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        item: &PossessionModel,
+        recipient: i32,
+        quantity: i32,
+    ) -> Result<bool, TradeError>;
+    async fn remove_from_trade<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        item: &PossessionModel,
+    ) -> Result<bool, TradeError>;
+    async fn offer_currency<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        recipient: i32,
+        currency: &str,
+        amount: i64,
+    ) -> Result<bool, TradeError>;
+    async fn change_trade_status<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+    ) -> Result<(), TradeError>;
+    async fn confirm_trade<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        items_hash: &str,
+        cooldown: TimeDelta,
+    ) -> Result<(), TradeError>;
 }
 
-pub trait TradeLogic {
-    fn add_to_trade(&mut self, owner: &OwnerModel, item: &PossessionModel) -> bool;
-    fn remove_from_trade(&mut self, owner: &OwnerModel, item: &PossessionModel) -> bool;
-    fn change_trade_status(&mut self, owner: &OwnerModel);
+#[derive(Debug)]
+pub enum TradeError {
+    // The trade was executed, cancelled or expired and can not change anymore
+    Closed { trade: TradeId, state: TradeState },
+    // The trade is past its expiry time but was not swept yet
+    Expired(TradeId),
+    InvalidTransition { trade: TradeId, from: TradeState, to: TradeState },
+    // Confirming needs every participant to be ready
+    NotReady(TradeId),
+    // Everyone is ready, but the cooldown has not passed yet
+    CoolingDown { trade: TradeId, until: DateTime<Utc> },
+    // The confirmed item hash no longer matches the offered items
+    ItemsChanged(TradeId),
+    // Nobody offers anything, so there is nothing to execute
+    NothingOffered(TradeId),
+    Database(DbErr),
+}
+
+impl From<DbErr> for TradeError {
+    fn from(err: DbErr) -> Self {
+        TradeError::Database(err)
+    }
+}
+
+impl fmt::Display for TradeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeError::Closed { trade, state } => {
+                write!(f, "Trade {} is {:?} and can not be changed", trade, state)
+            }
+            TradeError::Expired(trade) => write!(f, "Trade {} has expired", trade),
+            TradeError::InvalidTransition { trade, from, to } => {
+                write!(f, "Trade {} can not move from {:?} to {:?}", trade, from, to)
+            }
+            TradeError::NotReady(trade) => {
+                write!(f, "Every trader has to be ready before trade {} is confirmed", trade)
+            }
+            TradeError::CoolingDown { trade, until } => {
+                write!(f, "Trade {} can be confirmed from {}", trade, until.to_rfc3339())
+            }
+            TradeError::ItemsChanged(trade) => {
+                write!(f, "The items of trade {} changed, check them again before confirming", trade)
+            }
+            TradeError::NothingOffered(trade) => {
+                write!(f, "Nothing is offered in trade {}", trade)
+            }
+            TradeError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+pub fn is_open(state: TradeState) -> bool {
+    matches!(
+        state,
+        TradeState::Proposed | TradeState::Negotiating | TradeState::Accepted
+    )
 }
 
+// An owner taking part in a trade
+pub struct Participant {
+    pub id: i32,
+    pub owner: i32,
+    pub ready: bool,
+    pub confirmed: bool,
+}
+
+// A possession `owner` gives to `recipient` once the trade is executed.
+// Offering less than the whole stack splits `quantity` off at execution.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Offer {
+    pub owner: i32,
+    pub possession: i32,
+    pub recipient: i32,
+    pub quantity: i32,
+}
+
+// An amount of currency `owner` pays `recipient` once the trade is executed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurrencyOffer {
+    pub owner: i32,
+    pub recipient: i32,
+    pub currency: String,
+    pub amount: i64,
+}
+
+// A stored trade together with its participants and the possessions and currency they offer.
+// Two participants make a regular trade, or a gift when only one side gives,
+// more make a multi-party trade where every offer names its recipient.
+// Offers are released when the trade closes, so closed trades have none once
+// reloaded; executed trades are kept in the history.
 pub struct Trade {
-    pub id: u64,
+    pub id: TradeId,
 
-    pub trader_1: OwnerModel,
-    pub trade_1_accept: bool,
-    pub trade_1_items: Vec<i32>,
+    // `trader_1` opened the trade, `trader_2` was invited first
+    pub trader_1: i32,
+    pub trader_2: i32,
+    // Every participant, the one who opened the trade first
+    pub participants: Vec<Participant>,
+    pub offers: Vec<Offer>,
+    pub currency_offers: Vec<CurrencyOffer>,
 
-    pub trader_2: OwnerModel,
-    pub trade_2_accept: bool,
-    pub trade_2_items: Vec<i32>,
+    pub state: TradeState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+
+    // Set once every participant is ready, confirming is possible after the cooldown
+    pub ready_at: Option<DateTime<Utc>>,
+
+    // Changes made since the trade was loaded, published once they are committed
+    events: Vec<TradeEvent>,
 }
 
-impl TradeLogic for Trade {
-    fn add_to_trade(&mut self, owner: &OwnerModel, item: &PossessionModel) -> bool {
-        if self.trader_1.id == owner.id {
-            self.trade_1_items.push(item.id);
-            self.trade_1_accept = false;
-            self.trade_2_accept = false;
-            return true;
-        } else if self.trader_2.id == owner.id {
-            self.trade_2_items.push(item.id);
-            self.trade_1_accept = false;
-            self.trade_2_accept = false;
-            return true;
-        }
-        return false;
-    }
-
-    fn remove_from_trade(&mut self, owner: &OwnerModel, item: &PossessionModel) -> bool {
-        if self.trader_1.id == owner.id {
-            if let Some(i) = self
-                .trade_1_items
-                .iter()
-                .position(|value| *value == item.id)
-            {
-                self.trade_1_items.swap_remove(i);
-                self.trade_1_accept = false;
-                self.trade_2_accept = false;
-                return true;
-            }
-        } else if self.trader_2.id == owner.id {
-            if let Some(i) = self
-                .trade_2_items
+impl Trade {
+    fn from_rows(
+        model: trade::Model,
+        participants: Vec<trade_participant::Model>,
+        offers: Vec<trade_offer_item::Model>,
+        currency_offers: Vec<trade_offer_currency::Model>,
+    ) -> Self {
+        Trade {
+            id: model.id,
+            trader_1: model.trader_1,
+            trader_2: model.trader_2,
+            participants: participants
+                .into_iter()
+                .map(|p| Participant {
+                    id: p.id,
+                    owner: p.owner,
+                    ready: p.ready,
+                    confirmed: p.confirmed,
+                })
+                .collect(),
+            offers: offers
+                .into_iter()
+                .map(|o| Offer {
+                    owner: o.owner,
+                    possession: o.possession,
+                    recipient: o.recipient,
+                    quantity: o.quantity,
+                })
+                .collect(),
+            currency_offers: currency_offers
+                .into_iter()
+                .map(|o| CurrencyOffer {
+                    owner: o.owner,
+                    recipient: o.recipient,
+                    currency: o.currency,
+                    amount: o.amount,
+                })
+                .collect(),
+            state: model.state,
+            created_at: model.created_at,
+            updated_at: model.updated_at,
+            expires_at: model.expires_at,
+            closed_at: model.closed_at,
+            ready_at: model.ready_at,
+            events: Vec::new(),
+        }
+    }
+
+    fn record_event(&mut self, kind: TradeEventKind) {
+        self.events.push(TradeEvent { trade_id: self.id, kind });
+    }
+
+    // Hands over the recorded changes, the caller publishes them after committing
+    pub fn take_events(&mut self) -> Vec<TradeEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    pub fn has_trader(&self, owner_id: i32) -> bool {
+        self.participant(owner_id).is_some()
+    }
+
+    pub fn participant(&self, owner_id: i32) -> Option<&Participant> {
+        self.participants.iter().find(|p| p.owner == owner_id)
+    }
+
+    fn participant_mut(&mut self, owner_id: i32) -> Option<&mut Participant> {
+        self.participants.iter_mut().find(|p| p.owner == owner_id)
+    }
+
+    pub fn receives_anything(&self, owner_id: i32) -> bool {
+        self.offers.iter().any(|offer| offer.recipient == owner_id)
+            || self.currency_offers.iter().any(|offer| offer.recipient == owner_id)
+    }
+
+    pub fn offers_nothing(&self) -> bool {
+        self.offers.is_empty() && self.currency_offers.is_empty()
+    }
+
+    // Sum of what `owner_id` offers in `currency`, to whichever recipient
+    pub fn currency_offered_by(&self, owner_id: i32, currency: &str) -> i64 {
+        self.currency_offers
+            .iter()
+            .filter(|offer| offer.owner == owner_id && offer.currency == currency)
+            .map(|offer| offer.amount)
+            .sum()
+    }
+
+    // Fingerprint of the offered items and currency, a confirmation has to name the one it saw.
+    // The offers are sorted so the hash does not depend on the order they were made in.
+    pub fn items_hash(&self) -> String {
+        let mut offers: Vec<_> = self
+            .offers
+            .iter()
+            .map(|offer| (offer.possession, offer.owner, offer.recipient, offer.quantity))
+            .collect();
+        offers.sort_unstable();
+
+        let mut owners: Vec<_> = self.participants.iter().map(|p| p.owner).collect();
+        owners.sort_unstable();
+
+        let mut currency_offers: Vec<_> = self
+            .currency_offers
+            .iter()
+            .map(|offer| (offer.owner, offer.recipient, offer.currency.as_str(), offer.amount))
+            .collect();
+        currency_offers.sort_unstable();
+
+        let text = format!("{}:{:?}={:?}+{:?}", self.id, owners, offers, currency_offers);
+        hex::encode(Sha256::digest(text.as_bytes()))
+    }
+
+    pub fn all_ready(&self) -> bool {
+        self.participants.iter().all(|p| p.ready)
+    }
+
+    // Only participants who receive something confirm, a gift needs no
+    // confirmation from the giver beyond being ready
+    pub fn all_confirmed(&self) -> bool {
+        !self.offers_nothing()
+            && self
+                .participants
                 .iter()
-                .position(|value| *value == item.id)
-            {
-                self.trade_2_items.swap_remove(i);
-                self.trade_1_accept = false;
-                self.trade_2_accept = false;
-                return true;
+                .all(|p| p.confirmed || !self.receives_anything(p.owner))
+    }
+
+    // Withdraws readiness and confirmations of every participant
+    fn reset_acceptance(&mut self) {
+        for participant in &mut self.participants {
+            participant.ready = false;
+            participant.confirmed = false;
+        }
+        self.ready_at = None;
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    // Moves the trade to `next` in memory, the caller saves it.
+    // Only open trades change state, they never return to Proposed and only
+    // expire once their expiry time has passed.
+    fn transition(&mut self, next: TradeState, now: DateTime<Utc>) -> Result<(), TradeError> {
+        if !is_open(self.state) {
+            return Err(TradeError::Closed { trade: self.id, state: self.state });
+        }
+
+        let expired = self.is_expired(now);
+        if (next == TradeState::Expired && !expired) || next == TradeState::Proposed {
+            return Err(TradeError::InvalidTransition {
+                trade: self.id,
+                from: self.state,
+                to: next,
+            });
+        }
+        if expired && next != TradeState::Expired {
+            return Err(TradeError::Expired(self.id));
+        }
+
+        self.state = next;
+        self.updated_at = now;
+        if !is_open(next) {
+            self.closed_at = Some(now);
+        }
+        Ok(())
+    }
+
+    // Stores a new, empty trade opened by `trader` with the `invited` owners.
+    // The caller makes sure at least one distinct owner is invited.
+    pub async fn create<C: ConnectionTrait>(
+        db: &C,
+        trader: &OwnerModel,
+        invited: &[OwnerModel],
+        expires_at: Option<DateTime<Utc>>,
+    ) -> Result<Trade, DbErr> {
+        let Some(first_invited) = invited.first() else {
+            return Err(DbErr::Custom("A trade needs at least two traders".to_string()));
+        };
+
+        let now = Utc::now();
+        let new_trade = trade::ActiveModel {
+            trader_1: ActiveValue::set(trader.id),
+            trader_2: ActiveValue::set(first_invited.id),
+            state: ActiveValue::set(TradeState::Proposed),
+            created_at: ActiveValue::set(now),
+            updated_at: ActiveValue::set(now),
+            expires_at: ActiveValue::set(expires_at),
+            closed_at: ActiveValue::set(None),
+            ready_at: ActiveValue::set(None),
+            ..Default::default()
+        };
+        let model = new_trade.insert(db).await?;
+
+        let mut participants = Vec::new();
+        for owner in std::iter::once(trader).chain(invited) {
+            let new_participant = trade_participant::ActiveModel {
+                trade: ActiveValue::set(model.id),
+                owner: ActiveValue::set(owner.id),
+                ready: ActiveValue::set(false),
+                confirmed: ActiveValue::set(false),
+                ..Default::default()
+            };
+            participants.push(new_participant.insert(db).await?);
+        }
+
+        Ok(Trade::from_rows(model, participants, Vec::new(), Vec::new()))
+    }
+
+    pub async fn find_by_id<C: ConnectionTrait>(
+        db: &C,
+        id: TradeId,
+    ) -> Result<Option<Trade>, DbErr> {
+        let Some(model) = TradeEntity::find_by_id(id).one(db).await? else {
+            return Ok(None);
+        };
+
+        let participants = TradeParticipant::find()
+            .filter(trade_participant::Column::Trade.eq(model.id))
+            .order_by_asc(trade_participant::Column::Id)
+            .all(db)
+            .await?;
+
+        let offers = TradeOfferItem::find()
+            .filter(trade_offer_item::Column::Trade.eq(model.id))
+            .order_by_asc(trade_offer_item::Column::Id)
+            .all(db)
+            .await?;
+
+        let currency_offers = TradeOfferCurrency::find()
+            .filter(trade_offer_currency::Column::Trade.eq(model.id))
+            .order_by_asc(trade_offer_currency::Column::Id)
+            .all(db)
+            .await?;
+
+        Ok(Some(Trade::from_rows(model, participants, offers, currency_offers)))
+    }
+
+    // Trades matched by `query`, in its order. Participants and offers are loaded
+    // separately so a limit on the query counts trades rather than offered possessions.
+    pub async fn find_with<C: ConnectionTrait>(
+        db: &C,
+        query: Select<TradeEntity>,
+    ) -> Result<Vec<Trade>, DbErr> {
+        let models = query.all(db).await?;
+        let participants = models.load_many(TradeParticipant, db).await?;
+        let offers = models.load_many(TradeOfferItem, db).await?;
+        let currency_offers = models.load_many(TradeOfferCurrency, db).await?;
+
+        Ok(models
+            .into_iter()
+            .zip(participants)
+            .zip(offers)
+            .zip(currency_offers)
+            .map(|(((model, mut participants), offers), currency_offers)| {
+                participants.sort_by_key(|p| p.id);
+                Trade::from_rows(model, participants, offers, currency_offers)
+            })
+            .collect())
+    }
+
+    // The open trade holding a lock on the possession, if any.
+    // A possession offered in a trade stays locked until it is removed from
+    // the trade or the trade is closed.
+    pub async fn find_locking<C: ConnectionTrait>(
+        db: &C,
+        possession_id: i32,
+    ) -> Result<Option<TradeId>, DbErr> {
+        let offer = TradeOfferItem::find()
+            .filter(trade_offer_item::Column::Possession.eq(possession_id))
+            .one(db)
+            .await?;
+
+        Ok(offer.map(|offer| offer.trade))
+    }
+
+    // Open trades `owner_id` takes part in, oldest first
+    pub async fn find_open_by_owner<C: ConnectionTrait>(
+        db: &C,
+        owner_id: i32,
+    ) -> Result<Vec<Trade>, DbErr> {
+        let query = TradeEntity::find()
+            .filter(trade::Column::State.is_in([
+                TradeState::Proposed,
+                TradeState::Negotiating,
+                TradeState::Accepted,
+            ]))
+            .filter(
+                trade::Column::Id.in_subquery(
+                    sea_orm::sea_query::Query::select()
+                        .column(trade_participant::Column::Trade)
+                        .from(TradeParticipant)
+                        .and_where(trade_participant::Column::Owner.eq(owner_id))
+                        .to_owned(),
+                ),
+            )
+            .order_by_asc(trade::Column::Id);
+
+        Self::find_with(db, query).await
+    }
+
+    // Executes, cancels or expires the trade and releases its offered possessions and currency
+    pub async fn close<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        state: TradeState,
+        now: DateTime<Utc>,
+    ) -> Result<(), TradeError> {
+        if is_open(state) {
+            return Err(TradeError::InvalidTransition {
+                trade: self.id,
+                from: self.state,
+                to: state,
+            });
+        }
+        self.transition(state, now)?;
+        if let Some(kind) = TradeEventKind::closed(state) {
+            self.record_event(kind);
+        }
+
+        TradeOfferItem::delete_many()
+            .filter(trade_offer_item::Column::Trade.eq(self.id))
+            .exec(db)
+            .await?;
+        TradeOfferCurrency::delete_many()
+            .filter(trade_offer_currency::Column::Trade.eq(self.id))
+            .exec(db)
+            .await?;
+
+        self.save_status(db).await?;
+        Ok(())
+    }
+
+    // Expires every open trade whose expiry time has passed
+    pub async fn expire_stale<C: ConnectionTrait>(
+        db: &C,
+        now: DateTime<Utc>,
+    ) -> Result<Vec<TradeId>, TradeError> {
+        let query = TradeEntity::find()
+            .filter(trade::Column::State.is_in([
+                TradeState::Proposed,
+                TradeState::Negotiating,
+                TradeState::Accepted,
+            ]))
+            .filter(trade::Column::ExpiresAt.lte(now))
+            .order_by_asc(trade::Column::Id);
+
+        let mut expired = Vec::new();
+        for mut trade in Self::find_with(db, query).await? {
+            trade.close(db, TradeState::Expired, now).await?;
+            expired.push(trade.id);
+        }
+        Ok(expired)
+    }
+
+    // Removes the trade, the offered items are released along with it.
+    // Closed trades are kept, so only the tests clean up this way.
+    #[cfg(test)]
+    pub async fn delete<C: ConnectionTrait>(&self, db: &C) -> Result<(), DbErr> {
+        TradeEntity::delete_by_id(self.id).exec(db).await?;
+        Ok(())
+    }
+
+    // Writes the state and the flags of every participant back to the stored trade
+    async fn save_status<C: ConnectionTrait>(&self, db: &C) -> Result<(), DbErr> {
+        let active_model = trade::ActiveModel {
+            id: ActiveValue::unchanged(self.id),
+            state: ActiveValue::set(self.state),
+            updated_at: ActiveValue::set(self.updated_at),
+            closed_at: ActiveValue::set(self.closed_at),
+            ready_at: ActiveValue::set(self.ready_at),
+            ..Default::default()
+        };
+        active_model.update(db).await?;
+
+        for participant in &self.participants {
+            let active_model = trade_participant::ActiveModel {
+                id: ActiveValue::unchanged(participant.id),
+                ready: ActiveValue::set(participant.ready),
+                confirmed: ActiveValue::set(participant.confirmed),
+                ..Default::default()
+            };
+            active_model.update(db).await?;
+        }
+        Ok(())
+    }
+}
+
+impl TradeLogic for Trade {
+    // Offers `quantity` of the possession of `owner` to `recipient`, both have to take part in the trade.
+    // The caller makes sure the possession holds at least `quantity`.
+    async fn add_to_trade<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        item: &PossessionModel,
+        recipient: i32,
+        quantity: i32,
+    ) -> Result<bool, TradeError> {
+        if !self.has_trader(owner.id) || !self.has_trader(recipient) || owner.id == recipient {
+            return Ok(false);
+        }
+
+        // Any change to the offer withdraws readiness and confirmations of everyone
+        self.transition(TradeState::Negotiating, Utc::now())?;
+
+        // Fails on the unique offer index if the possession is already locked
+        let offer = trade_offer_item::ActiveModel {
+            trade: ActiveValue::set(self.id),
+            owner: ActiveValue::set(owner.id),
+            possession: ActiveValue::set(item.id),
+            recipient: ActiveValue::set(recipient),
+            quantity: ActiveValue::set(quantity),
+            ..Default::default()
+        };
+        offer.insert(db).await?;
+
+        self.offers.push(Offer {
+            owner: owner.id,
+            possession: item.id,
+            recipient,
+            quantity,
+        });
+        self.record_event(TradeEventKind::ItemAdded {
+            owner_id: owner.id,
+            possession_id: item.id,
+            to_owner_id: recipient,
+            quantity,
+        });
+
+        self.reset_acceptance();
+        self.save_status(db).await?;
+        Ok(true)
+    }
+
+    async fn remove_from_trade<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        item: &PossessionModel,
+    ) -> Result<bool, TradeError> {
+        let Some(i) = self
+            .offers
+            .iter()
+            .position(|offer| offer.owner == owner.id && offer.possession == item.id)
+        else {
+            return Ok(false);
+        };
+
+        self.transition(TradeState::Negotiating, Utc::now())?;
+        self.offers.remove(i);
+        self.record_event(TradeEventKind::ItemRemoved {
+            owner_id: owner.id,
+            possession_id: item.id,
+        });
+
+        TradeOfferItem::delete_many()
+            .filter(trade_offer_item::Column::Trade.eq(self.id))
+            .filter(trade_offer_item::Column::Owner.eq(owner.id))
+            .filter(trade_offer_item::Column::Possession.eq(item.id))
+            .exec(db)
+            .await?;
+
+        self.reset_acceptance();
+        self.save_status(db).await?;
+        Ok(true)
+    }
+
+    // Sets the amount of `currency` that `owner` pays `recipient`, 0 withdraws the offer.
+    // The caller checks the currency and that the owner holds the amount.
+    async fn offer_currency<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        recipient: i32,
+        currency: &str,
+        amount: i64,
+    ) -> Result<bool, TradeError> {
+        if !self.has_trader(owner.id) || !self.has_trader(recipient) || owner.id == recipient {
+            return Ok(false);
+        }
+
+        self.transition(TradeState::Negotiating, Utc::now())?;
+
+        TradeOfferCurrency::delete_many()
+            .filter(trade_offer_currency::Column::Trade.eq(self.id))
+            .filter(trade_offer_currency::Column::Owner.eq(owner.id))
+            .filter(trade_offer_currency::Column::Recipient.eq(recipient))
+            .filter(trade_offer_currency::Column::Currency.eq(currency))
+            .exec(db)
+            .await?;
+        self.currency_offers.retain(|offer| {
+            !(offer.owner == owner.id && offer.recipient == recipient && offer.currency == currency)
+        });
+
+        if amount > 0 {
+            let offer = trade_offer_currency::ActiveModel {
+                trade: ActiveValue::set(self.id),
+                owner: ActiveValue::set(owner.id),
+                recipient: ActiveValue::set(recipient),
+                currency: ActiveValue::set(currency.to_string()),
+                amount: ActiveValue::set(amount),
+                ..Default::default()
+            };
+            offer.insert(db).await?;
+
+            self.currency_offers.push(CurrencyOffer {
+                owner: owner.id,
+                recipient,
+                currency: currency.to_string(),
+                amount,
+            });
+        }
+        self.record_event(TradeEventKind::CurrencyOffered {
+            owner_id: owner.id,
+            to_owner_id: recipient,
+            currency: currency.to_string(),
+            amount,
+        });
+
+        self.reset_acceptance();
+        self.save_status(db).await?;
+        Ok(true)
+    }
+
+    // Toggles whether `owner` is ready. Any change withdraws every confirmation,
+    // once everyone is ready the cooldown before confirming starts.
+    async fn change_trade_status<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+    ) -> Result<(), TradeError> {
+        let Some(participant) = self.participant(owner.id) else {
+            return Ok(());
+        };
+        let ready = !participant.ready;
+
+        let now = Utc::now();
+        let next = if ready || self.participants.iter().any(|p| p.ready && p.owner != owner.id) {
+            TradeState::Accepted
+        } else {
+            TradeState::Negotiating
+        };
+        self.transition(next, now)?;
+
+        for participant in &mut self.participants {
+            if participant.owner == owner.id {
+                participant.ready = ready;
             }
+            participant.confirmed = false;
         }
-        return false;
+        self.ready_at = self.all_ready().then_some(now);
+        self.record_event(TradeEventKind::AcceptToggled { owner_id: owner.id, ready });
+        self.save_status(db).await?;
+        Ok(())
     }
-    fn change_trade_status(&mut self, owner: &OwnerModel) {
-        if self.trader_1.id == owner.id {
-            self.trade_1_accept = !self.trade_1_accept;
-        } else if self.trader_2.id == owner.id {
-            self.trade_2_accept = !self.trade_2_accept;
+
+    // Confirms the trade for `owner`. Only possible once everyone is ready,
+    // the cooldown has passed and `items_hash` matches the offered items.
+    // Once every recipient confirmed the caller executes the trade and closes it.
+    async fn confirm_trade<C: ConnectionTrait>(
+        &mut self,
+        db: &C,
+        owner: &OwnerModel,
+        items_hash: &str,
+        cooldown: TimeDelta,
+    ) -> Result<(), TradeError> {
+        let now = Utc::now();
+        self.transition(TradeState::Accepted, now)?;
+
+        let Some(ready_at) = self.ready_at.filter(|_| self.all_ready()) else {
+            return Err(TradeError::NotReady(self.id));
+        };
+
+        let until = ready_at + cooldown;
+        if now < until {
+            return Err(TradeError::CoolingDown { trade: self.id, until });
+        }
+
+        if items_hash != self.items_hash() {
+            return Err(TradeError::ItemsChanged(self.id));
         }
+
+        if self.offers_nothing() {
+            return Err(TradeError::NothingOffered(self.id));
+        }
+
+        let Some(participant) = self.participant_mut(owner.id) else {
+            return Ok(());
+        };
+        participant.confirmed = true;
+        self.record_event(TradeEventKind::Confirmed { owner_id: owner.id });
+
+        self.save_status(db).await?;
+        Ok(())
     }
 }
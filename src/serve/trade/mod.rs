@@ -1,2 +1,6 @@
 pub mod routes;
-pub mod logic;
\ No newline at end of file
+pub mod logic;
+pub mod history;
+pub mod scheduler;
+pub mod escrow;
+pub mod events;
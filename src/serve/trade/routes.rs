@@ -1,18 +1,39 @@
-use crate::db::entities::{possession, prelude::*};
-use crate::serve::trade::logic::{Trade, TradeLogic, TradesMutex};
+use crate::db::entities::owner::Model as OwnerModel;
+use crate::db::entities::possession::Model as PossessionModel;
+use crate::config::Config;
+use crate::db::entities::trade::TradeState;
+use crate::db::entities::{item, owner, possession, prelude::*, trade, trade_history, trade_participant};
+use crate::serve::auth::{Admin, Caller};
+use crate::serve::error::{ErrorResponse, VentilError};
+use crate::serve::idempotency::{Idempotency, Idempotent, IdempotentJson};
+use crate::serve::pagination::{Page, PageRequest, SortOrder};
+use crate::db::entities::escrow::{self, EscrowState};
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::events::{TradeEvent, TradeEventKind, TradeEvents};
+use crate::serve::trade::history::{Rollback, TradeRecord};
+use crate::serve::possession::stacks;
+use crate::serve::trade::logic::{Offer, Participant, Trade, TradeError, TradeId, TradeLogic, is_open};
+use crate::serve::wallet::ledger::{self, Account, LedgerError, Transfer};
+use crate::serve::wallet::routes::ensure_currency;
 use rocket::{
-    Build, Rocket, State,
+    Build, FromForm, Rocket, Shutdown, State,
     delete, get, post, put,
     http::Status,
-    response::status::{Created, NotFound},
+    response::{
+        status::Created,
+        stream::{Event, EventStream},
+    },
     routes,
     serde::{Deserialize, Serialize, json::Json},
+    tokio::{select, sync::broadcast::error::RecvError},
 };
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, DatabaseConnection, EntityTrait, DbErr, TransactionTrait
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DatabaseTransaction, EntityTrait, DbErr, QueryFilter, TransactionTrait
 };
-use std::sync::atomic::{AtomicU64, Ordering};
-use utoipa::{ToSchema, OpenApi};
+use chrono::{DateTime, TimeDelta, Utc};
+use std::fmt;
+use utoipa::{IntoParams, ToSchema, OpenApi};
 
 pub trait TradeRoutes {
     fn mount_trades(self) -> Self;
@@ -24,31 +45,144 @@ impl TradeRoutes for Rocket<Build> {
             "/trades",
             routes![
                 get_all_trades,
+                get_trade_history,
+                get_trade_history_by_id,
+                rollback_trade,
                 get_trade_by_id,
+                get_trade_events,
                 create_trade,
+                create_gift,
                 add_item_to_trade,
                 remove_item_from_trade,
+                offer_currency,
                 accept_trade,
+                confirm_trade,
                 cancel_trade,
             ],
         )
     }
 }
 
-// Unique ID generator for trades
-static NEXT_TRADE_ID: AtomicU64 = AtomicU64::new(1);
-
 // Response model for trades
 #[derive(Serialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
 pub struct TradeResponse {
-    pub id: u64,
+    pub id: TradeId,
+    /// Owner who opened the trade
+    pub trader_1_id: i32,
+    pub participants: Vec<TradeParticipantResponse>,
+    pub state: TradeState,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub closed_at: Option<DateTime<Utc>>,
+    /// When every trader became ready, confirming is possible after the cooldown
+    pub ready_at: Option<DateTime<Utc>>,
+    /// Hash of the offered items, has to be sent along when confirming
+    pub items_hash: String,
+}
+
+// A trader taking part in a trade and what they give
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeParticipantResponse {
+    pub owner_id: i32,
+    pub ready: bool,
+    /// Only traders receiving something have to confirm
+    pub confirmed: bool,
+    pub gives: Vec<TradeOfferResponse>,
+    pub gives_currency: Vec<TradeCurrencyOfferResponse>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeOfferResponse {
+    pub possession_id: i32,
+    pub to_owner_id: i32,
+    pub quantity: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeCurrencyOfferResponse {
+    pub to_owner_id: i32,
+    pub currency: String,
+    pub amount: i64,
+}
+
+// A possession that changed hands in an executed trade
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeHistoryItemResponse {
+    pub possession_id: i32,
+    pub from_owner_id: i32,
+    pub to_owner_id: i32,
+    pub quantity: i32,
+}
+
+// Response model for an executed trade in the history
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeHistoryResponse {
+    pub id: i32,
+    pub trade_id: TradeId,
     pub trader_1_id: i32,
     pub trader_1_items: Vec<i32>,
-    pub trader_1_accept: bool,
     pub trader_2_id: i32,
     pub trader_2_items: Vec<i32>,
-    pub trader_2_accept: bool,
+    /// Every possession that changed hands, including those of further traders
+    pub items: Vec<TradeHistoryItemResponse>,
+    /// Currency that changed hands
+    pub currency: Vec<TradeHistoryCurrencyResponse>,
+    pub executed_at: DateTime<Utc>,
+    pub rolled_back_at: Option<DateTime<Utc>>,
+}
+
+// An amount of currency that changed hands in an executed trade
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeHistoryCurrencyResponse {
+    pub from_owner_id: i32,
+    pub to_owner_id: i32,
+    pub currency: String,
+    pub amount: i64,
+}
+
+// A possession a rollback could not return to its original owner
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct UnrecoverableItemResponse {
+    pub possession_id: i32,
+    pub reason: String,
+}
+
+// Response model for a rolled back trade
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct RollbackResponse {
+    pub trade: TradeHistoryResponse,
+    /// Possessions returned to their original owner, units split off a grown stack come back as a new possession
+    pub restored: Vec<i32>,
+    /// Possessions that stay where they are, or of which only part was left to return
+    pub unrecoverable: Vec<UnrecoverableItemResponse>,
+    /// Currency paid back to the trader who gave it
+    pub refunded: Vec<TradeHistoryCurrencyResponse>,
+    /// Currency the recipient no longer holds, it stays where it is
+    pub unrefunded: Vec<TradeHistoryCurrencyResponse>,
+}
+
+// Response model for a possession held in escrow after a trade
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct EscrowResponse {
+    pub id: i32,
+    pub trade_history_id: i32,
+    pub possession_id: i32,
+    pub from_owner_id: i32,
+    pub to_owner_id: i32,
+    pub state: EscrowState,
+    pub held_at: DateTime<Utc>,
+    pub release_at: DateTime<Utc>,
 }
 
 // Request model for creating a trade
@@ -57,6 +191,12 @@ pub struct TradeResponse {
 pub struct CreateTradeRequest {
     pub trader_1_id: i32,
     pub trader_2_id: i32,
+    /// Further traders of a multi-party trade
+    #[serde(default)]
+    pub other_trader_ids: Vec<i32>,
+    /// Seconds until the trade expires, overrides the configured default. 0 never expires.
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
 }
 
 // Request model for adding/removing items
@@ -65,78 +205,570 @@ pub struct CreateTradeRequest {
 pub struct TradeItemRequest {
     pub owner_id: i32,
     pub item_id: i32,
+    /// Trader receiving the item, may be left out when there is only one other trader
+    #[serde(default)]
+    pub to_owner_id: Option<i32>,
+    /// Part of a stack to offer, the whole stack when left out
+    #[serde(default)]
+    pub quantity: Option<i32>,
+}
+
+// Request model for offering currency, an amount of 0 withdraws the offer
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct TradeCurrencyRequest {
+    pub owner_id: i32,
+    pub currency: String,
+    pub amount: i64,
+    /// Trader receiving the amount, may be left out when there is only one other trader
+    #[serde(default)]
+    pub to_owner_id: Option<i32>,
+}
+
+// Request model for gifting items, the recipient only has to accept
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct GiftRequest {
+    pub from_owner_id: i32,
+    pub to_owner_id: i32,
+    pub item_ids: Vec<i32>,
+    /// Seconds until the gift expires, overrides the configured default. 0 never expires.
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+// Request model for confirming a trade, names the items the trader agreed to
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct ConfirmTradeRequest {
+    pub items_hash: String,
 }
 
+// Response model for readying or confirming a trade, the trade is executed once every recipient confirmed
 #[derive(Serialize, ToSchema)]
 #[serde(crate = "rocket::serde")]
-pub struct ApiResponse {
+pub struct AcceptTradeResponse {
     pub message: String,
 }
 
-// Helper function to execute a trade
+impl From<&Trade> for TradeResponse {
+    fn from(trade: &Trade) -> Self {
+        TradeResponse {
+            id: trade.id,
+            trader_1_id: trade.trader_1,
+            participants: trade
+                .participants
+                .iter()
+                .map(|participant| TradeParticipantResponse {
+                    owner_id: participant.owner,
+                    ready: participant.ready,
+                    confirmed: participant.confirmed,
+                    gives: trade
+                        .offers
+                        .iter()
+                        .filter(|offer| offer.owner == participant.owner)
+                        .map(|offer| TradeOfferResponse {
+                            possession_id: offer.possession,
+                            to_owner_id: offer.recipient,
+                            quantity: offer.quantity,
+                        })
+                        .collect(),
+                    gives_currency: trade
+                        .currency_offers
+                        .iter()
+                        .filter(|offer| offer.owner == participant.owner)
+                        .map(|offer| TradeCurrencyOfferResponse {
+                            to_owner_id: offer.recipient,
+                            currency: offer.currency.clone(),
+                            amount: offer.amount,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            state: trade.state,
+            created_at: trade.created_at,
+            updated_at: trade.updated_at,
+            expires_at: trade.expires_at,
+            closed_at: trade.closed_at,
+            ready_at: trade.ready_at,
+            items_hash: trade.items_hash(),
+        }
+    }
+}
+
+impl From<&TradeRecord> for TradeHistoryResponse {
+    fn from(record: &TradeRecord) -> Self {
+        TradeHistoryResponse {
+            id: record.history.id,
+            trade_id: record.history.trade,
+            trader_1_id: record.history.trader_1,
+            trader_1_items: record.items_given_by(record.history.trader_1),
+            trader_2_id: record.history.trader_2,
+            trader_2_items: record.items_given_by(record.history.trader_2),
+            items: record
+                .items
+                .iter()
+                .map(|item| TradeHistoryItemResponse {
+                    possession_id: item.possession,
+                    from_owner_id: item.from_owner,
+                    to_owner_id: item.to_owner,
+                    quantity: item.quantity,
+                })
+                .collect(),
+            currency: currency_responses(&record.currency),
+            executed_at: record.history.executed_at,
+            rolled_back_at: record.history.rolled_back_at,
+        }
+    }
+}
+
+// Trade transfers only ever move currency between owners
+fn currency_responses(transfers: &[Transfer]) -> Vec<TradeHistoryCurrencyResponse> {
+    transfers
+        .iter()
+        .filter_map(|transfer| match (transfer.from, transfer.to) {
+            (Account::Owner(from), Account::Owner(to)) => Some(TradeHistoryCurrencyResponse {
+                from_owner_id: from,
+                to_owner_id: to,
+                currency: transfer.currency.clone(),
+                amount: transfer.amount,
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+impl RollbackResponse {
+    fn new(record: &TradeRecord, rollback: Rollback) -> Self {
+        RollbackResponse {
+            trade: TradeHistoryResponse::from(record),
+            restored: rollback.restored,
+            unrecoverable: rollback
+                .unrecoverable
+                .into_iter()
+                .map(|(possession_id, reason)| UnrecoverableItemResponse {
+                    possession_id,
+                    reason: reason.to_string(),
+                })
+                .collect(),
+            refunded: currency_responses(&rollback.refunded),
+            unrefunded: currency_responses(&rollback.unrefunded),
+        }
+    }
+}
+
+impl From<&escrow::Model> for EscrowResponse {
+    fn from(entry: &escrow::Model) -> Self {
+        EscrowResponse {
+            id: entry.id,
+            trade_history_id: entry.trade_history,
+            possession_id: entry.possession,
+            from_owner_id: entry.from_owner,
+            to_owner_id: entry.to_owner,
+            state: entry.state,
+            held_at: entry.held_at,
+            release_at: entry.release_at,
+        }
+    }
+}
+
+// Reasons an accepted trade can not be executed
+pub enum TradeExecutionError {
+    PossessionMissing(i32),
+    OwnerChanged { possession: i32, expected: i32, actual: i32 },
+    OfferedElsewhere { possession: i32, trade: TradeId },
+    QuantityChanged { possession: i32, offered: i32, held: i32 },
+    // A trader or recipient was deleted or banned after agreeing to the trade
+    MayNotTrade(VentilError),
+    // A trader no longer holds the offered currency, or a balance can not grow that much
+    Settlement(LedgerError),
+    Database(DbErr),
+}
+
+impl From<DbErr> for TradeExecutionError {
+    fn from(err: DbErr) -> Self {
+        TradeExecutionError::Database(err)
+    }
+}
+
+impl From<LedgerError> for TradeExecutionError {
+    fn from(err: LedgerError) -> Self {
+        match err {
+            LedgerError::Database(err) => TradeExecutionError::Database(err),
+            err => TradeExecutionError::Settlement(err),
+        }
+    }
+}
+
+impl fmt::Display for TradeExecutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeExecutionError::PossessionMissing(possession) => {
+                write!(f, "Possession {} no longer exists", possession)
+            }
+            TradeExecutionError::OwnerChanged { possession, expected, actual } => write!(
+                f,
+                "Possession {} is owned by {} instead of trader {}",
+                possession, actual, expected
+            ),
+            TradeExecutionError::OfferedElsewhere { possession, trade } => write!(
+                f,
+                "Possession {} is also offered in open trade {}",
+                possession, trade
+            ),
+            TradeExecutionError::QuantityChanged { possession, offered, held } => write!(
+                f,
+                "Possession {} holds {}, less than the {} offered",
+                possession, held, offered
+            ),
+            TradeExecutionError::MayNotTrade(err) => write!(f, "{}", err),
+            TradeExecutionError::Settlement(err) => write!(f, "{}", err),
+            TradeExecutionError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+// Helper function to execute a trade.
+// Runs entirely inside the caller's transaction, so either every possession
+// and every offered amount changes hands or none do. With `hold_until` the
+// possessions stay in escrow until then instead of changing hands right away,
+// the currency is paid regardless.
 async fn execute_trade_internal(
     trade: &Trade,
-    db: &DatabaseConnection
-) -> Result<(), DbErr> {
-    // Start a transaction
-    let txn = db.begin().await?;
-    
-    // Update ownership of all items from trader 1 to trader 2
-    for item_id in &trade.trade_1_items {
-        let possession_result = Possession::find_by_id(*item_id).one(db).await?;
-        
-        if let Some(possession) = possession_result {
-            let mut active_model: possession::ActiveModel = possession.into();
-            active_model.owner = ActiveValue::set(trade.trader_2.id);
-            active_model.update(db).await?;
-        }
+    txn: &DatabaseTransaction,
+    hold_until: Option<DateTime<Utc>>,
+) -> Result<(), TradeExecutionError> {
+    // Every trader and recipient has to still be allowed to trade, not just the one confirming last
+    let mut traders: Vec<i32> = trade.participants.iter().map(|p| p.owner).collect();
+    traders.extend(trade.offers.iter().map(|offer| offer.recipient));
+    traders.extend(trade.currency_offers.iter().map(|offer| offer.recipient));
+    traders.sort_unstable();
+    traders.dedup();
+    for owner_id in traders {
+        let owner = Owner::find_by_id(owner_id)
+            .one(txn)
+            .await?
+            .ok_or_else(|| TradeExecutionError::MayNotTrade(owner_not_found(owner_id)))?;
+        ensure_may_trade(&owner).map_err(TradeExecutionError::MayNotTrade)?;
     }
-    
-    // Update ownership of all items from trader 2 to trader 1
-    for item_id in &trade.trade_2_items {
-        let possession_result = Possession::find_by_id(*item_id).one(db).await?;
-        
-        if let Some(possession) = possession_result {
+
+    let mut delivered = Vec::with_capacity(trade.offers.len());
+
+    // Every offered item goes to the recipient named in its offer
+    for offer in &trade.offers {
+        let (item_id, from_owner, to_owner) = (offer.possession, offer.owner, offer.recipient);
+
+        // Re-validate the possession now that the trade is about to commit
+        let possession = Possession::find_by_id(item_id)
+            .one(txn)
+            .await?
+            .ok_or(TradeExecutionError::PossessionMissing(item_id))?;
+
+        if possession.owner != from_owner {
+            return Err(TradeExecutionError::OwnerChanged {
+                possession: item_id,
+                expected: from_owner,
+                actual: possession.owner,
+            });
+        }
+
+        if let Some(other_trade) = Trade::find_locking(txn, item_id).await?
+            && other_trade != trade.id
+        {
+            return Err(TradeExecutionError::OfferedElsewhere {
+                possession: item_id,
+                trade: other_trade,
+            });
+        }
+
+        if possession.quantity < offer.quantity {
+            return Err(TradeExecutionError::QuantityChanged {
+                possession: item_id,
+                offered: offer.quantity,
+                held: possession.quantity,
+            });
+        }
+
+        // Part of a stack is split off, the rest stays with the sender
+        let possession = if possession.quantity > offer.quantity {
+            stacks::split_off(txn, possession, offer.quantity).await?
+        } else {
+            possession
+        };
+        delivered.push(Offer { possession: possession.id, ..*offer });
+
+        if hold_until.is_none() {
             let mut active_model: possession::ActiveModel = possession.into();
-            active_model.owner = ActiveValue::set(trade.trader_1.id);
-            active_model.update(db).await?;
+            active_model.owner = ActiveValue::set(to_owner);
+            active_model.update(txn).await?;
         }
     }
     
-    // Commit the transaction
-    txn.commit().await?;
+    // Record what changed hands in the trade history
+    let mut record = TradeRecord::record(txn, trade, &delivered).await?;
+
+    // Fails when a trader spent the offered currency since offering it
+    record.settle(txn, &trade.currency_offers).await?;
+
+    if let Some(release_at) = hold_until {
+        Escrow::hold(txn, &record, release_at).await?;
+    }
     
     Ok(())
 }
 
+// Trades involving an account younger than the configured age are held in escrow
+async fn hold_until(
+    txn: &DatabaseTransaction,
+    trade: &Trade,
+    config: &Config,
+) -> Result<Option<DateTime<Utc>>, DbErr> {
+    let now = Utc::now();
+    let (Some(hold), Some(account_age)) = (
+        seconds(config.trade_hold_secs),
+        seconds(config.trade_hold_account_age_secs),
+    ) else {
+        return Ok(None);
+    };
+
+    let traders = Owner::find()
+        .filter(owner::Column::Id.is_in(trade.participants.iter().map(|p| p.owner)))
+        .all(txn)
+        .await?;
+
+    let is_new = traders.iter().any(|trader| trader.created_at + account_age > now);
+    Ok(is_new.then(|| now.checked_add_signed(hold).unwrap_or(DateTime::<Utc>::MAX_UTC)))
+}
+
+// A positive number of seconds as a `TimeDelta`, 0 and out of range values are `None`
+fn seconds(secs: u64) -> Option<TimeDelta> {
+    i64::try_from(secs)
+        .ok()
+        .filter(|secs| *secs > 0)
+        .and_then(TimeDelta::try_seconds)
+}
+
+// Traders see and cancel their own trades, admins every trade
+fn can_access(caller: &Caller, trade: &Trade) -> bool {
+    trade.participants.iter().any(|p| caller.can_act_for(p.owner))
+}
+
+fn not_found(id: TradeId) -> VentilError {
+    VentilError::NotFound(format!("Trade with id {} not found", id))
+}
+
+fn owner_not_found(id: i32) -> VentilError {
+    VentilError::NotFound(format!("Owner with id {} not found", id))
+}
+
+// Time every trader waits after getting ready before confirming
+fn confirm_cooldown(config: &Config) -> TimeDelta {
+    TimeDelta::try_seconds(i64::try_from(config.trade_confirm_cooldown_secs).unwrap_or(i64::MAX))
+        .unwrap_or(TimeDelta::MAX)
+}
+
+fn forbidden_owner(id: i32) -> VentilError {
+    VentilError::Forbidden(format!("Not allowed to act for owner {}", id))
+}
+
+// Deleted, banned and trade banned owners can not open, join or agree to trades
+fn ensure_may_trade(owner: &OwnerModel) -> Result<(), VentilError> {
+    if owner.deleted_at.is_some() {
+        return Err(VentilError::Forbidden(format!("Owner {} is deleted", owner.id)));
+    }
+    if owner.banned || owner.trade_banned {
+        return Err(VentilError::Forbidden(format!("Owner {} is banned from trading", owner.id)));
+    }
+    Ok(())
+}
+
+// Query of the trade list, filters are combined
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TradeQuery {
+    /// Maximum number of trades to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return trades after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Only trades this owner takes part in
+    pub trader_id: Option<i32>,
+}
+
 // GET /trades - Get all trades
 #[utoipa::path(
     get,
     path = "/trades",
     tags = ["trades"],
+    params(TradeQuery),
+    security(("api_key" = [])),
     responses(
-        (status = 200, description = "List all trades successfully", body = [TradeResponse])
+        (status = 200, description = "List trades successfully", body = Page<TradeResponse>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
     )
 )]
-#[get("/")]
+#[get("/?<query..>")]
 pub async fn get_all_trades(
-    trades: &State<TradesMutex>,
-) -> Json<Vec<TradeResponse>> {
-    let trades_lock = trades.lock().await;
-    
-    let responses = trades_lock.iter().map(|(_ ,trade)| {
-        TradeResponse {
-            id: trade.id,
-            trader_1_id: trade.trader_1.id,
-            trader_1_items: trade.trade_1_items.clone(),
-            trader_1_accept: trade.trade_1_accept,
-            trader_2_id: trade.trader_2.id,
-            trader_2_items: trade.trade_2_items.clone(),
-            trader_2_accept: trade.trade_2_accept,
-        }
-    }).collect();
-    
-    Json(responses)
+    _admin: Admin,
+    query: TradeQuery,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Page<TradeResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+    let page = PageRequest::new(query.limit, query.after, query.order);
+
+    let mut select = trade::Entity::find();
+    if let Some(trader_id) = query.trader_id {
+        select = select.filter(
+            trade::Column::Id.in_subquery(
+                sea_orm::sea_query::Query::select()
+                    .column(trade_participant::Column::Trade)
+                    .from(TradeParticipant)
+                    .and_where(trade_participant::Column::Owner.eq(trader_id))
+                    .to_owned(),
+            ),
+        );
+    }
+
+    let trades = Trade::find_with(db, page.apply(select, trade::Column::Id)).await?;
+    let (trades, next) = page.finish(trades, |t| t.id);
+
+    let items = trades.iter().map(TradeResponse::from).collect();
+
+    Ok(Json(Page { items, next }))
+}
+
+// Query of the trade history list
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TradeHistoryQuery {
+    /// Maximum number of executed trades to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return executed trades after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending, newest first by default
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+}
+
+// GET /trades/history - Get all executed trades
+#[utoipa::path(
+    get,
+    path = "/trades/history",
+    tags = ["trades"],
+    params(TradeHistoryQuery),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "List executed trades successfully", body = Page<TradeHistoryResponse>),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[get("/history?<query..>")]
+pub async fn get_trade_history(
+    _admin: Admin,
+    query: TradeHistoryQuery,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Page<TradeHistoryResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+    let order = query.order.unwrap_or(SortOrder::Desc);
+    let page = PageRequest::new(query.limit, query.after, Some(order));
+
+    let select = page.apply(TradeHistory::find(), trade_history::Column::Id);
+    let records = TradeRecord::find_with(db, select).await?;
+    let (records, next) = page.finish(records, |r| r.history.id);
+
+    let items = records.iter().map(TradeHistoryResponse::from).collect();
+
+    Ok(Json(Page { items, next }))
+}
+
+// GET /trades/history/<id> - Get an executed trade by history ID
+#[utoipa::path(
+    get,
+    path = "/trades/history/{id}",
+    tags = ["trades"],
+    params(
+        ("id" = i32, Path, description = "Trade history identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Executed trade found successfully", body = TradeHistoryResponse),
+        (status = 404, description = "Executed trade not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller did not take part in the trade", body = ErrorResponse)
+    )
+)]
+#[get("/history/<id>")]
+pub async fn get_trade_history_by_id(
+    caller: Caller,
+    id: i32,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<TradeHistoryResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    let record = TradeRecord::find_by_id(db, id)
+        .await?
+        .ok_or_else(|| VentilError::NotFound(format!("Trade history with id {} not found", id)))?;
+
+    let took_part = [record.history.trader_1, record.history.trader_2]
+        .into_iter()
+        .chain(record.items.iter().flat_map(|item| [item.from_owner, item.to_owner]))
+        .any(|owner_id| caller.can_act_for(owner_id));
+    if !took_part {
+        return Err(VentilError::Forbidden(format!("Not allowed to view trade history {}", id)));
+    }
+
+    Ok(Json(TradeHistoryResponse::from(&record)))
+}
+
+// POST /trades/history/<id>/rollback - Reverse an executed trade
+#[utoipa::path(
+    post,
+    path = "/trades/history/{id}/rollback",
+    tags = ["trades"],
+    params(
+        ("id" = i32, Path, description = "Trade history identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Trade rolled back, possessions that could not be returned are listed", body = RollbackResponse),
+        (status = 404, description = "Executed trade not found", body = ErrorResponse),
+        (status = 409, description = "Trade was already rolled back", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/history/<id>/rollback")]
+pub async fn rollback_trade(
+    _admin: Admin,
+    id: i32,
+    database: &State<DatabaseConnection>,
+    events: &State<TradeEvents>,
+) -> Result<Json<RollbackResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // Every possession is returned, or none are
+    let txn = db.begin().await?;
+
+    let mut record = TradeRecord::find_by_id(&txn, id)
+        .await?
+        .ok_or_else(|| VentilError::NotFound(format!("Trade history with id {} not found", id)))?;
+
+    if record.is_rolled_back() {
+        return Err(VentilError::Conflict(format!(
+            "Trade history {} was already rolled back",
+            id
+        )));
+    }
+
+    let mut rollback = record.roll_back(&txn, Utc::now()).await?;
+    txn.commit().await?;
+    events.publish(std::mem::take(&mut rollback.events));
+
+    Ok(Json(RollbackResponse::new(&record, rollback)))
 }
 
 // GET /trades/<id> - Get trade by ID
@@ -145,38 +777,95 @@ pub async fn get_all_trades(
     path = "/trades/{id}",
     tags = ["trades"],
     params(
-        ("id" = u64, Path, description = "Trade identifier")
+        ("id" = i32, Path, description = "Trade identifier")
     ),
+    security(("api_key" = [])),
     responses(
         (status = 200, description = "Trade found successfully", body = TradeResponse),
-        (status = 404, description = "Trade not found", body = ApiResponse)
+        (status = 404, description = "Trade not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not part of the trade", body = ErrorResponse)
     )
 )]
 #[get("/<id>")]
 pub async fn get_trade_by_id(
-    id: u64,
-    trades: &State<TradesMutex>,
-) -> Result<Json<TradeResponse>, NotFound<Json<ApiResponse>>> {
+    caller: Caller,
+    id: TradeId,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<TradeResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
 
-    let mut trades_lock = trades.lock().await;
-    let trade_opt= trades_lock.get_mut(&id);
-    
-    match trade_opt {
-        Some(trade) => {
-            Ok(Json(TradeResponse {
-                id: trade.id,
-                trader_1_id: trade.trader_1.id,
-                trader_1_items: trade.trade_1_items.clone(),
-                trader_1_accept: trade.trade_1_accept,
-                trader_2_id: trade.trader_2.id,
-                trader_2_items: trade.trade_2_items.clone(),
-                trader_2_accept: trade.trade_2_accept,
-            }))
-        },
-        None => Err(NotFound(Json(ApiResponse {
-            message: format!("Trade with id {} not found", id),
-        }))),
+    let trade = Trade::find_by_id(db, id).await?.ok_or_else(|| not_found(id))?;
+
+    if !can_access(&caller, &trade) {
+        return Err(VentilError::Forbidden(format!("Not allowed to view trade {}", id)));
     }
+
+    Ok(Json(TradeResponse::from(&trade)))
+}
+
+// GET /trades/<id>/events - Follow changes to a trade as Server-Sent Events
+#[utoipa::path(
+    get,
+    path = "/trades/{id}/events",
+    tags = ["trades"],
+    params(
+        ("id" = i32, Path, description = "Trade identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Stream of trade events, named by their type. It ends once the trade is executed, cancelled or expired", body = TradeEvent, content_type = "text/event-stream"),
+        (status = 404, description = "Trade not found", body = ErrorResponse),
+        (status = 409, description = "Trade is already closed", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not part of the trade", body = ErrorResponse)
+    )
+)]
+#[get("/<id>/events", rank = 2)]
+pub async fn get_trade_events(
+    caller: Caller,
+    id: TradeId,
+    database: &State<DatabaseConnection>,
+    events: &State<TradeEvents>,
+    mut shutdown: Shutdown,
+) -> Result<EventStream![Event + 'static], VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // Subscribe before looking at the trade so no change in between is missed
+    let mut receiver = events.subscribe();
+
+    let trade = Trade::find_by_id(db, id).await?.ok_or_else(|| not_found(id))?;
+
+    if !can_access(&caller, &trade) {
+        return Err(VentilError::Forbidden(format!("Not allowed to follow trade {}", id)));
+    }
+
+    if !is_open(trade.state) {
+        return Err(TradeError::Closed { trade: id, state: trade.state }.into());
+    }
+
+    Ok(EventStream! {
+        loop {
+            let event = select! {
+                received = receiver.recv() => match received {
+                    Ok(event) if event.trade_id == id => event,
+                    Ok(_) => continue,
+                    // Missed events are gone, the client reloads the trade to catch up
+                    Err(RecvError::Lagged(_)) => {
+                        yield Event::empty().event("lagged");
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                },
+                _ = &mut shutdown => break,
+            };
+
+            yield Event::json(&event).event(event.kind.name());
+            if event.kind.is_final() {
+                break;
+            }
+        }
+    })
 }
 
 // POST /trades - Create a new trade
@@ -184,73 +873,171 @@ pub async fn get_trade_by_id(
     post,
     path = "/trades",
     tags = ["trades"],
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
     request_body = CreateTradeRequest,
+    security(("api_key" = [])),
     responses(
         (status = 201, description = "Trade created successfully", body = TradeResponse),
-        (status = 400, description = "Invalid request data", body = ApiResponse)
+        (status = 400, description = "Invalid request data", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for trader 1, or a trader is banned from trading", body = ErrorResponse)
     )
 )]
 #[post("/", data = "<trade_data>")]
 pub async fn create_trade(
-    trade_data: Json<CreateTradeRequest>,
-    trades: &State<TradesMutex>,
+    caller: Caller,
+    idempotency: Idempotency,
+    trade_data: IdempotentJson<CreateTradeRequest>,
     database: &State<DatabaseConnection>,
-) -> Result<Created<Json<TradeResponse>>, Json<ApiResponse>> {
+    config: &State<Config>,
+) -> Result<Idempotent<Created<Json<TradeResponse>>>, VentilError> {
     let db = database as &DatabaseConnection;
-    
-    // Validate both traders exist
-    let trader_1 = match Owner::find_by_id(trade_data.trader_1_id).one(db).await {
-        Ok(Some(owner)) => owner,
-        _ => return Err(Json(ApiResponse {
-            message: format!("Trader with id {} not found", trade_data.trader_1_id),
-        })),
-    };
-    
-    let trader_2 = match Owner::find_by_id(trade_data.trader_2_id).one(db).await {
-        Ok(Some(owner)) => owner,
-        _ => return Err(Json(ApiResponse {
-            message: format!("Trader with id {} not found", trade_data.trader_2_id),
-        })),
-    };
-    
-    // Make sure traders are different
-    if trader_1.id == trader_2.id {
-        return Err(Json(ApiResponse {
-            message: "Cannot create trade with same trader on both sides".to_string(),
-        }));
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    // Only trader 1 can open a trade, the others join by adding items
+    if !caller.can_act_for(trade_data.trader_1_id) {
+        return Err(forbidden_owner(trade_data.trader_1_id));
+    }
+
+    let invited_ids: Vec<i32> = std::iter::once(trade_data.trader_2_id)
+        .chain(trade_data.other_trader_ids.iter().copied())
+        .collect();
+    let expires_at = expiry(config, trade_data.expires_in_secs)?;
+
+    // The trade and its participants are stored together
+    let txn = db.begin().await?;
+    let (trader_1, invited) = find_traders(&txn, trade_data.trader_1_id, &invited_ids).await?;
+
+    // Store the new trade, the database hands out the ID
+    let new_trade = Trade::create(&txn, &trader_1, &invited, expires_at).await?;
+    txn.commit().await?;
+
+    Ok(Created::new(format!("/trades/{}", new_trade.id)).body(Json(TradeResponse::from(&new_trade))).into())
+}
+
+// Looks up the trader opening a trade and the ones invited, all have to differ
+async fn find_traders<C: ConnectionTrait>(
+    db: &C,
+    trader_id: i32,
+    invited_ids: &[i32],
+) -> Result<(OwnerModel, Vec<OwnerModel>), VentilError> {
+    let trader_not_found = |id: i32| VentilError::BadRequest(format!("Trader with id {} not found", id));
+
+    let trader = Owner::find_by_id(trader_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| trader_not_found(trader_id))?;
+    ensure_may_trade(&trader)?;
+
+    let mut seen = vec![trader.id];
+    let mut invited = Vec::new();
+    for id in invited_ids {
+        // Make sure traders are different
+        if seen.contains(id) {
+            return Err(VentilError::BadRequest(format!(
+                "Trader {} can not take part in a trade twice",
+                id
+            )));
+        }
+        seen.push(*id);
+
+        let owner = Owner::find_by_id(*id)
+            .one(db)
+            .await?
+            .ok_or_else(|| trader_not_found(*id))?;
+        ensure_may_trade(&owner)?;
+        invited.push(owner);
+    }
+
+    Ok((trader, invited))
+}
+
+// Trades left open past their expiry are closed by the trade scheduler
+fn expiry(config: &Config, expires_in_secs: Option<u64>) -> Result<Option<DateTime<Utc>>, VentilError> {
+    match expires_in_secs.unwrap_or(config.trade_expiry_secs) {
+        0 => Ok(None),
+        secs => {
+            let too_large = || VentilError::BadRequest("expires_in_secs is too large".to_string());
+            let secs = i64::try_from(secs).map_err(|_| too_large())?;
+            let expires_at = TimeDelta::try_seconds(secs)
+                .and_then(|delta| Utc::now().checked_add_signed(delta))
+                .ok_or_else(too_large)?;
+            Ok(Some(expires_at))
+        }
     }
-    
-    // Get new ID
-    let trade_id = NEXT_TRADE_ID.fetch_add(1, Ordering::SeqCst);
-    
-    // Create new trade
-    let new_trade = Trade {
-        id: trade_id,
-        trader_1,
-        trade_1_accept: false,
-        trade_1_items: Vec::new(),
-        trader_2,
-        trade_2_accept: false,
-        trade_2_items: Vec::new(),
-    };
-    
-    // Add to collection
-    
-    let trade_res = Created::new(format!("/trades/{}", trade_id)).body(Json(TradeResponse {
-        id: new_trade.id,
-        trader_1_id: new_trade.trader_1.id,
-        trader_1_items: new_trade.trade_1_items.clone(),
-        trader_1_accept: new_trade.trade_1_accept,
-        trader_2_id: new_trade.trader_2.id,
-        trader_2_items: new_trade.trade_2_items.clone(),
-        trader_2_accept: new_trade.trade_2_accept,
-    }));
-    
-    let mut trades_lock = trades.lock().await;
-    trades_lock.insert(new_trade.id, new_trade);
-    drop(trades_lock);
-    
-    Ok(trade_res)
+}
+
+// POST /trades/gift - Gift items to another owner
+#[utoipa::path(
+    post,
+    path = "/trades/gift",
+    tags = ["trades"],
+    params(
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = GiftRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 201, description = "Gift offered, the giver is ready and the recipient only has to accept", body = TradeResponse),
+        (status = 400, description = "Invalid request data or item is not tradable", body = ErrorResponse),
+        (status = 404, description = "Possession or its item not found", body = ErrorResponse),
+        (status = 409, description = "Possession is already offered in a trade or held in escrow", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for the giver, or a trader is banned from trading", body = ErrorResponse)
+    )
+)]
+#[post("/gift", data = "<gift_data>")]
+pub async fn create_gift(
+    caller: Caller,
+    idempotency: Idempotency,
+    gift_data: IdempotentJson<GiftRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+    events: &State<TradeEvents>,
+) -> Result<Idempotent<Created<Json<TradeResponse>>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if !caller.can_act_for(gift_data.from_owner_id) {
+        return Err(forbidden_owner(gift_data.from_owner_id));
+    }
+
+    if gift_data.item_ids.is_empty() {
+        return Err(VentilError::BadRequest("A gift needs at least one item".to_string()));
+    }
+
+    let expires_at = expiry(config, gift_data.expires_in_secs)?;
+
+    // The trade, its offers and the giver's readiness are stored together
+    let txn = db.begin().await?;
+
+    let (giver, invited) = find_traders(&txn, gift_data.from_owner_id, &[gift_data.to_owner_id]).await?;
+    let mut trade = Trade::create(&txn, &giver, &invited, expires_at).await?;
+
+    for item_id in &gift_data.item_ids {
+        let (owner, possession) = find_offer(&txn, giver.id, *item_id).await?;
+        ensure_offerable(&txn, &possession).await?;
+        trade
+            .add_to_trade(&txn, &owner, &possession, gift_data.to_owner_id, possession.quantity)
+            .await?;
+    }
+
+    // Giving is all the giver does, the recipient accepts by getting ready and confirming
+    trade.change_trade_status(&txn, &giver).await?;
+    txn.commit().await?;
+    events.publish(trade.take_events());
+
+    Ok(Created::new(format!("/trades/{}", trade.id)).body(Json(TradeResponse::from(&trade))).into())
 }
 
 // POST /trades/<id>/add-item - Add item to trade
@@ -259,68 +1046,149 @@ pub async fn create_trade(
     path = "/trades/{id}/add-item",
     tags = ["trades"],
     params(
-        ("id" = u64, Path, description = "Trade identifier")
+        ("id" = i32, Path, description = "Trade identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
     ),
     request_body = TradeItemRequest,
+    security(("api_key" = [])),
     responses(
         (status = 200, description = "Item added to trade successfully", body = TradeResponse),
-        (status = 400, description = "Invalid request data", body = ApiResponse),
-        (status = 404, description = "Trade, owner or possession not found", body = ApiResponse)
+        (status = 400, description = "Invalid request data or quantity, or item is not tradable", body = ErrorResponse),
+        (status = 404, description = "Trade, owner, possession or its item not found", body = ErrorResponse),
+        (status = 409, description = "Possession is already offered in a trade or held in escrow, or the trade is closed or expired", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner, or the owner is banned from trading", body = ErrorResponse)
     )
 )]
 #[post("/<id>/add-item", data = "<item_data>")]
 pub async fn add_item_to_trade(
-    id: u64,
-    item_data: Json<TradeItemRequest>,
-    trades: &State<TradesMutex>,
+    caller: Caller,
+    idempotency: Idempotency,
+    id: TradeId,
+    item_data: IdempotentJson<TradeItemRequest>,
     database: &State<DatabaseConnection>,
-) -> Result<Json<TradeResponse>, Status> {
+    events: &State<TradeEvents>,
+) -> Result<Idempotent<Json<TradeResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
-    
-    // Find owner and possession
-    let owner = match Owner::find_by_id(item_data.owner_id).one(db).await {
-        Ok(Some(owner)) => owner,
-        _ => return Err(Status::NotFound),
-    };
-    
-    let possession = match Possession::find_by_id(item_data.item_id).one(db).await {
-        Ok(Some(possession)) => possession,
-        _ => return Err(Status::NotFound),
-    };
-    
-    // Verify ownership
-    if possession.owner != owner.id {
-        return Err(Status::BadRequest);
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
     }
-    
+
+    if !caller.can_act_for(item_data.owner_id) {
+        return Err(forbidden_owner(item_data.owner_id));
+    }
+
+    // The checks and the offer are one transaction, so a confirmation can not slip in between
+    let txn = db.begin().await?;
+
+    // Find owner and possession
+    let (owner, possession) = find_offer(&txn, item_data.owner_id, item_data.item_id).await?;
+    ensure_may_trade(&owner)?;
+    ensure_offerable(&txn, &possession).await?;
+
     // Find and update trade
-    let mut trades_lock = trades.lock().await;
-    let trade_opt= trades_lock.get_mut(&id);
-    
-    match trade_opt {
-        Some(trade) => {
-            
-            // Verify trader is part of trade
-            if trade.trader_1.id != owner.id && trade.trader_2.id != owner.id {
-                return Err(Status::BadRequest);
-            }
-            
-            // Add item to trade
-            if trade.add_to_trade(&owner, &possession) {
-                Ok(Json(TradeResponse {
-                    id: trade.id,
-                    trader_1_id: trade.trader_1.id,
-                    trader_1_items: trade.trade_1_items.clone(),
-                    trader_1_accept: trade.trade_1_accept,
-                    trader_2_id: trade.trader_2.id,
-                    trader_2_items: trade.trade_2_items.clone(),
-                    trader_2_accept: trade.trade_2_accept,
-                }))
-            } else {
-                Err(Status::InternalServerError)
-            }
+    let mut trade = Trade::find_by_id(&txn, id).await?.ok_or_else(|| not_found(id))?;
+
+    // Verify trader is part of trade
+    if !trade.has_trader(owner.id) {
+        return Err(VentilError::BadRequest(format!(
+            "Owner {} is not part of trade {}",
+            owner.id, trade.id
+        )));
+    }
+
+    let recipient = find_recipient(&trade, owner.id, item_data.to_owner_id)?;
+    let quantity = offered_quantity(&possession, item_data.quantity)?;
+
+    // Add item to trade, the unique offer index catches concurrent locks
+    trade.add_to_trade(&txn, &owner, &possession, recipient, quantity).await?;
+    txn.commit().await?;
+    events.publish(trade.take_events());
+
+    Ok(Json(TradeResponse::from(&trade)).into())
+}
+
+// Checks that a possession may be offered in a trade
+async fn ensure_offerable<C: ConnectionTrait>(
+    db: &C,
+    possession: &PossessionModel,
+) -> Result<(), VentilError> {
+    // Deleted items can not change hands anymore
+    let item = Item::find_by_id(possession.item)
+        .filter(item::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or_else(|| {
+            VentilError::NotFound(format!("Item {} of possession {} not found", possession.item, possession.id))
+        })?;
+
+    // Items marked as not tradable stay with their owner
+    if !item.tradable {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} is not tradable",
+            possession.id
+        )));
+    }
+
+    // A possession can only be offered in one trade at a time, and only once
+    if let Some(trade_id) = Trade::find_locking(db, possession.id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Possession {} is already offered in trade {}",
+            possession.id, trade_id
+        )));
+    }
+
+    // Possessions held in escrow can not be offered until they are delivered
+    if let Some(entry) = Escrow::find_holding(db, possession.id).await? {
+        return Err(VentilError::Conflict(format!(
+            "Possession {} is held in escrow until {}",
+            possession.id,
+            entry.release_at.to_rfc3339()
+        )));
+    }
+
+    Ok(())
+}
+
+// How much of the possession's stack is offered, all of it unless the request names a part
+fn offered_quantity(possession: &PossessionModel, quantity: Option<i32>) -> Result<i32, VentilError> {
+    match quantity {
+        None => Ok(possession.quantity),
+        Some(quantity) if (1..=possession.quantity).contains(&quantity) => Ok(quantity),
+        Some(quantity) => Err(VentilError::BadRequest(format!(
+            "Possession {} holds {}, it can not offer {}",
+            possession.id, possession.quantity, quantity
+        ))),
+    }
+}
+
+// The trader receiving an item of `owner_id`, only the sole other trader may be left out
+fn find_recipient(trade: &Trade, owner_id: i32, to_owner_id: Option<i32>) -> Result<i32, VentilError> {
+    let mut others = trade
+        .participants
+        .iter()
+        .map(|p| p.owner)
+        .filter(|id| *id != owner_id);
+
+    match to_owner_id {
+        Some(id) if id == owner_id => Err(VentilError::BadRequest(format!(
+            "Owner {} can not give items to themselves",
+            owner_id
+        ))),
+        Some(id) if others.any(|other| other == id) => Ok(id),
+        Some(id) => Err(VentilError::BadRequest(format!(
+            "Owner {} is not part of trade {}",
+            id, trade.id
+        ))),
+        None => match (others.next(), others.next()) {
+            (Some(id), None) => Ok(id),
+            _ => Err(VentilError::BadRequest(format!(
+                "Trade {} has several other traders, to_owner_id has to name the recipient",
+                trade.id
+            ))),
         },
-        None => Err(Status::NotFound),
     }
 }
 
@@ -330,152 +1198,406 @@ pub async fn add_item_to_trade(
     path = "/trades/{id}/remove-item",
     tags = ["trades"],
     params(
-        ("id" = u64, Path, description = "Trade identifier")
+        ("id" = i32, Path, description = "Trade identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
     ),
     request_body = TradeItemRequest,
+    security(("api_key" = [])),
     responses(
         (status = 200, description = "Item removed from trade successfully", body = TradeResponse),
-        (status = 400, description = "Invalid request data", body = ApiResponse),
-        (status = 404, description = "Trade, owner or possession not found", body = ApiResponse)
+        (status = 400, description = "Invalid request data", body = ErrorResponse),
+        (status = 409, description = "Trade is closed or expired", body = ErrorResponse),
+        (status = 404, description = "Trade, owner or possession not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner", body = ErrorResponse)
     )
 )]
 #[delete("/<id>/remove-item", data = "<item_data>")]
 pub async fn remove_item_from_trade(
-    id: u64,
-    item_data: Json<TradeItemRequest>,
-    trades: &State<TradesMutex>,
+    caller: Caller,
+    idempotency: Idempotency,
+    id: TradeId,
+    item_data: IdempotentJson<TradeItemRequest>,
     database: &State<DatabaseConnection>,
-) -> Result<Json<TradeResponse>, Status> {
+    events: &State<TradeEvents>,
+) -> Result<Idempotent<Json<TradeResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
-    
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if !caller.can_act_for(item_data.owner_id) {
+        return Err(forbidden_owner(item_data.owner_id));
+    }
+
+    // Removing the offer and withdrawing readiness happen together
+    let txn = db.begin().await?;
+
     // Find owner and possession
-    let owner = match Owner::find_by_id(item_data.owner_id).one(db).await {
-        Ok(Some(owner)) => owner,
-        _ => return Err(Status::NotFound),
-    };
-    
-    let possession = match Possession::find_by_id(item_data.item_id).one(db).await {
-        Ok(Some(possession)) => possession,
-        _ => return Err(Status::NotFound),
-    };
-    
+    let (owner, possession) = find_offer(&txn, item_data.owner_id, item_data.item_id).await?;
+
     // Find and update trade
-    let mut trades_lock = trades.lock().await;
-    let trade_opt= trades_lock.get_mut(&id);
-    
-    match trade_opt {
-        Some(trade) => {
-            
-            // Verify trader is part of trade
-            if trade.trader_1.id != owner.id && trade.trader_2.id != owner.id {
-                return Err(Status::BadRequest);
-            }
-            
-            // Remove item from trade
-            if trade.remove_from_trade(&owner, &possession) {
-                Ok(Json(TradeResponse {
-                    id: trade.id,
-                    trader_1_id: trade.trader_1.id,
-                    trader_1_items: trade.trade_1_items.clone(),
-                    trader_1_accept: trade.trade_1_accept,
-                    trader_2_id: trade.trader_2.id,
-                    trader_2_items: trade.trade_2_items.clone(),
-                    trader_2_accept: trade.trade_2_accept,
-                }))
-            } else {
-                Err(Status::BadRequest)
-            }
-        },
-        None => Err(Status::NotFound),
+    let mut trade = Trade::find_by_id(&txn, id).await?.ok_or_else(|| not_found(id))?;
+
+    // Verify trader is part of trade
+    if !trade.has_trader(owner.id) {
+        return Err(VentilError::BadRequest(format!(
+            "Owner {} is not part of trade {}",
+            owner.id, trade.id
+        )));
+    }
+
+    // Remove item from trade
+    if !trade.remove_from_trade(&txn, &owner, &possession).await? {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} is not offered in trade {}",
+            possession.id, trade.id
+        )));
     }
+    txn.commit().await?;
+    events.publish(trade.take_events());
+
+    Ok(Json(TradeResponse::from(&trade)).into())
+}
+
+// Looks up the owner and possession of an add or remove request
+async fn find_offer<C: ConnectionTrait>(
+    db: &C,
+    owner_id: i32,
+    item_id: i32,
+) -> Result<(OwnerModel, PossessionModel), VentilError> {
+    let owner = Owner::find_by_id(owner_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| owner_not_found(owner_id))?;
+
+    let possession = Possession::find_by_id(item_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| VentilError::NotFound(format!("Possession with id {} not found", item_id)))?;
+
+    // Verify ownership
+    if possession.owner != owner.id {
+        return Err(VentilError::BadRequest(format!(
+            "Possession {} is not owned by owner {}",
+            possession.id, owner.id
+        )));
+    }
+
+    Ok((owner, possession))
 }
 
-// PUT /trades/<id>/accept - Accept trade
+// PUT /trades/<id>/currency - Offer an amount of currency in a trade
+#[utoipa::path(
+    put,
+    path = "/trades/{id}/currency",
+    tags = ["trades"],
+    params(
+        ("id" = i32, Path, description = "Trade identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = TradeCurrencyRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Currency offer updated successfully", body = TradeResponse),
+        (status = 400, description = "Invalid request data, unknown currency or negative amount", body = ErrorResponse),
+        (status = 404, description = "Trade or owner not found", body = ErrorResponse),
+        (status = 409, description = "Owner does not hold the amount offered, or the trade is closed or expired", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner, or the owner is banned from trading", body = ErrorResponse)
+    )
+)]
+#[put("/<id>/currency", data = "<currency_data>")]
+pub async fn offer_currency(
+    caller: Caller,
+    idempotency: Idempotency,
+    id: TradeId,
+    currency_data: IdempotentJson<TradeCurrencyRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+    events: &State<TradeEvents>,
+) -> Result<Idempotent<Json<TradeResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if !caller.can_act_for(currency_data.owner_id) {
+        return Err(forbidden_owner(currency_data.owner_id));
+    }
+
+    ensure_currency(config, &currency_data.currency)?;
+    if currency_data.amount < 0 {
+        return Err(VentilError::BadRequest("Amount can not be negative".to_string()));
+    }
+
+    // The balance check and the offer are one transaction with the trade update
+    let txn = db.begin().await?;
+
+    let owner = Owner::find_by_id(currency_data.owner_id)
+        .one(&txn)
+        .await?
+        .ok_or_else(|| owner_not_found(currency_data.owner_id))?;
+    ensure_may_trade(&owner)?;
+
+    let mut trade = Trade::find_by_id(&txn, id).await?.ok_or_else(|| not_found(id))?;
+
+    if !trade.has_trader(owner.id) {
+        return Err(VentilError::BadRequest(format!(
+            "Owner {} is not part of trade {}",
+            owner.id, trade.id
+        )));
+    }
+
+    let recipient = find_recipient(&trade, owner.id, currency_data.to_owner_id)?;
+    let currency = &currency_data.currency;
+
+    // Everything the owner offers in the currency has to be covered, the amount is only taken at execution
+    let replaced: i64 = trade
+        .currency_offers
+        .iter()
+        .filter(|o| o.owner == owner.id && o.recipient == recipient && &o.currency == currency)
+        .map(|o| o.amount)
+        .sum();
+    let offered = trade
+        .currency_offered_by(owner.id, currency)
+        .saturating_sub(replaced)
+        .saturating_add(currency_data.amount);
+    let balance = ledger::balance(&txn, owner.id, currency).await?;
+    if offered > balance {
+        return Err(VentilError::Conflict(format!(
+            "Owner {} holds {} {}, it can not offer {}",
+            owner.id, balance, currency, offered
+        )));
+    }
+
+    trade
+        .offer_currency(&txn, &owner, recipient, currency, currency_data.amount)
+        .await?;
+    txn.commit().await?;
+    events.publish(trade.take_events());
+
+    Ok(Json(TradeResponse::from(&trade)).into())
+}
+
+// PUT /trades/<id>/accept - Mark the trade as ready
 #[utoipa::path(
     put,
     path = "/trades/{id}/accept",
     tags = ["trades"],
     params(
-        ("id" = u64, Path, description = "Trade identifier"),
-        ("owner_id" = i32, Query, description = "Owner accepting the trade")
+        ("id" = i32, Path, description = "Trade identifier"),
+        ("owner_id" = i32, Query, description = "Owner toggling their readiness"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
     ),
+    security(("api_key" = [])),
     responses(
-        (status = 200, description = "Trade status updated successfully", body = TradeResponse),
-        (status = 201, description = "Trade executed successfully", body = ApiResponse),
-        (status = 404, description = "Trade or owner not found", body = ApiResponse),
-        (status = 500, description = "Error executing trade", body = ApiResponse)
+        (status = 200, description = "Ready status updated", body = AcceptTradeResponse),
+        (status = 400, description = "Owner is not part of the trade", body = ErrorResponse),
+        (status = 404, description = "Trade or owner not found", body = ErrorResponse),
+        (status = 409, description = "Trade is closed or expired", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner, or the owner is banned from trading", body = ErrorResponse)
     )
 )]
 #[put("/<id>/accept?<owner_id>")]
 pub async fn accept_trade(
-    id: u64,
+    caller: Caller,
+    idempotency: Idempotency,
+    id: TradeId,
     owner_id: i32,
-    trades: &State<TradesMutex>,
     database: &State<DatabaseConnection>,
-) -> Result<Json<ApiResponse>, Status> {
+    config: &State<Config>,
+    events: &State<TradeEvents>,
+) -> Result<Idempotent<Json<AcceptTradeResponse>>, VentilError> {
     let db = database as &DatabaseConnection;
-    
-    // Find owner
-    let owner = match Owner::find_by_id(owner_id).one(db).await {
-        Ok(Some(owner)) => owner,
-        _ => return Err(Status::NotFound),
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if !caller.can_act_for(owner_id) {
+        return Err(forbidden_owner(owner_id));
+    }
+
+    let owner = Owner::find_by_id(owner_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| owner_not_found(owner_id))?;
+    ensure_may_trade(&owner)?;
+
+    // The trade and every participant's flags are saved together
+    let txn = db.begin().await?;
+
+    let mut trade = Trade::find_by_id(&txn, id).await?.ok_or_else(|| not_found(id))?;
+
+    if !trade.has_trader(owner.id) {
+        return Err(VentilError::BadRequest(format!(
+            "Owner {} is not part of trade {}",
+            owner.id, trade.id
+        )));
+    }
+
+    // Readiness only starts the cooldown, the trade runs once every recipient confirmed
+    trade.change_trade_status(&txn, &owner).await?;
+    txn.commit().await?;
+    events.publish(trade.take_events());
+
+    let message = match trade.ready_at {
+        Some(ready_at) => format!(
+            "Every trader is ready. Confirm with items hash {} from {}",
+            trade.items_hash(),
+            (ready_at + confirm_cooldown(config)).to_rfc3339()
+        ),
+        None => format!(
+            "Trade ready status updated. {}",
+            participant_status(&trade, |p| p.ready, "Ready", "Not ready")
+        ),
     };
-    
+
+    Ok(Json(AcceptTradeResponse { message }).into())
+}
+
+// PUT /trades/<id>/confirm - Confirm a trade every trader is ready for
+#[utoipa::path(
+    put,
+    path = "/trades/{id}/confirm",
+    tags = ["trades"],
+    params(
+        ("id" = i32, Path, description = "Trade identifier"),
+        ("owner_id" = i32, Query, description = "Owner confirming the trade"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = ConfirmTradeRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Confirmation stored or trade executed", body = AcceptTradeResponse),
+        (status = 400, description = "Owner is not part of the trade", body = ErrorResponse),
+        (status = 404, description = "Trade or owner not found", body = ErrorResponse),
+        (status = 409, description = "Trade is not ready, cooling down, changed items, is closed or expired, or an offered possession changed owner, was deleted or is offered in another trade", body = ErrorResponse),
+        (status = 500, description = "Error executing trade", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not act for this owner, or a trader is deleted or banned from trading", body = ErrorResponse)
+    )
+)]
+#[put("/<id>/confirm?<owner_id>", data = "<confirm_data>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn confirm_trade(
+    caller: Caller,
+    idempotency: Idempotency,
+    id: TradeId,
+    owner_id: i32,
+    confirm_data: IdempotentJson<ConfirmTradeRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+    events: &State<TradeEvents>,
+) -> Result<Idempotent<Json<AcceptTradeResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    if !caller.can_act_for(owner_id) {
+        return Err(forbidden_owner(owner_id));
+    }
+
+    // Find owner
+    let owner = Owner::find_by_id(owner_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| owner_not_found(owner_id))?;
+    ensure_may_trade(&owner)?;
+
+    // Confirmation, execution and closing of the trade happen in one transaction
+    let txn = db.begin().await?;
+
     // Find and update trade
-    let mut trades_lock = trades.lock().await;
-    let trade_opt= trades_lock.get_mut(&id);
-    
-    match trade_opt {
-        Some(trade) => {
-            
-            // Verify trader is part of trade
-            if trade.trader_1.id != owner.id && trade.trader_2.id != owner.id {
-                return Err(Status::BadRequest);
-            }
-            
-            // Change trade status
-            trade.change_trade_status(&owner);
-            
-            // Check if both traders have accepted
-            if trade.trade_1_accept && trade.trade_2_accept {
-                // Both traders have accepted, execute the trade
-                if let Err(_) = execute_trade_internal(trade, db).await {
-                    return Err(Status::InternalServerError);
-                }
-                
-                // Trade executed successfully, remove it from active trades
-                
-                // Get a new lock and remove the trade
-                if let Some(deleted) = trades_lock.remove(&id){
-                    return Ok(Json(ApiResponse {
-                        message: format!(
-                            "Trade between {} and {} executed successfully", 
-                            deleted.trader_1.id, 
-                            deleted.trader_2.id
-                        ),
-                    }));
-
-                }
-                else {
-                    return Ok(Json(ApiResponse {
-                            message: format!(
-                            "Trade executed successfully"
-                        ),
-                    }));
-                }
-                
+    let mut trade = Trade::find_by_id(&txn, id).await?.ok_or_else(|| not_found(id))?;
+
+    // Verify trader is part of trade
+    if !trade.has_trader(owner.id) {
+        return Err(VentilError::BadRequest(format!(
+            "Owner {} is not part of trade {}",
+            owner.id, trade.id
+        )));
+    }
+
+    trade
+        .confirm_trade(&txn, &owner, &confirm_data.items_hash, confirm_cooldown(config))
+        .await?;
+
+    // Check if every trader receiving something has confirmed
+    if trade.all_confirmed() {
+        // Everyone has confirmed, execute the trade.
+        // On failure the transaction is dropped and everything is rolled back.
+        let hold_until = hold_until(&txn, &trade, config).await?;
+        match execute_trade_internal(&trade, &txn, hold_until).await {
+            Ok(()) => {}
+            Err(TradeExecutionError::Database(err)) => return Err(err.into()),
+            Err(TradeExecutionError::MayNotTrade(err)) => return Err(err),
+            Err(err) => {
+                return Err(VentilError::Conflict(format!(
+                    "Trade {} could not be executed: {}",
+                    trade.id, err
+                )));
             }
-            
-            // Return the updated trade status
-            Ok(Json(ApiResponse {
-                message: format!(
-                    "Trade acceptance status updated. Trader 1: {}, Trader 2: {}", 
-                    if trade.trade_1_accept { "Accepted" } else { "Not accepted" },
-                    if trade.trade_2_accept { "Accepted" } else { "Not accepted" }
-                ),
-            }))
-        },
-        None => Err(Status::NotFound),
+        }
+
+        // Trade executed successfully, close it and release the offers
+        trade.close(&txn, TradeState::Executed, Utc::now()).await?;
+        txn.commit().await?;
+        events.publish(trade.take_events());
+
+        let message = match hold_until {
+            Some(release_at) => format!(
+                "Trade between {} executed, the items are held in escrow until {}",
+                trader_list(&trade),
+                release_at.to_rfc3339()
+            ),
+            None => format!("Trade between {} executed successfully", trader_list(&trade)),
+        };
+        return Ok(Json(AcceptTradeResponse { message }).into());
+    }
+
+    txn.commit().await?;
+    events.publish(trade.take_events());
+
+    Ok(Json(AcceptTradeResponse {
+        message: format!(
+            "Trade confirmation updated. {}",
+            participant_status(&trade, |p| p.confirmed, "Confirmed", "Not confirmed")
+        ),
+    }).into())
+}
+
+// Lists every trader of the trade with one of two labels, e.g. "Trader 3: Ready, Trader 5: Not ready"
+fn participant_status(
+    trade: &Trade,
+    flag: impl Fn(&Participant) -> bool,
+    yes: &str,
+    no: &str,
+) -> String {
+    trade
+        .participants
+        .iter()
+        .map(|p| format!("Trader {}: {}", p.owner, if flag(p) { yes } else { no }))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// "1 and 2" or "1, 2 and 3"
+fn trader_list(trade: &Trade) -> String {
+    let ids: Vec<String> = trade.participants.iter().map(|p| p.owner.to_string()).collect();
+    match ids.split_last() {
+        Some((last, rest)) if !rest.is_empty() => format!("{} and {}", rest.join(", "), last),
+        _ => ids.join(""),
     }
 }
 
@@ -485,29 +1607,39 @@ pub async fn accept_trade(
     path = "/trades/{id}",
     tags = ["trades"],
     params(
-        ("id" = u64, Path, description = "Trade identifier")
+        ("id" = i32, Path, description = "Trade identifier")
     ),
+    security(("api_key" = [])),
     responses(
         (status = 204, description = "Trade cancelled successfully"),
-        (status = 404, description = "Trade not found", body = ApiResponse)
+        (status = 404, description = "Trade not found", body = ErrorResponse),
+        (status = 409, description = "Trade is already closed or has expired", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller is not part of the trade", body = ErrorResponse)
     )
 )]
 #[delete("/<id>")]
 pub async fn cancel_trade(
-    id: u64,
-    trades: &State<TradesMutex>,
-) -> Result<Status, NotFound<Json<ApiResponse>>> {
-    let mut trades_lock = trades.lock().await;
-    
-    if trades_lock.remove(&id).is_some(){
-        
-        Ok(Status::NoContent)
-    }
-    else {
-        Err(NotFound(Json(ApiResponse {
-            message: format!("Trade with id {} not found", id),
-        })))
+    caller: Caller,
+    id: TradeId,
+    database: &State<DatabaseConnection>,
+    events: &State<TradeEvents>,
+) -> Result<Status, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    // Releasing the offers and saving the state happen together
+    let txn = db.begin().await?;
+
+    let mut trade = Trade::find_by_id(&txn, id).await?.ok_or_else(|| not_found(id))?;
+
+    if !can_access(&caller, &trade) {
+        return Err(VentilError::Forbidden(format!("Not allowed to cancel trade {}", id)));
     }
+
+    trade.close(&txn, TradeState::Cancelled, Utc::now()).await?;
+    txn.commit().await?;
+    events.publish(trade.take_events());
+    Ok(Status::NoContent)
 }
 
 // Create the OpenAPI documentation struct
@@ -515,15 +1647,22 @@ pub async fn cancel_trade(
 #[openapi(
     paths(
         get_all_trades,
+        get_trade_history,
+        get_trade_history_by_id,
+        rollback_trade,
         get_trade_by_id,
+        get_trade_events,
         create_trade,
+        create_gift,
         add_item_to_trade,
         remove_item_from_trade,
+        offer_currency,
         accept_trade,
+        confirm_trade,
         cancel_trade,
     ),
     components(
-        schemas(TradeResponse, CreateTradeRequest, TradeItemRequest, ApiResponse)
+        schemas(TradeResponse, TradeParticipantResponse, TradeOfferResponse, TradeState, TradeHistoryItemResponse, TradeHistoryResponse, TradeCurrencyOfferResponse, TradeHistoryCurrencyResponse, TradeCurrencyRequest, RollbackResponse, UnrecoverableItemResponse, EscrowResponse, EscrowState, TradeEvent, TradeEventKind, CreateTradeRequest, TradeItemRequest, GiftRequest, ConfirmTradeRequest, AcceptTradeResponse)
     ),
     tags(
         (name = "trades", description = "Trade management API")
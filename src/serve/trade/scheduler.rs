@@ -0,0 +1,73 @@
+use crate::serve::idempotency;
+use crate::serve::trade::escrow::Escrow;
+use crate::serve::trade::events::{TradeEvent, TradeEventKind, TradeEvents};
+use crate::serve::trade::logic::Trade;
+use rocket::fairing::AdHoc;
+use sea_orm::{DatabaseConnection, TransactionTrait};
+use std::time::Duration;
+
+// Starts a task on liftoff that runs the periodic trade jobs every `interval`:
+// expiring stale trades, which releases the possessions they offered and
+// ends the event streams following them, and delivering possessions whose
+// escrow hold is over. Idempotency keys past their TTL are swept along with them.
+pub fn fairing(interval: Duration) -> AdHoc {
+    AdHoc::on_liftoff("Trade scheduler", move |rocket| {
+        Box::pin(async move {
+            let (Some(db), Some(events)) = (
+                rocket.state::<DatabaseConnection>().cloned(),
+                rocket.state::<TradeEvents>().cloned(),
+            ) else {
+                rocket::error!("Trade scheduler is not running, no database or trade events are managed");
+                return;
+            };
+
+            tokio::spawn(async move {
+                let mut ticks = tokio::time::interval(interval);
+                loop {
+                    ticks.tick().await;
+                    if let Err(err) = expire_trades(&db, &events).await {
+                        rocket::error!("Could not expire stale trades: {}", err);
+                    }
+                    if let Err(err) = release_escrow(&db).await {
+                        rocket::error!("Could not release held possessions: {}", err);
+                    }
+                    if let Err(err) = idempotency::purge_expired(&db, chrono::Utc::now()).await {
+                        rocket::error!("Could not purge expired idempotency keys: {}", err);
+                    }
+                }
+            });
+        })
+    })
+}
+
+async fn expire_trades(
+    db: &DatabaseConnection,
+    events: &TradeEvents,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let txn = db.begin().await?;
+    let expired = Trade::expire_stale(&txn, chrono::Utc::now())
+        .await
+        .map_err(|err| err.to_string())?;
+    txn.commit().await?;
+
+    events.publish(expired.iter().map(|trade_id| TradeEvent {
+        trade_id: *trade_id,
+        kind: TradeEventKind::Expired,
+    }));
+
+    if !expired.is_empty() {
+        rocket::info!("Expired trades {:?}", expired);
+    }
+    Ok(())
+}
+
+async fn release_escrow(db: &DatabaseConnection) -> Result<(), Box<dyn std::error::Error>> {
+    let txn = db.begin().await?;
+    let released = Escrow::release_due(&txn, chrono::Utc::now()).await?;
+    txn.commit().await?;
+
+    if !released.is_empty() {
+        rocket::info!("Released escrow entries {:?}", released);
+    }
+    Ok(())
+}
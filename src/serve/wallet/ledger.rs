@@ -0,0 +1,245 @@
+use crate::db::entities::ledger_transaction::LedgerKind;
+use crate::db::entities::prelude::{LedgerEntry, LedgerTransaction, Wallet};
+use crate::db::entities::{ledger_entry, ledger_transaction, wallet};
+use chrono::Utc;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue, ColumnTrait, ConnectionTrait, DbErr, EntityTrait, QueryFilter,
+    QueryOrder, prelude::Expr,
+};
+use std::fmt;
+
+// Side of a ledger entry. The issuer is where credited currency comes from and
+// debited currency goes to, its balance is the only one that goes below 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Account {
+    Issuer,
+    Owner(i32),
+}
+
+impl Account {
+    fn owner(self) -> Option<i32> {
+        match self {
+            Account::Issuer => None,
+            Account::Owner(id) => Some(id),
+        }
+    }
+}
+
+impl From<Option<i32>> for Account {
+    fn from(owner: Option<i32>) -> Self {
+        owner.map_or(Account::Issuer, Account::Owner)
+    }
+}
+
+// An amount of one currency moving between two accounts
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transfer {
+    pub from: Account,
+    pub to: Account,
+    pub currency: String,
+    pub amount: i64,
+}
+
+#[derive(Debug)]
+pub enum LedgerError {
+    // Only positive amounts move
+    InvalidAmount(i64),
+    InsufficientFunds { owner: i32, currency: String },
+    // The balance would not fit anymore
+    Overflow { owner: i32, currency: String },
+    Database(DbErr),
+}
+
+impl From<DbErr> for LedgerError {
+    fn from(err: DbErr) -> Self {
+        LedgerError::Database(err)
+    }
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LedgerError::InvalidAmount(amount) => {
+                write!(f, "Amounts have to be positive, not {}", amount)
+            }
+            LedgerError::InsufficientFunds { owner, currency } => {
+                write!(f, "Owner {} does not hold enough {}", owner, currency)
+            }
+            LedgerError::Overflow { owner, currency } => {
+                write!(f, "The {} balance of owner {} can not grow that much", currency, owner)
+            }
+            LedgerError::Database(err) => write!(f, "Database error: {}", err),
+        }
+    }
+}
+
+// Balance of the owner in `currency`, 0 without a wallet
+pub async fn balance<C: ConnectionTrait>(db: &C, owner_id: i32, currency: &str) -> Result<i64, DbErr> {
+    let wallet = Wallet::find()
+        .filter(wallet::Column::Owner.eq(owner_id))
+        .filter(wallet::Column::Currency.eq(currency))
+        .one(db)
+        .await?;
+    Ok(wallet.map_or(0, |w| w.balance))
+}
+
+// Every wallet of the owner, by currency
+pub async fn wallets<C: ConnectionTrait>(db: &C, owner_id: i32) -> Result<Vec<wallet::Model>, DbErr> {
+    Wallet::find()
+        .filter(wallet::Column::Owner.eq(owner_id))
+        .order_by_asc(wallet::Column::Currency)
+        .all(db)
+        .await
+}
+
+// Books `transfer` as a new ledger transaction with one entry per side and updates both wallets.
+// Run it in a transaction, a failed transfer leaves the entries it already wrote behind.
+pub async fn post<C: ConnectionTrait>(
+    db: &C,
+    kind: LedgerKind,
+    transfer: &Transfer,
+    trade_history: Option<i32>,
+    memo: Option<String>,
+) -> Result<ledger_transaction::Model, LedgerError> {
+    if transfer.amount <= 0 {
+        return Err(LedgerError::InvalidAmount(transfer.amount));
+    }
+
+    if let Account::Owner(owner) = transfer.from {
+        withdraw(db, owner, &transfer.currency, transfer.amount).await?;
+    }
+    if let Account::Owner(owner) = transfer.to {
+        deposit(db, owner, &transfer.currency, transfer.amount).await?;
+    }
+
+    let transaction = ledger_transaction::ActiveModel {
+        kind: ActiveValue::set(kind),
+        trade_history: ActiveValue::set(trade_history),
+        memo: ActiveValue::set(memo),
+        created_at: ActiveValue::set(Utc::now()),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+
+    for (account, amount) in [(transfer.from, -transfer.amount), (transfer.to, transfer.amount)] {
+        ledger_entry::ActiveModel {
+            ledger_transaction: ActiveValue::set(transaction.id),
+            owner: ActiveValue::set(account.owner()),
+            currency: ActiveValue::set(transfer.currency.clone()),
+            amount: ActiveValue::set(amount),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?;
+    }
+
+    Ok(transaction)
+}
+
+// The balance check and the update are one statement, so concurrent debits can not overdraw
+async fn withdraw<C: ConnectionTrait>(
+    db: &C,
+    owner: i32,
+    currency: &str,
+    amount: i64,
+) -> Result<(), LedgerError> {
+    let updated = Wallet::update_many()
+        .col_expr(wallet::Column::Balance, Expr::col(wallet::Column::Balance).sub(amount))
+        .filter(wallet::Column::Owner.eq(owner))
+        .filter(wallet::Column::Currency.eq(currency))
+        .filter(wallet::Column::Balance.gte(amount))
+        .exec(db)
+        .await?;
+
+    if updated.rows_affected == 0 {
+        return Err(LedgerError::InsufficientFunds {
+            owner,
+            currency: currency.to_string(),
+        });
+    }
+    Ok(())
+}
+
+// Opens the wallet on the first deposit in a currency
+async fn deposit<C: ConnectionTrait>(
+    db: &C,
+    owner: i32,
+    currency: &str,
+    amount: i64,
+) -> Result<(), LedgerError> {
+    let updated = Wallet::update_many()
+        .col_expr(wallet::Column::Balance, Expr::col(wallet::Column::Balance).add(amount))
+        .filter(wallet::Column::Owner.eq(owner))
+        .filter(wallet::Column::Currency.eq(currency))
+        .filter(wallet::Column::Balance.lte(i64::MAX - amount))
+        .exec(db)
+        .await?;
+    if updated.rows_affected > 0 {
+        return Ok(());
+    }
+
+    let existing = Wallet::find()
+        .filter(wallet::Column::Owner.eq(owner))
+        .filter(wallet::Column::Currency.eq(currency))
+        .one(db)
+        .await?;
+    if existing.is_some() {
+        return Err(LedgerError::Overflow {
+            owner,
+            currency: currency.to_string(),
+        });
+    }
+
+    wallet::ActiveModel {
+        owner: ActiveValue::set(owner),
+        currency: ActiveValue::set(currency.to_string()),
+        balance: ActiveValue::set(amount),
+        ..Default::default()
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+// The transfers of ledger transactions, read back from their entries
+pub async fn transfers<C: ConnectionTrait>(
+    db: &C,
+    transactions: Vec<ledger_transaction::Model>,
+) -> Result<Vec<(ledger_transaction::Model, Transfer)>, DbErr> {
+    let entries = LedgerEntry::find()
+        .filter(ledger_entry::Column::LedgerTransaction.is_in(transactions.iter().map(|t| t.id)))
+        .all(db)
+        .await?;
+
+    Ok(transactions
+        .into_iter()
+        .filter_map(|transaction| {
+            let mut sides = entries.iter().filter(|e| e.ledger_transaction == transaction.id);
+            let (Some(a), Some(b)) = (sides.next(), sides.next()) else {
+                return None;
+            };
+            let (from, to) = if a.amount < 0 { (a, b) } else { (b, a) };
+            let transfer = Transfer {
+                from: from.owner.into(),
+                to: to.owner.into(),
+                currency: to.currency.clone(),
+                amount: to.amount,
+            };
+            Some((transaction, transfer))
+        })
+        .collect())
+}
+
+// Currency that changed hands in recorded trades, and the reversals of it, oldest first
+pub async fn trade_transfers<C: ConnectionTrait>(
+    db: &C,
+    trade_history_ids: impl IntoIterator<Item = i32>,
+) -> Result<Vec<(ledger_transaction::Model, Transfer)>, DbErr> {
+    let transactions = LedgerTransaction::find()
+        .filter(ledger_transaction::Column::TradeHistory.is_in(trade_history_ids))
+        .order_by_asc(ledger_transaction::Column::Id)
+        .all(db)
+        .await?;
+    transfers(db, transactions).await
+}
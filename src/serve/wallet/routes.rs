@@ -0,0 +1,383 @@
+use crate::config::Config;
+use crate::db::entities::ledger_transaction::LedgerKind;
+use crate::db::entities::{ledger_entry, ledger_transaction, owner, prelude::*};
+use crate::serve::auth::{Admin, Caller};
+use crate::serve::error::{ErrorResponse, VentilError};
+use crate::serve::idempotency::{Idempotency, Idempotent, IdempotentJson};
+use crate::serve::pagination::{Page, PageRequest, SortOrder};
+use crate::serve::wallet::ledger::{self, Account, Transfer};
+use chrono::{DateTime, Utc};
+use rocket::{
+    Build, FromForm, Rocket, State, get, post,
+    routes,
+    serde::{Deserialize, Serialize, json::Json},
+};
+use sea_orm::{
+    ColumnTrait, ConnectionTrait, DatabaseConnection, EntityTrait, LoaderTrait, QueryFilter,
+    TransactionTrait,
+};
+use utoipa::{IntoParams, OpenApi, ToSchema};
+
+pub trait WalletRoutes {
+    fn mount_wallets(self) -> Self;
+}
+
+impl WalletRoutes for Rocket<Build> {
+    fn mount_wallets(self) -> Self {
+        self.mount(
+            "/wallets",
+            routes![get_wallet, get_ledger, credit_wallet, debit_wallet],
+        )
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct BalanceResponse {
+    pub currency: String,
+    pub balance: i64,
+}
+
+// Every configured currency of the owner, including the ones never held
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct WalletResponse {
+    pub owner_id: i32,
+    pub balances: Vec<BalanceResponse>,
+}
+
+// One side of a ledger transaction as seen from the owner's wallet
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct LedgerEntryResponse {
+    pub id: i32,
+    pub transaction_id: i32,
+    pub kind: LedgerKind,
+    pub currency: String,
+    /// Positive when the owner received the amount
+    pub amount: i64,
+    pub trade_history_id: Option<i32>,
+    pub memo: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Request model for crediting or debiting a wallet
+#[derive(Deserialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct WalletChangeRequest {
+    pub currency: String,
+    pub amount: i64,
+    /// Reason kept with the ledger transaction, e.g. the purchase or event it belongs to
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+#[serde(crate = "rocket::serde")]
+pub struct WalletChangeResponse {
+    pub transaction_id: i32,
+    pub currency: String,
+    /// Balance of the currency after the change
+    pub balance: i64,
+}
+
+// Query of the ledger of a wallet
+#[derive(FromForm, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct LedgerQuery {
+    /// Maximum number of entries to return, at most 1000
+    pub limit: Option<u64>,
+    /// Return entries after this id, taken from `next` of the previous page
+    pub after: Option<i32>,
+    /// Sort by id ascending or descending
+    #[param(inline)]
+    pub order: Option<SortOrder>,
+    /// Only entries in this currency
+    pub currency: Option<String>,
+}
+
+fn owner_not_found(id: i32) -> VentilError {
+    VentilError::NotFound(format!("Owner with id {} not found", id))
+}
+
+// Only currencies named in the configuration can be held
+pub fn ensure_currency(config: &Config, currency: &str) -> Result<(), VentilError> {
+    if !config.currencies.iter().any(|c| c == currency) {
+        return Err(VentilError::BadRequest(format!("Currency {} is not configured", currency)));
+    }
+    Ok(())
+}
+
+async fn find_active_owner<C: ConnectionTrait>(db: &C, id: i32) -> Result<owner::Model, VentilError> {
+    Owner::find_by_id(id)
+        .filter(owner::Column::DeletedAt.is_null())
+        .one(db)
+        .await?
+        .ok_or_else(|| owner_not_found(id))
+}
+
+// Credits and debits are transfers between the issuer and the owner
+async fn change_wallet(
+    db: &DatabaseConnection,
+    config: &Config,
+    owner_id: i32,
+    kind: LedgerKind,
+    change: &WalletChangeRequest,
+) -> Result<WalletChangeResponse, VentilError> {
+    ensure_currency(config, &change.currency)?;
+    if change.amount <= 0 {
+        return Err(VentilError::BadRequest("Amount must be positive".to_string()));
+    }
+    if change.memo.as_ref().is_some_and(|memo| memo.len() > 255) {
+        return Err(VentilError::BadRequest("Memos hold at most 255 characters".to_string()));
+    }
+
+    let (from, to) = match kind {
+        LedgerKind::Debit => (Account::Owner(owner_id), Account::Issuer),
+        _ => (Account::Issuer, Account::Owner(owner_id)),
+    };
+    let transfer = Transfer {
+        from,
+        to,
+        currency: change.currency.clone(),
+        amount: change.amount,
+    };
+
+    let txn = db.begin().await?;
+    find_active_owner(&txn, owner_id).await?;
+    let transaction = ledger::post(&txn, kind, &transfer, None, change.memo.clone()).await?;
+    let balance = ledger::balance(&txn, owner_id, &change.currency).await?;
+    txn.commit().await?;
+
+    Ok(WalletChangeResponse {
+        transaction_id: transaction.id,
+        currency: change.currency.clone(),
+        balance,
+    })
+}
+
+// GET /wallets/<owner_id> - Get the balances of an owner
+#[utoipa::path(
+    get,
+    path = "/wallets/{owner_id}",
+    tags = ["wallets"],
+    params(
+        ("owner_id" = i32, Path, description = "Owner identifier")
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Balances found successfully", body = WalletResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not view this owner's wallet", body = ErrorResponse)
+    )
+)]
+#[get("/<owner_id>")]
+pub async fn get_wallet(
+    caller: Caller,
+    owner_id: i32,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+) -> Result<Json<WalletResponse>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    if !caller.can_act_for(owner_id) {
+        return Err(VentilError::Forbidden(format!(
+            "Not allowed to view the wallet of owner {}",
+            owner_id
+        )));
+    }
+
+    let owner = Owner::find_by_id(owner_id)
+        .one(db)
+        .await?
+        .ok_or_else(|| owner_not_found(owner_id))?;
+
+    // Currencies that are no longer configured still show while they hold something
+    let wallets = ledger::wallets(db, owner.id).await?;
+    let mut balances: Vec<BalanceResponse> = config
+        .currencies
+        .iter()
+        .map(|currency| BalanceResponse {
+            currency: currency.clone(),
+            balance: wallets
+                .iter()
+                .find(|w| &w.currency == currency)
+                .map_or(0, |w| w.balance),
+        })
+        .collect();
+    for wallet in wallets {
+        if !config.currencies.contains(&wallet.currency) && wallet.balance != 0 {
+            balances.push(BalanceResponse {
+                currency: wallet.currency,
+                balance: wallet.balance,
+            });
+        }
+    }
+
+    Ok(Json(WalletResponse { owner_id: owner.id, balances }))
+}
+
+// GET /wallets/<owner_id>/ledger - List the ledger entries of an owner
+#[utoipa::path(
+    get,
+    path = "/wallets/{owner_id}/ledger",
+    tags = ["wallets"],
+    params(
+        ("owner_id" = i32, Path, description = "Owner identifier"),
+        LedgerQuery
+    ),
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Ledger entries found successfully", body = Page<LedgerEntryResponse>),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Caller may not view this owner's wallet", body = ErrorResponse)
+    )
+)]
+#[get("/<owner_id>/ledger?<query..>")]
+pub async fn get_ledger(
+    caller: Caller,
+    owner_id: i32,
+    query: LedgerQuery,
+    database: &State<DatabaseConnection>,
+) -> Result<Json<Page<LedgerEntryResponse>>, VentilError> {
+    let db = database as &DatabaseConnection;
+
+    if !caller.can_act_for(owner_id) {
+        return Err(VentilError::Forbidden(format!(
+            "Not allowed to view the wallet of owner {}",
+            owner_id
+        )));
+    }
+
+    if Owner::find_by_id(owner_id).one(db).await?.is_none() {
+        return Err(owner_not_found(owner_id));
+    }
+
+    let page = PageRequest::new(query.limit, query.after, query.order);
+    let mut select = LedgerEntry::find().filter(ledger_entry::Column::Owner.eq(owner_id));
+    if let Some(currency) = query.currency {
+        select = select.filter(ledger_entry::Column::Currency.eq(currency));
+    }
+
+    let entries = page.apply(select, ledger_entry::Column::Id).all(db).await?;
+    let (entries, next) = page.finish(entries, |e| e.id);
+    let transactions = entries.load_one(LedgerTransaction, db).await?;
+
+    let items = entries
+        .into_iter()
+        .zip(transactions)
+        .filter_map(|(entry, transaction)| {
+            let transaction: ledger_transaction::Model = transaction?;
+            Some(LedgerEntryResponse {
+                id: entry.id,
+                transaction_id: transaction.id,
+                kind: transaction.kind,
+                currency: entry.currency,
+                amount: entry.amount,
+                trade_history_id: transaction.trade_history,
+                memo: transaction.memo,
+                created_at: transaction.created_at,
+            })
+        })
+        .collect();
+
+    Ok(Json(Page { items, next }))
+}
+
+// POST /wallets/<owner_id>/credit - Issue currency to an owner
+#[utoipa::path(
+    post,
+    path = "/wallets/{owner_id}/credit",
+    tags = ["wallets"],
+    params(
+        ("owner_id" = i32, Path, description = "Owner identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = WalletChangeRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Wallet credited successfully", body = WalletChangeResponse),
+        (status = 400, description = "Unknown currency, amount is not positive or memo is too long", body = ErrorResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 409, description = "The balance can not grow that much", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/<owner_id>/credit", data = "<change_data>")]
+pub async fn credit_wallet(
+    _admin: Admin,
+    idempotency: Idempotency,
+    owner_id: i32,
+    change_data: IdempotentJson<WalletChangeRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+) -> Result<Idempotent<Json<WalletChangeResponse>>, VentilError> {
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    let response = change_wallet(database, config, owner_id, LedgerKind::Credit, &change_data).await?;
+    Ok(Json(response).into())
+}
+
+// POST /wallets/<owner_id>/debit - Take currency back from an owner
+#[utoipa::path(
+    post,
+    path = "/wallets/{owner_id}/debit",
+    tags = ["wallets"],
+    params(
+        ("owner_id" = i32, Path, description = "Owner identifier"),
+        ("Idempotency-Key" = Option<String>, Header, description = "Retries with the same key get the first response back")
+    ),
+    request_body = WalletChangeRequest,
+    security(("api_key" = [])),
+    responses(
+        (status = 200, description = "Wallet debited successfully", body = WalletChangeResponse),
+        (status = 400, description = "Unknown currency, amount is not positive or memo is too long", body = ErrorResponse),
+        (status = 404, description = "Owner not found", body = ErrorResponse),
+        (status = 409, description = "The owner does not hold enough of the currency", body = ErrorResponse),
+        (status = 401, description = "Missing or invalid API key", body = ErrorResponse),
+        (status = 403, description = "Admin key required", body = ErrorResponse)
+    )
+)]
+#[post("/<owner_id>/debit", data = "<change_data>")]
+pub async fn debit_wallet(
+    _admin: Admin,
+    idempotency: Idempotency,
+    owner_id: i32,
+    change_data: IdempotentJson<WalletChangeRequest>,
+    database: &State<DatabaseConnection>,
+    config: &State<Config>,
+) -> Result<Idempotent<Json<WalletChangeResponse>>, VentilError> {
+    // A retry gets the response of the first request
+    if let Some(replayed) = idempotency.replayed() {
+        return Ok(replayed);
+    }
+
+    let response = change_wallet(database, config, owner_id, LedgerKind::Debit, &change_data).await?;
+    Ok(Json(response).into())
+}
+
+// Create the OpenAPI documentation struct
+#[derive(OpenApi)]
+#[openapi(
+    paths(get_wallet, get_ledger, credit_wallet, debit_wallet),
+    components(
+        schemas(
+            WalletResponse,
+            BalanceResponse,
+            LedgerEntryResponse,
+            WalletChangeRequest,
+            WalletChangeResponse,
+            LedgerKind
+        )
+    ),
+    tags(
+        (name = "wallets", description = "Wallet and currency ledger API")
+    )
+)]
+pub struct WalletApiDoc;